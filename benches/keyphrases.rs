@@ -0,0 +1,59 @@
+//! Compares keyphrase detection with one scan per keyphrase against the precompiled automaton
+//!
+//! Run with `cargo bench --bench keyphrases`.
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use std::collections::HashMap;
+use sw_catcher::casefold::Normalization;
+use sw_catcher::config::KeyphraseEntry;
+use sw_catcher::keyphrase::{
+    detect_all_keyphrases, parse_keyphrase_map, KeyphraseMatchingStrategy, KeyphraseProcessingOptions, KeyphraseSet,
+};
+
+/// A config-sized keyphrase map: `count` commands like "open app 17"
+fn keyphrase_map(count: usize) -> HashMap<String, KeyphraseEntry> {
+    (0..count)
+        .map(|i| (format!("open app {}", i), KeyphraseEntry::Action(format!("App{}", i))))
+        .collect()
+}
+
+/// A dictated paragraph of a few hundred words with a handful of keyphrases in it
+fn dictation() -> String {
+    let sentence = "The meeting went well and we agreed on the plan for next quarter. ";
+    let mut text = sentence.repeat(20);
+    for i in [3, 42, 117, 199] {
+        text.push_str(&format!("Open app {} and then carry on writing. ", i));
+        text.push_str(&sentence.repeat(5));
+    }
+    text
+}
+
+fn bench_detection(c: &mut Criterion) {
+    let text = dictation();
+    let mut group = c.benchmark_group("detect_keyphrases");
+
+    for strategy in [KeyphraseMatchingStrategy::Simple, KeyphraseMatchingStrategy::WholeWord] {
+        let options = KeyphraseProcessingOptions {
+            matching_strategy: strategy.clone(),
+            ..Default::default()
+        };
+
+        for count in [20, 200] {
+            let keyphrases = parse_keyphrase_map(&keyphrase_map(count), &strategy).unwrap();
+            let set = KeyphraseSet::new(keyphrases.clone(), &strategy, Normalization::Nfc);
+            let label = format!("{:?}/{}", strategy, count);
+
+            group.bench_with_input(BenchmarkId::new("scan_per_keyphrase", &label), &text, |b, text| {
+                b.iter(|| detect_all_keyphrases(black_box(text), &keyphrases, &options))
+            });
+            group.bench_with_input(BenchmarkId::new("automaton", &label), &text, |b, text| {
+                b.iter(|| set.detect(black_box(text), &options))
+            });
+        }
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_detection);
+criterion_main!(benches);
@@ -1,20 +1,148 @@
-use log::{debug, info};
+use crate::exec::{run_exec, ExecAction};
+use crate::macros::{run_macro, Macro};
+use log::{debug, info, warn};
+use regex::Regex;
 use std::process::Command;
+use std::sync::OnceLock;
 
 /// Action type for keyphrases
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub enum ActionType {
     OpenApplication(String),
     OpenUrl(String),
+    /// Several actions run in order
+    Sequence(Vec<ActionType>),
+    /// Steps with delays and variables, see `macros`
+    Macro(Macro),
+    /// A command run without a shell, see `exec`
+    Exec(ExecAction),
+    #[default]
     None,
 }
 
+/// What a `{query}` placeholder captures from the text after its keyphrase
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum QueryCapture {
+    /// The rest of the sentence (`{query}` or `{query:sentence}`)
+    Sentence,
+    /// The next N words (`{query:words:N}`)
+    Words(usize),
+    /// Everything up to the next keyphrase (`{query:segment}`)
+    UntilNextKeyphrase,
+}
+
+/// Matches a `{query}` placeholder and its optional capture mode
+fn query_placeholder() -> &'static Regex {
+    static PLACEHOLDER: OnceLock<Regex> = OnceLock::new();
+    PLACEHOLDER.get_or_init(|| {
+        Regex::new(r"\{query(?::(sentence|segment|words:(\d+)))?\}").expect("placeholder regex is valid")
+    })
+}
+
+/// Matches any `{name}` placeholder, including the `{query:...}` forms
+fn any_placeholder() -> &'static Regex {
+    static PLACEHOLDER: OnceLock<Regex> = OnceLock::new();
+    PLACEHOLDER.get_or_init(|| {
+        Regex::new(r"\{(?:(\w+)|query:(?:sentence|segment|words:\d+))\}").expect("placeholder regex is valid")
+    })
+}
+
+/// Replace `{name}` placeholders with the variables' values in a single pass
+///
+/// Substituted values are never scanned again, so text that happens to contain a
+/// placeholder stays as it is. Later variables win over earlier ones with the same name,
+/// and placeholders without a variable are left alone.
+pub(crate) fn fill_placeholders(template: &str, variables: &[(String, String)], encode: bool) -> String {
+    any_placeholder()
+        .replace_all(template, |caps: &regex::Captures| {
+            let name = caps.get(1).map_or("query", |m| m.as_str());
+            match variables.iter().rev().find(|(variable, _)| variable == name) {
+                Some((_, value)) if encode => urlencoding::encode(value).into_owned(),
+                Some((_, value)) => value.clone(),
+                None => caps[0].to_string(),
+            }
+        })
+        .into_owned()
+}
+
+/// Whether a value may be put into an application name
+///
+/// On Windows the name goes through `cmd /C start`, so dictated text must not bring
+/// shell syntax with it: only letters, digits, spaces, `.`, `-` and `_` are allowed.
+fn is_safe_app_value(value: &str) -> bool {
+    value.chars().all(|c| c.is_alphanumeric() || matches!(c, ' ' | '.' | '-' | '_'))
+}
+
+/// Fill an application name's placeholders, or `None` if a value isn't safe to use there
+fn fill_app_placeholders(app: &str, variables: &[(String, String)]) -> Option<String> {
+    let unsafe_value = any_placeholder().captures_iter(app).find_map(|caps| {
+        let name = caps.get(1).map_or("query", |m| m.as_str());
+        variables
+            .iter()
+            .rev()
+            .find(|(variable, _)| variable == name)
+            .filter(|(_, value)| !is_safe_app_value(value))
+    });
+
+    match unsafe_value {
+        Some((name, value)) => {
+            warn!("Not opening application '{}': {{{}}} is \"{}\", which can't be used in an application name", app, name, value);
+            None
+        }
+        None => Some(fill_placeholders(app, variables, false)),
+    }
+}
+
+impl ActionType {
+    /// The capture asked for by a `{query}` placeholder in the action, if it has one
+    pub fn query_capture(&self) -> Option<QueryCapture> {
+        let target = match self {
+            ActionType::OpenApplication(target) | ActionType::OpenUrl(target) => target,
+            ActionType::Sequence(actions) => return actions.iter().find_map(ActionType::query_capture),
+            ActionType::Macro(steps) => return steps.query_capture(),
+            ActionType::Exec(exec) => return exec.query_capture(),
+            ActionType::None => return None,
+        };
+        query_capture_in(target)
+    }
+
+    /// Substitute captured text for the `{query}` placeholder
+    ///
+    /// The text is URL-encoded for URLs. Applications only take plain words, see
+    /// `with_captures`.
+    pub fn with_query(&self, query: &str) -> ActionType {
+        self.with_captures(&[("query".to_string(), query.to_string())])
+    }
+
+    /// Substitute variables, such as named regex captures, for their `{name}` placeholders
+    ///
+    /// All placeholders are filled in one pass (see `fill_placeholders`). Macros keep the
+    /// variables and fill them in along with their own when each step runs. An application
+    /// whose name would take a value with anything but plain words is not opened at all.
+    pub fn with_captures(&self, captures: &[(String, String)]) -> ActionType {
+        match self {
+            ActionType::OpenUrl(url) => ActionType::OpenUrl(fill_placeholders(url, captures, true)),
+            ActionType::OpenApplication(app) => match fill_app_placeholders(app, captures) {
+                Some(app) => ActionType::OpenApplication(app),
+                None => ActionType::None,
+            },
+            ActionType::Sequence(actions) => {
+                ActionType::Sequence(actions.iter().map(|action| action.with_captures(captures)).collect())
+            }
+            ActionType::Macro(steps) => ActionType::Macro(steps.with_variables(captures)),
+            ActionType::Exec(exec) => {
+                ActionType::Exec(exec.map_templates(&|template| fill_placeholders(template, captures, false)))
+            }
+            ActionType::None => ActionType::None,
+        }
+    }
+}
+
 /// Parse a string into an ActionType
 pub fn parse_action(action_str: &str) -> ActionType {
     // Check for special action keywords first
-    match action_str.to_lowercase().as_str() {
-        "" => return ActionType::None,
-        _ => {} // Continue with other checks
+    if action_str.is_empty() {
+        return ActionType::None;
     }
     
     // URI detection - look for scheme:something or scheme://something pattern
@@ -37,12 +165,52 @@ pub fn parse_action(action_str: &str) -> ActionType {
     ActionType::OpenApplication(action_str.to_string())
 }
 
+/// The capture asked for by a `{query}` placeholder in a URL, application or template
+pub(crate) fn query_capture_in(target: &str) -> Option<QueryCapture> {
+    let captures = query_placeholder().captures(target)?;
+    Some(match captures.get(1).map(|m| m.as_str()) {
+        Some("segment") => QueryCapture::UntilNextKeyphrase,
+        Some(_) if captures.get(2).is_some() => QueryCapture::Words(captures[2].parse().unwrap_or(1).max(1)),
+        _ => QueryCapture::Sentence,
+    })
+}
+
+/// Parse a list of action strings, which run in order when there is more than one
+pub fn parse_actions(actions: &[String]) -> ActionType {
+    let mut parsed: Vec<ActionType> = actions
+        .iter()
+        .map(|action| parse_action(action))
+        .filter(|action| !matches!(action, ActionType::None))
+        .collect();
+
+    match parsed.len() {
+        0 => ActionType::None,
+        1 => parsed.remove(0),
+        _ => ActionType::Sequence(parsed),
+    }
+}
+
 /// Execute an action based on its type
 pub fn execute_action(action: &ActionType) -> std::result::Result<(), Box<dyn std::error::Error>> {
+    execute_action_output(action).map(|_| ())
+}
+
+/// Execute an action, returning text to copy instead of the dictated text if it produced any
+///
+/// Only commands with `replace_text` produce text; when several do, the last one wins.
+pub fn execute_action_output(action: &ActionType) -> std::result::Result<Option<String>, Box<dyn std::error::Error>> {
     match action {
-        ActionType::OpenApplication(app) => open_application(app),
-        ActionType::OpenUrl(url) => open_url(url),
-        ActionType::None => Ok(()),
+        ActionType::OpenApplication(app) => open_application(app).map(|_| None),
+        ActionType::OpenUrl(url) => open_url(url).map(|_| None),
+        ActionType::Sequence(actions) => actions
+            .iter()
+            .try_fold(None, |replacement, action| Ok(execute_action_output(action)?.or(replacement))),
+        ActionType::Macro(steps) => run_macro(steps),
+        ActionType::Exec(exec) => {
+            let output = run_exec(exec)?;
+            Ok(exec.replace_text.then_some(output))
+        }
+        ActionType::None => Ok(None),
     }
 }
 
@@ -100,6 +268,62 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_query_placeholder() {
+        let action = parse_action("https://www.google.com/search?q={query}");
+        assert_eq!(action.query_capture(), Some(QueryCapture::Sentence));
+        match action.with_query("rust & cargo") {
+            ActionType::OpenUrl(url) => assert_eq!(url, "https://www.google.com/search?q=rust%20%26%20cargo"),
+            _ => panic!("Expected OpenUrl"),
+        }
+
+        assert_eq!(parse_action("https://x.test/{query:words:3}").query_capture(), Some(QueryCapture::Words(3)));
+        assert_eq!(parse_action("https://x.test/{query:segment}").query_capture(), Some(QueryCapture::UntilNextKeyphrase));
+        assert_eq!(parse_action("https://example.com").query_capture(), None);
+
+        let captures = vec![("n".to_string(), "5".to_string())];
+        match parse_action("https://example.com/remind?minutes={n}").with_captures(&captures) {
+            ActionType::OpenUrl(url) => assert_eq!(url, "https://example.com/remind?minutes=5"),
+            _ => panic!("Expected OpenUrl"),
+        }
+    }
+
+    #[test]
+    fn test_captured_placeholders_are_not_expanded() {
+        // A captured value that looks like a placeholder is inserted literally, whatever the order
+        let captures = vec![
+            ("name".to_string(), "{file}".to_string()),
+            ("file".to_string(), "/tmp/meta.json".to_string()),
+            ("query".to_string(), "{name} {text}".to_string()),
+        ];
+        assert_eq!(
+            fill_placeholders("notes {name} {file} {query:words:2} {unknown}", &captures, false),
+            "notes {file} /tmp/meta.json {name} {text} {unknown}"
+        );
+        match parse_action("https://x.test/?q={name}").with_captures(&captures) {
+            ActionType::OpenUrl(url) => assert_eq!(url, "https://x.test/?q=%7Bfile%7D"),
+            _ => panic!("Expected OpenUrl"),
+        }
+    }
+
+    #[test]
+    fn test_app_captures_are_plain_words() {
+        let capture = |value: &str| vec![("query".to_string(), value.to_string())];
+        let action = parse_action("Notes {query:words:2}");
+
+        match action.with_captures(&capture("Groceries list")) {
+            ActionType::OpenApplication(app) => assert_eq!(app, "Notes Groceries list"),
+            _ => panic!("Expected OpenApplication"),
+        }
+        // Shell syntax in dictated text never reaches the application launcher
+        for value in ["x & calc", "a|b", "^x", "\"quoted\""] {
+            assert!(matches!(action.with_captures(&capture(value)), ActionType::None), "{}", value);
+        }
+        // Unused variables don't matter
+        let context = vec![("file".to_string(), "C:\\rec & co\\meta.json".to_string())];
+        assert!(matches!(parse_action("Notes").with_captures(&context), ActionType::OpenApplication(_)));
+    }
+
     #[test]
     fn test_parse_action_app() {
         match parse_action("notepad") {
@@ -109,4 +333,21 @@ mod tests {
             _ => panic!("Expected OpenApplication for app name"),
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_parse_actions_sequence() {
+        let actions = vec!["Obsidian".to_string(), String::new(), "https://example.com/?q={query}".to_string()];
+        match parse_actions(&actions).with_query("two words") {
+            ActionType::Sequence(steps) => match &steps[..] {
+                [ActionType::OpenApplication(app), ActionType::OpenUrl(url)] => {
+                    assert_eq!(app, "Obsidian");
+                    assert_eq!(url, "https://example.com/?q=two%20words");
+                }
+                _ => panic!("Expected an application then a URL"),
+            },
+            _ => panic!("Expected Sequence for several actions"),
+        }
+        assert!(matches!(parse_actions(&["notepad".to_string()]), ActionType::OpenApplication(_)));
+        assert!(matches!(parse_actions(&[]), ActionType::None));
+    }
+}
@@ -0,0 +1,261 @@
+use crate::casefold::{fold, FoldedText, Normalization};
+use crate::keyphrase::{in_position, resolve_overlaps, KeyphraseAction, KeyphraseMatch, KeyphraseMatchingStrategy};
+use aho_corasick::{AhoCorasick, AhoCorasickBuilder, MatchKind};
+
+/// All literal keyphrases of a set compiled into one Aho-Corasick automaton
+///
+/// Built once per config load, it finds every keyphrase in a single pass over the
+/// text. Every overlapping match is found, matches rejected for whole words or
+/// positions are dropped, and `resolve_overlaps` chooses between the ones that remain:
+/// the highest priority, then the longest, then the earliest. Searching keyphrase by
+/// keyphrase uses the same rule and skips past whole-word rejects one character at a
+/// time, so both give the same result.
+#[derive(Debug, Clone)]
+pub struct KeyphraseAutomaton {
+    automaton: AhoCorasick,
+    /// Index into the keyphrase list for each pattern in the automaton
+    keyphrase_index: Vec<usize>,
+    strategy: KeyphraseMatchingStrategy,
+    normalization: Normalization,
+}
+
+impl KeyphraseAutomaton {
+    /// Compile the literal keyphrases for a matching strategy
+    ///
+    /// Returns `None` when there is nothing to compile, or for the fuzzy strategy,
+    /// which matches word by word instead. Regex keyphrases are left out. Case
+    /// insensitive strategies compile the case folded, normalized keyphrases and
+    /// search the folded text.
+    pub fn build(
+        keyphrases: &[KeyphraseAction],
+        strategy: &KeyphraseMatchingStrategy,
+        normalization: Normalization,
+    ) -> Option<KeyphraseAutomaton> {
+        if *strategy == KeyphraseMatchingStrategy::Fuzzy {
+            return None;
+        }
+
+        let folding = *strategy != KeyphraseMatchingStrategy::Exact;
+        let (keyphrase_index, patterns): (Vec<usize>, Vec<String>) = keyphrases
+            .iter()
+            .enumerate()
+            .filter(|(_, ka)| covers(ka, strategy) && !ka.keyphrase.is_empty())
+            .map(|(i, ka)| match folding {
+                true => (i, fold(&ka.keyphrase, normalization)),
+                false => (i, ka.keyphrase.clone()),
+            })
+            .unzip();
+        if patterns.is_empty() {
+            return None;
+        }
+
+        // Overlapping search needs the standard match kind
+        let automaton = AhoCorasickBuilder::new()
+            .match_kind(MatchKind::Standard)
+            .build(&patterns)
+            .ok()?;
+
+        Some(KeyphraseAutomaton {
+            automaton,
+            keyphrase_index,
+            strategy: strategy.clone(),
+            normalization,
+        })
+    }
+
+    /// True if the automaton finds this keyphrase, which it does unless the keyphrase
+    /// is a regex or has a strategy of its own
+    pub fn covers(&self, ka: &KeyphraseAction) -> bool {
+        covers(ka, &self.strategy)
+    }
+
+    /// The strategy this automaton was built for
+    pub fn strategy(&self) -> &KeyphraseMatchingStrategy {
+        &self.strategy
+    }
+
+    /// The normalization the keyphrases were folded with
+    pub fn normalization(&self) -> Normalization {
+        self.normalization
+    }
+
+    /// Find every literal keyphrase in the text
+    ///
+    /// `keyphrases` must be the list the automaton was built from. Positions are
+    /// byte offsets into `text`, even where folding changed its length.
+    pub fn find_all(&self, text: &str, keyphrases: &[KeyphraseAction]) -> Vec<KeyphraseMatch> {
+        let folded = (self.strategy != KeyphraseMatchingStrategy::Exact).then(|| FoldedText::new(text, self.normalization));
+        let haystack = folded.as_ref().map_or(text, FoldedText::as_str);
+
+        let matches = self
+            .automaton
+            .find_overlapping_iter(haystack)
+            .filter_map(|m| {
+                let (start, end) = match &folded {
+                    Some(folded) => folded.original_span(m.start(), m.end())?,
+                    None => (m.start(), m.end()),
+                };
+                if self.strategy == KeyphraseMatchingStrategy::WholeWord && !is_whole_word(text, start, end) {
+                    return None;
+                }
+                let ka = &keyphrases[self.keyphrase_index[m.pattern().as_usize()]];
+                if !in_position(text, ka, start, end) {
+                    return None;
+                }
+                Some(KeyphraseMatch::new(ka, ka.action.clone(), start, end, 1.0))
+            })
+            .collect();
+
+        resolve_overlaps(matches)
+    }
+}
+
+fn covers(ka: &KeyphraseAction, strategy: &KeyphraseMatchingStrategy) -> bool {
+    ka.pattern.is_none() && ka.strategy.as_ref().is_none_or(|own| own == strategy)
+}
+
+/// True if the match isn't part of a longer word
+pub fn is_whole_word(text: &str, start: usize, end: usize) -> bool {
+    let is_word_char = |c: char| c.is_alphanumeric() || c == '_';
+    !text[..start].chars().next_back().is_some_and(is_word_char) && !text[end..].chars().next().is_some_and(is_word_char)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::actions::ActionType;
+    use proptest::prelude::*;
+
+    fn keyphrases(phrases: &[&str]) -> Vec<KeyphraseAction> {
+        phrases
+            .iter()
+            .map(|phrase| KeyphraseAction {
+                keyphrase: phrase.to_string(),
+                action: ActionType::None,
+                ..Default::default()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_matches_searching_one_by_one() {
+        let options = crate::keyphrase::KeyphraseProcessingOptions::default();
+        let text = "Please turn on the lights, then open notes app.";
+        let find = |keyphrases: &[KeyphraseAction]| {
+            let automaton = KeyphraseAutomaton::build(keyphrases, &KeyphraseMatchingStrategy::Simple, Normalization::Nfc).unwrap();
+            let found: Vec<_> = automaton.find_all(text, keyphrases).into_iter().map(|m| (m.keyphrase, m.start_pos)).collect();
+            let one_by_one: Vec<_> = resolve_overlaps(crate::keyphrase::detect_all_keyphrases(text, keyphrases, &options))
+                .into_iter()
+                .map(|m| (m.keyphrase, m.start_pos))
+                .collect();
+            assert_eq!(found, one_by_one);
+            found
+        };
+
+        // The longest overlapping keyphrase wins, whether or not an unrelated one sets a priority
+        let mut phrases = keyphrases(&["turn on", "on the lights", "open", "open notes", "notes app"]);
+        let expected = vec![("on the lights".to_string(), 12), ("open notes".to_string(), 32)];
+        assert_eq!(find(&phrases), expected);
+
+        phrases.push(KeyphraseAction {
+            keyphrase: "good night".to_string(),
+            priority: 5,
+            ..Default::default()
+        });
+        assert_eq!(find(&phrases), expected);
+
+        // A priority still beats length
+        phrases[0].priority = 10;
+        assert_eq!(find(&phrases), vec![("turn on".to_string(), 7), ("open notes".to_string(), 32)]);
+    }
+
+    #[test]
+    fn test_strategies() {
+        let keyphrases = keyphrases(&["note"]);
+        let count = |strategy: KeyphraseMatchingStrategy, text: &str| {
+            KeyphraseAutomaton::build(&keyphrases, &strategy, Normalization::Nfc).unwrap().find_all(text, &keyphrases).len()
+        };
+
+        assert_eq!(count(KeyphraseMatchingStrategy::Simple, "Notebook note"), 2);
+        assert_eq!(count(KeyphraseMatchingStrategy::WholeWord, "Notebook note, Note"), 2);
+        assert_eq!(count(KeyphraseMatchingStrategy::Exact, "Notebook note, Note"), 1);
+        assert!(KeyphraseAutomaton::build(&keyphrases, &KeyphraseMatchingStrategy::Fuzzy, Normalization::Nfc).is_none());
+    }
+
+    #[test]
+    fn test_rejected_longer_match_keeps_shorter_one() {
+        let keyphrases = keyphrases(&["notes", "notes x"]);
+        let automaton = KeyphraseAutomaton::build(&keyphrases, &KeyphraseMatchingStrategy::WholeWord, Normalization::Nfc).unwrap();
+        let options = crate::keyphrase::KeyphraseProcessingOptions {
+            matching_strategy: KeyphraseMatchingStrategy::WholeWord,
+            ..Default::default()
+        };
+
+        // "notes x" ends inside "xylophone", so only "notes" counts, as it does searching one by one
+        let text = "Open notes xylophone, then notes x.";
+        let found: Vec<_> = automaton
+            .find_all(text, &keyphrases)
+            .into_iter()
+            .map(|m| (m.keyphrase, m.start_pos))
+            .collect();
+        let one_by_one: Vec<_> = resolve_overlaps(crate::keyphrase::detect_all_keyphrases(text, &keyphrases, &options))
+            .into_iter()
+            .map(|m| (m.keyphrase, m.start_pos))
+            .collect();
+        assert_eq!(found, vec![("notes".to_string(), 5), ("notes x".to_string(), 27)]);
+        assert_eq!(found, one_by_one);
+    }
+
+    #[test]
+    fn test_unicode_case_folding() {
+        let keyphrases = keyphrases(&["straße", "café"]);
+        let automaton = KeyphraseAutomaton::build(&keyphrases, &KeyphraseMatchingStrategy::WholeWord, Normalization::Nfc).unwrap();
+
+        // "İ" grows when folded, so positions after it only line up if they are mapped back
+        let text = "İ, STRASSE und Cafe\u{301}!";
+        let found: Vec<_> = automaton
+            .find_all(text, &keyphrases)
+            .into_iter()
+            .map(|m| &text[m.start_pos..m.end_pos])
+            .collect();
+        assert_eq!(found, vec!["STRASSE", "Cafe\u{301}"]);
+    }
+
+    #[test]
+    fn test_rejected_match_does_not_hide_overlapping_one() {
+        let keyphrases = keyphrases(&["a a"]);
+        let options = crate::keyphrase::KeyphraseProcessingOptions {
+            matching_strategy: KeyphraseMatchingStrategy::WholeWord,
+            ..Default::default()
+        };
+
+        // "aa a" is not a whole word match, but the "a a" it overlaps is
+        let found = crate::keyphrase::detect_all_keyphrases("aa a a", &keyphrases, &options);
+        assert_eq!(found.iter().map(|m| m.start_pos).collect::<Vec<_>>(), vec![3]);
+    }
+
+    proptest! {
+        #[test]
+        fn prop_whole_word_matches_searching_one_by_one(
+            words in proptest::collection::vec("(a|A|an|x)", 0..12),
+            separators in proptest::collection::vec("( |-|)", 12),
+            phrases in proptest::collection::vec("(a|an|a a|a an|an a|a x)", 1..4),
+        ) {
+            let text: String = words.iter().zip(&separators).map(|(word, sep)| format!("{}{}", word, sep)).collect();
+            let phrases: Vec<&str> = phrases.iter().map(String::as_str).collect();
+            let keyphrases = keyphrases(&phrases);
+            let options = crate::keyphrase::KeyphraseProcessingOptions {
+                matching_strategy: KeyphraseMatchingStrategy::WholeWord,
+                ..Default::default()
+            };
+
+            let automaton = KeyphraseAutomaton::build(&keyphrases, &KeyphraseMatchingStrategy::WholeWord, Normalization::Nfc).unwrap();
+            let found: Vec<_> = automaton.find_all(&text, &keyphrases).into_iter().map(|m| (m.keyphrase, m.start_pos, m.end_pos)).collect();
+            let one_by_one: Vec<_> = resolve_overlaps(crate::keyphrase::detect_all_keyphrases(&text, &keyphrases, &options))
+                .into_iter()
+                .map(|m| (m.keyphrase, m.start_pos, m.end_pos))
+                .collect();
+            prop_assert_eq!(found, one_by_one, "text {:?}", text);
+        }
+    }
+}
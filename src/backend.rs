@@ -0,0 +1,208 @@
+use crate::profile::WatchProfile;
+use log::{debug, info, warn};
+use notify::{Config, EventHandler, PollWatcher, RecommendedWatcher, Result as NotifyResult, Watcher};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Default interval between scans for the poll backend
+pub const DEFAULT_POLL_INTERVAL: Duration = Duration::from_millis(2000);
+
+/// File system types where native change notifications don't arrive reliably
+const NETWORK_FS_TYPES: &[&str] = &[
+    "nfs", "nfs4", "cifs", "smb", "smb2", "smb3", "smbfs", "afpfs", "webdav", "davfs",
+    "fuse.sshfs", "sshfs", "osxfuse", "macfuse", "fuse", "9p", "afs",
+];
+
+/// Which notify backend to use for a watch directory
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WatcherBackend {
+    /// Native notifications (inotify, FSEvents, ReadDirectoryChangesW)
+    Native,
+    /// Periodically scan the directory for changes
+    Poll,
+    /// Native, unless the directory is on a network mount or native watching fails
+    Auto,
+}
+
+/// A running watcher together with the backend that ended up being used
+pub struct ActiveWatcher {
+    pub watcher: Box<dyn Watcher + Send>,
+    pub backend: WatcherBackend,
+}
+
+/// Start watching a profile's directory with its configured backend
+///
+/// `make_handler` is called with `true` when the handler will receive polling events.
+pub fn start_profile_watcher<H, F>(profile: &WatchProfile, make_handler: F) -> NotifyResult<ActiveWatcher>
+where
+    H: EventHandler,
+    F: Fn(bool) -> H,
+{
+    match profile.watcher_backend {
+        WatcherBackend::Poll => {
+            info!("Watch '{}': using polling watcher (configured)", profile.name);
+            start_poll_watcher(profile, make_handler(true))
+        }
+        WatcherBackend::Native => {
+            let watcher = start_native_watcher(profile, make_handler(false))?;
+            info!("Watch '{}': using native watcher (configured)", profile.name);
+            Ok(watcher)
+        }
+        WatcherBackend::Auto => {
+            if let Some(fs_type) = network_fs_type(&profile.path) {
+                info!(
+                    "Watch '{}': {:?} is on a {} mount, using polling watcher",
+                    profile.name, profile.path, fs_type
+                );
+                return start_poll_watcher(profile, make_handler(true));
+            }
+
+            match start_native_watcher(profile, make_handler(false)) {
+                Ok(watcher) => {
+                    info!("Watch '{}': using native watcher", profile.name);
+                    Ok(watcher)
+                }
+                Err(e) => {
+                    warn!(
+                        "Watch '{}': native watcher failed ({}), falling back to polling",
+                        profile.name, e
+                    );
+                    start_poll_watcher(profile, make_handler(true))
+                }
+            }
+        }
+    }
+}
+
+fn start_native_watcher<H: EventHandler>(profile: &WatchProfile, handler: H) -> NotifyResult<ActiveWatcher> {
+    let mut watcher = RecommendedWatcher::new(handler, Config::default())?;
+    watcher.watch(&profile.path, profile.recursive_mode())?;
+
+    Ok(ActiveWatcher {
+        watcher: Box::new(watcher),
+        backend: WatcherBackend::Native,
+    })
+}
+
+fn start_poll_watcher<H: EventHandler>(profile: &WatchProfile, handler: H) -> NotifyResult<ActiveWatcher> {
+    let config = Config::default().with_poll_interval(profile.poll_interval);
+    let mut watcher = PollWatcher::new(handler, config)?;
+    watcher.watch(&profile.path, profile.recursive_mode())?;
+    info!(
+        "Watch '{}': polling {:?} every {:?}",
+        profile.name, profile.path, profile.poll_interval
+    );
+
+    Ok(ActiveWatcher {
+        watcher: Box::new(watcher),
+        backend: WatcherBackend::Poll,
+    })
+}
+
+/// Return the file system type if the path lives on a network or FUSE mount
+pub fn network_fs_type(path: &Path) -> Option<String> {
+    let mounts = read_mounts();
+    let path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+    let fs_type = mount_fs_type(&path, &mounts)?;
+    debug!("{:?} is on a {} file system", path, fs_type);
+
+    NETWORK_FS_TYPES
+        .contains(&fs_type.as_str())
+        .then_some(fs_type)
+}
+
+/// Find the file system type of the most specific mount containing the path
+fn mount_fs_type(path: &Path, mounts: &[(PathBuf, String)]) -> Option<String> {
+    mounts
+        .iter()
+        .filter(|(mount_point, _)| path.starts_with(mount_point))
+        .max_by_key(|(mount_point, _)| mount_point.components().count())
+        .map(|(_, fs_type)| fs_type.to_lowercase())
+}
+
+/// Read the mount table as (mount point, file system type) pairs
+#[cfg(target_os = "linux")]
+fn read_mounts() -> Vec<(PathBuf, String)> {
+    std::fs::read_to_string("/proc/mounts")
+        .map(|content| parse_proc_mounts(&content))
+        .unwrap_or_default()
+}
+
+/// Read the mount table as (mount point, file system type) pairs
+#[cfg(target_os = "macos")]
+fn read_mounts() -> Vec<(PathBuf, String)> {
+    std::process::Command::new("mount")
+        .output()
+        .map(|output| parse_bsd_mount_output(&String::from_utf8_lossy(&output.stdout)))
+        .unwrap_or_default()
+}
+
+/// Read the mount table as (mount point, file system type) pairs
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn read_mounts() -> Vec<(PathBuf, String)> {
+    Vec::new()
+}
+
+/// Parse /proc/mounts: "device mount_point fs_type options dump pass"
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn parse_proc_mounts(content: &str) -> Vec<(PathBuf, String)> {
+    content
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let mount_point = fields.nth(1)?;
+            let fs_type = fields.next()?;
+            // Spaces in mount points are escaped as \040
+            Some((PathBuf::from(mount_point.replace("\\040", " ")), fs_type.to_string()))
+        })
+        .collect()
+}
+
+/// Parse `mount` output on macOS: "device on /mount/point (fs_type, options...)"
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+fn parse_bsd_mount_output(content: &str) -> Vec<(PathBuf, String)> {
+    content
+        .lines()
+        .filter_map(|line| {
+            let (_, rest) = line.split_once(" on ")?;
+            let (mount_point, details) = rest.rsplit_once(" (")?;
+            let fs_type = details.split([',', ')']).next()?.trim();
+            Some((PathBuf::from(mount_point), fs_type.to_string()))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_proc_mounts() {
+        let content = "/dev/sda1 / ext4 rw,relatime 0 0\n\
+                       server:/export /mnt/nfs nfs4 rw 0 0\n\
+                       user@host:/home /mnt/my\\040share fuse.sshfs rw 0 0\n";
+        let mounts = parse_proc_mounts(content);
+
+        assert_eq!(mounts.len(), 3);
+        assert_eq!(mount_fs_type(Path::new("/home/user"), &mounts).as_deref(), Some("ext4"));
+        assert_eq!(mount_fs_type(Path::new("/mnt/nfs/recordings"), &mounts).as_deref(), Some("nfs4"));
+        assert_eq!(
+            mount_fs_type(Path::new("/mnt/my share/recordings"), &mounts).as_deref(),
+            Some("fuse.sshfs")
+        );
+    }
+
+    #[test]
+    fn test_parse_bsd_mount_output() {
+        let content = "/dev/disk3s1s1 on / (apfs, sealed, local, read-only, journaled)\n\
+                       //user@nas/Recordings on /Volumes/Recordings (smbfs, nodev, nosuid, mounted by user)\n";
+        let mounts = parse_bsd_mount_output(content);
+
+        assert_eq!(mounts.len(), 2);
+        assert_eq!(
+            mount_fs_type(Path::new("/Volumes/Recordings/superwhisper"), &mounts).as_deref(),
+            Some("smbfs")
+        );
+        assert_eq!(mount_fs_type(Path::new("/Users/me"), &mounts).as_deref(), Some("apfs"));
+    }
+}
@@ -0,0 +1,249 @@
+use crate::config::AppState;
+use crate::meta_processor::{debounce, sink_key, LastProcessedMap, ProcessJob, ProcessQueue};
+use crate::profile::WatchProfile;
+use log::{debug, info, warn};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use walkdir::WalkDir;
+
+/// Name of the file in the state directory that records when sw-catcher last ran
+const LAST_RUN_FILE: &str = "last_run";
+
+/// Cutoff for the startup backfill scan
+#[derive(Debug, Clone, PartialEq)]
+pub enum BackfillCutoff {
+    /// Files modified since sw-catcher was last running
+    LastRun,
+    /// Files modified after an explicit point in time
+    Since(SystemTime),
+}
+
+/// Parse a backfill cutoff: "last_run", a duration ago ("2h", "30min"), a unix
+/// timestamp in seconds, or an RFC 3339 timestamp ("2024-05-01T09:00:00Z")
+pub fn parse_backfill_cutoff(cutoff: &str, now: SystemTime) -> Result<BackfillCutoff, String> {
+    let cutoff = cutoff.trim();
+
+    if matches!(cutoff.to_lowercase().as_str(), "last_run" | "lastrun" | "last-run") {
+        return Ok(BackfillCutoff::LastRun);
+    }
+
+    if let Ok(secs) = cutoff.parse::<u64>() {
+        return Ok(BackfillCutoff::Since(UNIX_EPOCH + Duration::from_secs(secs)));
+    }
+
+    if let Ok(ago) = humantime::parse_duration(cutoff) {
+        return now
+            .checked_sub(ago)
+            .map(BackfillCutoff::Since)
+            .ok_or_else(|| format!("Backfill duration '{}' reaches before the epoch", cutoff));
+    }
+
+    humantime::parse_rfc3339_weak(cutoff)
+        .map(BackfillCutoff::Since)
+        .map_err(|e| format!("Invalid backfill cutoff '{}': {}", cutoff, e))
+}
+
+/// Read the time sw-catcher was last seen running from the state directory
+pub fn read_last_run(state_dir: &Path) -> Option<SystemTime> {
+    let content = fs::read_to_string(state_dir.join(LAST_RUN_FILE)).ok()?;
+    let secs = content.trim().parse::<u64>().ok()?;
+    Some(UNIX_EPOCH + Duration::from_secs(secs))
+}
+
+/// Record the current time as the last time sw-catcher was running
+pub fn record_last_run(state_dir: &Path) -> std::io::Result<()> {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+
+    fs::create_dir_all(state_dir)?;
+    fs::write(state_dir.join(LAST_RUN_FILE), secs.to_string())
+}
+
+/// Resolve a cutoff into a point in time, if there is anything to backfill
+pub fn resolve_cutoff(cutoff: &BackfillCutoff, state_dir: &Path) -> Option<SystemTime> {
+    match cutoff {
+        BackfillCutoff::LastRun => {
+            let last_run = read_last_run(state_dir);
+            if last_run.is_none() {
+                info!("No previous run recorded in {}, nothing to backfill", state_dir.display());
+            }
+            last_run
+        }
+        BackfillCutoff::Since(time) => Some(*time),
+    }
+}
+
+/// Find a profile's files modified after the cutoff, oldest first
+pub fn find_backfill_candidates(profile: &WatchProfile, cutoff: SystemTime) -> Vec<PathBuf> {
+    let mut candidates = Vec::new();
+
+    let walker = WalkDir::new(&profile.path).max_depth(profile.effective_max_depth());
+    for entry in walker.into_iter() {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(e) => {
+                warn!("Backfill could not read entry: {}", e);
+                continue;
+            }
+        };
+
+        if !entry.file_type().is_file() || !profile.matches_file(entry.path()) {
+            continue;
+        }
+
+        let modified = entry
+            .metadata()
+            .map_err(std::io::Error::from)
+            .and_then(|m| m.modified());
+
+        match modified {
+            Ok(modified) if modified > cutoff => {
+                candidates.push((modified, entry.into_path()));
+            }
+            Ok(_) => {}
+            Err(e) => warn!("Backfill could not stat {}: {}", entry.path().display(), e),
+        }
+    }
+
+    // Process in the order the recordings were written so the newest ends up on the clipboard
+    candidates.sort();
+    candidates.into_iter().map(|(_, path)| path).collect()
+}
+
+/// Queue meta.json files that arrived while sw-catcher was not running
+///
+/// Files already recorded in the ledger are skipped by the meta processor. Waits for
+/// room in the queue rather than dropping jobs, and stops early once `shutdown` is set.
+/// Returns the number of files queued.
+pub fn run_backfill(
+    profile: &Arc<WatchProfile>,
+    cutoff: SystemTime,
+    last_processed: &LastProcessedMap,
+    queue: &ProcessQueue,
+    app_state: &AppState,
+    shutdown: &AtomicBool,
+) -> usize {
+    if shutdown.load(Ordering::SeqCst) {
+        return 0;
+    }
+
+    let candidates = find_backfill_candidates(profile, cutoff);
+    info!(
+        "Backfill found {} {} files to check in watch '{}'",
+        candidates.len(),
+        profile.pattern,
+        profile.name
+    );
+
+    let sink = sink_key(profile, app_state);
+    let mut queued = 0;
+    for path in candidates {
+        if shutdown.load(Ordering::SeqCst) {
+            info!("Shutdown requested, stopping backfill of watch '{}'", profile.name);
+            break;
+        }
+        if !debounce(&path, last_processed) {
+            continue;
+        }
+
+        debug!("Backfill queueing {}", path.display());
+        queue.submit_wait(
+            &sink,
+            ProcessJob {
+                path,
+                profile: profile.clone(),
+            },
+        );
+        queued += 1;
+    }
+
+    queued
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_parse_backfill_cutoff() {
+        let now = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+
+        assert_eq!(parse_backfill_cutoff("last_run", now), Ok(BackfillCutoff::LastRun));
+        assert_eq!(parse_backfill_cutoff("LAST-RUN", now), Ok(BackfillCutoff::LastRun));
+        assert_eq!(
+            parse_backfill_cutoff("2h", now),
+            Ok(BackfillCutoff::Since(now - Duration::from_secs(7200)))
+        );
+        assert_eq!(
+            parse_backfill_cutoff("1700000000", now),
+            Ok(BackfillCutoff::Since(now))
+        );
+        assert_eq!(
+            parse_backfill_cutoff("2023-11-14T22:13:20Z", now),
+            Ok(BackfillCutoff::Since(now))
+        );
+        assert!(parse_backfill_cutoff("yesterday-ish", now).is_err());
+    }
+
+    #[test]
+    fn test_last_run_round_trip() {
+        let temp_dir = tempdir().unwrap();
+        assert_eq!(read_last_run(temp_dir.path()), None);
+
+        record_last_run(temp_dir.path()).unwrap();
+        let last_run = read_last_run(temp_dir.path()).unwrap();
+        assert!(SystemTime::now().duration_since(last_run).unwrap() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn test_find_backfill_candidates() {
+        let temp_dir = tempdir().unwrap();
+        let recording = temp_dir.path().join("1700000000");
+        fs::create_dir_all(&recording).unwrap();
+        fs::write(recording.join("meta.json"), "{}").unwrap();
+        fs::write(recording.join("output.wav"), "").unwrap();
+        fs::write(temp_dir.path().join("meta.json"), "{}").unwrap();
+
+        let mut profile = WatchProfile::new("test", temp_dir.path());
+        profile.max_depth = Some(2);
+        assert_eq!(find_backfill_candidates(&profile, UNIX_EPOCH).len(), 2);
+
+        // Only files below the top level
+        profile.pattern = "*/meta.json".to_string();
+        profile.matcher = crate::profile::FilenameMatcher::glob("*/meta.json").unwrap();
+
+        let all = find_backfill_candidates(&profile, UNIX_EPOCH);
+        assert_eq!(all, vec![recording.join("meta.json")]);
+
+        let future = SystemTime::now() + Duration::from_secs(3600);
+        assert!(find_backfill_candidates(&profile, future).is_empty());
+    }
+
+    #[test]
+    fn test_backfill_stops_on_shutdown() {
+        let temp_dir = tempdir().unwrap();
+        fs::write(temp_dir.path().join("meta.json"), "{}").unwrap();
+        let profile = Arc::new(WatchProfile::new("test", temp_dir.path()));
+        let app_state = AppState {
+            config: Default::default(),
+            clipboard_format: crate::clipboard::ClipboardFormat::PlainText,
+            dry_run: true,
+            disable_logs: true,
+        };
+        let last_processed: LastProcessedMap = Default::default();
+        let queue = ProcessQueue::new(1, 4, crate::queue::OverflowPolicy::Block, |_| {});
+
+        let shutdown = AtomicBool::new(true);
+        assert_eq!(run_backfill(&profile, UNIX_EPOCH, &last_processed, &queue, &app_state, &shutdown), 0);
+
+        shutdown.store(false, Ordering::SeqCst);
+        assert_eq!(run_backfill(&profile, UNIX_EPOCH, &last_processed, &queue, &app_state, &shutdown), 1);
+        queue.shutdown(Duration::from_secs(1));
+    }
+}
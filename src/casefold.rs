@@ -0,0 +1,186 @@
+use caseless::Caseless;
+use unicode_normalization::char::{canonical_combining_class, compose};
+use unicode_normalization::UnicodeNormalization;
+
+/// Unicode normalization applied along with case folding
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Normalization {
+    /// Case folding only
+    None,
+    /// Canonical equivalence: "é" matches "e" followed by a combining acute accent
+    Nfc,
+    /// Compatibility equivalence as well: "²" matches "2", fullwidth letters match ASCII
+    Nfkc,
+}
+
+/// Case fold and normalize a string, e.g. a keyphrase
+///
+/// This gives the same result as the folded form of `FoldedText`, so folded
+/// keyphrases can be searched for in it.
+pub fn fold(text: &str, normalization: Normalization) -> String {
+    FoldedText::new(text, normalization).folded
+}
+
+/// Case fold and normalize one segment
+fn fold_segment(text: &str, normalization: Normalization) -> String {
+    match normalization {
+        Normalization::None => text.chars().default_case_fold().collect(),
+        Normalization::Nfc => text.chars().nfd().default_case_fold().nfc().collect(),
+        Normalization::Nfkc => text
+            .chars()
+            .nfd()
+            .default_case_fold()
+            .nfkd()
+            .default_case_fold()
+            .nfkc()
+            .collect(),
+    }
+}
+
+/// Case-folded text that remembers where each piece came from in the original
+///
+/// The text is folded one segment at a time (a character and the combining marks
+/// after it), so a match in the folded text maps back to whole characters of the
+/// original even when folding changes lengths ("ẞ" becomes "ss", "İ" becomes "i̇").
+#[derive(Debug, Clone)]
+pub struct FoldedText {
+    folded: String,
+    /// Start of each segment in the folded and the original text, ending with both lengths
+    segments: Vec<(usize, usize)>,
+}
+
+impl FoldedText {
+    pub fn new(text: &str, normalization: Normalization) -> FoldedText {
+        let mut folded = String::with_capacity(text.len());
+        let mut segments = Vec::new();
+
+        let starts = segment_starts(text);
+        for (i, &start) in starts.iter().enumerate() {
+            let end = starts.get(i + 1).copied().unwrap_or(text.len());
+            segments.push((folded.len(), start));
+            folded.push_str(&fold_segment(&text[start..end], normalization));
+        }
+        segments.push((folded.len(), text.len()));
+
+        FoldedText { folded, segments }
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.folded
+    }
+
+    /// Map a span of the folded text back to the original text
+    ///
+    /// Returns `None` if the span starts or ends inside a segment, e.g. "s" matching
+    /// half of the "ss" that "ß" folds to.
+    pub fn original_span(&self, start: usize, end: usize) -> Option<(usize, usize)> {
+        let first = self.segments.partition_point(|&(folded, _)| folded < start);
+        let (folded_start, original_start) = *self.segments.get(first)?;
+        let last = self.segments.partition_point(|&(folded, _)| folded <= end).checked_sub(1)?;
+        let (folded_end, original_end) = self.segments[last];
+
+        (folded_start == start && folded_end == end).then_some((original_start, original_end))
+    }
+
+    /// Find the first occurrence of an already folded needle at or after `from` in the folded text
+    ///
+    /// Returns the span in the original text and the end in the folded text.
+    /// Occurrences that don't line up with whole characters are skipped.
+    pub fn find(&self, needle: &str, from: usize) -> Option<(usize, usize, usize)> {
+        if needle.is_empty() {
+            return None;
+        }
+
+        let mut pos = from;
+        while let Some(offset) = self.folded.get(pos..)?.find(needle) {
+            let start = pos + offset;
+            let end = start + needle.len();
+            if let Some((original_start, original_end)) = self.original_span(start, end) {
+                return Some((original_start, original_end, end));
+            }
+            pos = start + self.folded[start..].chars().next().map_or(1, char::len_utf8);
+        }
+        None
+    }
+}
+
+/// Byte offsets where each segment of the text starts
+///
+/// A segment is a character with the combining marks after it, plus any following
+/// characters that compose with it (such as Hangul vowels and final consonants).
+fn segment_starts(text: &str) -> Vec<usize> {
+    let mut starts = Vec::new();
+    let mut base: Option<char> = None;
+
+    for (i, c) in text.char_indices() {
+        match base.and_then(|base| compose(base, c)) {
+            Some(composed) => base = Some(composed),
+            None if base.is_some() && canonical_combining_class(c) != 0 => {}
+            None => {
+                starts.push(i);
+                base = Some(c);
+            }
+        }
+    }
+
+    starts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    #[test]
+    fn test_fold_changes_lengths() {
+        let text = FoldedText::new("Große STRAẞE in İstanbul", Normalization::Nfc);
+        assert_eq!(text.as_str(), "grosse strasse in i\u{307}stanbul");
+
+        let (start, end, _) = text.find("strasse", 0).unwrap();
+        assert_eq!(&"Große STRAẞE in İstanbul"[start..end], "STRAẞE");
+        // Half of a folded "ß" is not a match
+        assert_eq!(text.find("se", 0), None);
+    }
+
+    #[test]
+    fn test_normalization_forms() {
+        let decomposed = "Cafe\u{301} x²";
+        assert_eq!(fold(decomposed, Normalization::Nfc), "café x²");
+        assert_eq!(fold(decomposed, Normalization::Nfkc), "café x2");
+        assert_eq!(fold("Ｍｏｄｅ", Normalization::Nfkc), "mode");
+
+        let text = FoldedText::new(decomposed, Normalization::Nfc);
+        let (start, end, _) = text.find(&fold("CAFÉ", Normalization::Nfc), 0).unwrap();
+        assert_eq!(&decomposed[start..end], "Cafe\u{301}");
+    }
+
+    proptest! {
+        #[test]
+        fn prop_spans_are_valid_and_match(text in "\\PC{0,40}", needle in "\\PC{1,6}") {
+            for normalization in [Normalization::None, Normalization::Nfc, Normalization::Nfkc] {
+                let folded_text = FoldedText::new(&text, normalization);
+                let folded_needle = fold(&needle, normalization);
+
+                let mut from = 0;
+                while let Some((start, end, next)) = folded_text.find(&folded_needle, from) {
+                    prop_assert!(text.is_char_boundary(start) && text.is_char_boundary(end));
+                    prop_assert!(start < end);
+                    prop_assert_eq!(fold(&text[start..end], normalization), folded_needle.clone());
+                    from = next;
+                }
+            }
+        }
+
+        #[test]
+        fn prop_planted_needle_is_found(prefix in "\\PC{0,20}", needle in "[a-zA-Z]\\PC{0,6}", suffix in "\\PC{0,20}") {
+            let text = format!("{} {} {}", prefix, needle.to_uppercase(), suffix);
+            for normalization in [Normalization::None, Normalization::Nfc, Normalization::Nfkc] {
+                let folded_needle = fold(&needle, normalization);
+                if folded_needle == fold(&needle.to_uppercase(), normalization) {
+                    let found = FoldedText::new(&text, normalization).find(&folded_needle, 0);
+                    prop_assert!(found.is_some(), "{:?} not found in {:?}", needle, text);
+                }
+            }
+        }
+    }
+}
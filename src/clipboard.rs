@@ -1,7 +1,7 @@
 use copypasta::{ClipboardContext, ClipboardProvider};
 use log::{debug, warn};
 use serde::Deserialize;
-use std::io::{Error, ErrorKind};
+use std::io::Error;
 use std::time::Duration;
 use std::thread;
 
@@ -26,17 +26,11 @@ pub fn parse_clipboard_format(format: &str) -> ClipboardFormat {
 /// Get the current clipboard content
 pub fn get_clipboard_content() -> std::io::Result<String> {
     let mut ctx = ClipboardContext::new().map_err(|e| {
-        Error::new(
-            ErrorKind::Other,
-            format!("Failed to access clipboard: {}", e),
-        )
+        Error::other(format!("Failed to access clipboard: {}", e))
     })?;
     
     ctx.get_contents().map_err(|e| {
-        Error::new(
-            ErrorKind::Other,
-            format!("Failed to get clipboard contents: {}", e),
-        )
+        Error::other(format!("Failed to get clipboard contents: {}", e))
     })
 }
 
@@ -48,18 +42,12 @@ fn normalize_for_comparison(text: &str) -> String {
 /// Copy text to system clipboard with format support
 pub fn copy_to_clipboard_with_format(text: &str, format: &ClipboardFormat) -> std::io::Result<()> {
     let mut ctx = ClipboardContext::new().map_err(|e| {
-        Error::new(
-            ErrorKind::Other,
-            format!("Failed to access clipboard: {}", e),
-        )
+        Error::other(format!("Failed to access clipboard: {}", e))
     })?;
 
     match format {
         ClipboardFormat::PlainText => ctx.set_contents(text.to_owned()).map_err(|e| {
-            Error::new(
-                ErrorKind::Other,
-                format!("Failed to set clipboard contents: {}", e),
-            )
+            Error::other(format!("Failed to set clipboard contents: {}", e))
         }),
         ClipboardFormat::RichText => {
             #[cfg(target_os = "windows")]
@@ -78,20 +66,14 @@ pub fn copy_to_clipboard_with_format(text: &str, format: &ClipboardFormat) -> st
                 );
 
                 ctx.set_contents(text.to_owned()).map_err(|e| {
-                    Error::new(
-                        ErrorKind::Other,
-                        format!("Failed to set clipboard contents: {}", e),
-                    )
+                    Error::other(format!("Failed to set clipboard contents: {}", e))
                 })
             }
             #[cfg(not(target_os = "windows"))]
             {
                 debug!("Rich text clipboard format requested - platform support limited");
                 ctx.set_contents(text.to_owned()).map_err(|e| {
-                    Error::new(
-                        ErrorKind::Other,
-                        format!("Failed to set clipboard contents: {}", e),
-                    )
+                    Error::other(format!("Failed to set clipboard contents: {}", e))
                 })
             }
         }
@@ -101,10 +83,7 @@ pub fn copy_to_clipboard_with_format(text: &str, format: &ClipboardFormat) -> st
             // On most platforms, we'll just put the plain text,
             // but applications that understand markdown will interpret it correctly
             ctx.set_contents(text.to_owned()).map_err(|e| {
-                Error::new(
-                    ErrorKind::Other,
-                    format!("Failed to set clipboard contents: {}", e),
-                )
+                Error::other(format!("Failed to set clipboard contents: {}", e))
             })
         }
     }
@@ -1,8 +1,13 @@
+use crate::backend::WatcherBackend;
+use crate::casefold::Normalization;
 use crate::clipboard::parse_clipboard_format;
 use crate::clipboard::ClipboardFormat;
-use crate::keyphrase::{KeyphraseMatchingStrategy, PunctuationHandling};
+use crate::filter::FilterCombine;
+use crate::keyphrase::{KeyphraseMatchingStrategy, KeyphrasePosition, PunctuationHandling};
+use crate::queue::OverflowPolicy;
+use crate::text_processing::SubtitleFormat;
 use clap::Parser;
-use log::{debug, error, LevelFilter};
+use log::{debug, error, warn, LevelFilter};
 use notify::Error as NotifyError;
 use notify::Result as NotifyResult;
 use serde::Deserialize;
@@ -12,6 +17,9 @@ use std::fs;
 use std::io::Write;
 use std::path::Path;
 
+/// Config file read from the working directory
+pub const CONFIG_FILE: &str = "config.toml";
+
 /// Text cleaning options
 #[derive(Debug, Clone, Deserialize)]
 pub struct TextCleaningOptions {
@@ -29,32 +37,342 @@ pub struct TextCleaningOptions {
 #[derive(Debug, Clone, Deserialize)]
 pub struct KeyphraseConfig {
     #[serde(default)]
-    pub matching_strategy: Option<String>,  // "simple", "wholeword", or "exact"
+    pub matching_strategy: Option<String>,  // "simple", "wholeword", "exact", "regex", or "fuzzy"
     #[serde(default)]
     pub punctuation_handling: Option<String>,  // "ignore", "sentence", or "all"
+    #[serde(default)]
+    pub fuzzy_threshold: Option<usize>,  // Edits allowed per word with the fuzzy strategy
+    #[serde(default)]
+    pub phonetic: Option<bool>,  // Fuzzy matching also accepts words that sound alike
+    #[serde(default)]
+    pub min_confidence: Option<f64>,  // Fuzzy matches below this are logged, not acted on
+    #[serde(default)]
+    pub normalization: Option<String>,  // "nfc", "nfkc", or "none", applied with case folding
+    #[serde(default)]
+    pub escape_word: Option<String>,  // Say it before a keyphrase to dictate the phrase as text
 }
 
-/// Configuration structure for the application
+/// A keyphrase's action, either as a plain string or a table with options
 #[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum KeyphraseEntry {
+    Action(String),
+    Options(KeyphraseEntryOptions),
+}
+
+/// The table form of a keyphrase entry
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct KeyphraseEntryOptions {
+    #[serde(default)]
+    pub action: String,
+    #[serde(default)]
+    pub priority: i32,  // Wins over overlapping keyphrases with a lower priority
+    #[serde(default)]
+    pub strip: Option<bool>,  // Remove the phrase from the text, defaults to true
+    #[serde(default)]
+    pub keep_phrase: Option<bool>,  // Deprecated, the opposite of strip
+    #[serde(default)]
+    pub position: Option<String>,  // "start", "end", "alone", or "anywhere" (default)
+}
+
+impl KeyphraseEntry {
+    pub fn options(&self) -> KeyphraseEntryOptions {
+        match self {
+            KeyphraseEntry::Action(action) => KeyphraseEntryOptions {
+                action: action.clone(),
+                ..Default::default()
+            },
+            KeyphraseEntry::Options(options) => options.clone(),
+        }
+    }
+
+    /// The entry as a `[[keyphrase]]` definition for the phrase it is keyed by
+    pub fn definition(&self, phrase: &str) -> KeyphraseDefinition {
+        let options = self.options();
+        if options.keep_phrase.is_some() {
+            warn!("Keyphrase '{}' uses keep_phrase, which is deprecated; use strip instead", phrase);
+        }
+        KeyphraseDefinition {
+            phrase: phrase.to_string(),
+            action: Some(StringOrList::One(options.action)),
+            priority: options.priority,
+            strip: options.strip.or(options.keep_phrase.map(|keep| !keep)),
+            position: options.position,
+            ..Default::default()
+        }
+    }
+}
+
+impl From<&str> for KeyphraseEntry {
+    fn from(action: &str) -> Self {
+        KeyphraseEntry::Action(action.to_string())
+    }
+}
+
+/// A `[[keyphrase]]` entry, the structured alternative to `[keyphrases]`
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct KeyphraseDefinition {
+    pub phrase: String,  // Literal, or a regex after "re:"
+    #[serde(default)]
+    pub aliases: Vec<String>,  // Other phrases that trigger the same action
+    #[serde(default)]
+    pub action: Option<StringOrList>,  // One action, or a list run in order
+    #[serde(default)]
+    pub matching_strategy: Option<String>,  // Overrides [keyphrase_settings] for this entry
+    #[serde(default)]
+    pub punctuation_handling: Option<String>,  // Punctuation removed after the phrase: ignore, sentence, all
+    #[serde(default)]
+    pub strip: Option<bool>,  // Remove the phrase from the text, defaults to true
+    #[serde(default)]
+    pub enabled: Option<bool>,  // Set to false to ignore the entry
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub priority: i32,
+    #[serde(default)]
+    pub position: Option<String>,  // "start", "end", "alone", or "anywhere" (default)
+    #[serde(default)]
+    pub steps: Option<Vec<MacroStepConfig>>,  // Run these in order instead of an action
+    #[serde(default)]
+    pub exec: Option<ExecConfig>,  // Run a command instead of an action
+}
+
+/// One step of a `[[keyphrase]]` macro: an action, a command, clipboard text, or a value to save
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct MacroStepConfig {
+    #[serde(default)]
+    pub action: Option<String>,  // Application or URL to open
+    #[serde(default)]
+    pub clipboard: Option<String>,  // Text to copy instead of the dictated text, e.g. "{text}"
+    #[serde(default)]
+    pub exec: Option<ExecConfig>,  // Command to run; its output is the step's output
+    #[serde(default)]
+    pub value: Option<String>,  // Text to save with save_as, e.g. "{segment_after}"
+    #[serde(default)]
+    pub delay_ms: Option<u64>,  // Wait before running the step
+    #[serde(default)]
+    pub on_failure: Option<String>,  // "stop" (default) or "continue"
+    #[serde(default)]
+    pub save_as: Option<String>,  // Make the step's output available to later steps as {name}
+}
+
+/// An `exec` action: a command run without a shell
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ExecConfig {
+    pub argv: Vec<String>,  // Program and arguments, e.g. ["notes-cli", "add", "{segment_after}"]
+    #[serde(default)]
+    pub stdin: Option<String>,  // Written to the command's standard input, e.g. "{text}"
+    #[serde(default)]
+    pub cwd: Option<String>,
+    #[serde(default)]
+    pub env: Option<HashMap<String, String>>,
+    #[serde(default)]
+    pub timeout_ms: Option<u64>,  // Defaults to 10 seconds
+    #[serde(default)]
+    pub on_timeout: Option<String>,  // "kill" (default), "terminate", or "detach"
+    #[serde(default)]
+    pub replace_text: bool,  // Copy the command's output instead of the dictated text
+}
+
+/// A setting that takes either one string or a list of them
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum StringOrList {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl StringOrList {
+    /// The configured entries, in order
+    pub fn entries(&self) -> Vec<String> {
+        match self {
+            StringOrList::One(entry) => vec![entry.clone()],
+            StringOrList::Many(entries) => entries.clone(),
+        }
+    }
+}
+
+/// Rules deciding which recordings are processed
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct FilterConfig {
+    #[serde(rename = "match", default)]
+    pub combine: Option<String>,  // "all" (default) or "any"
+    #[serde(default)]
+    pub mode: Option<StringOrList>,  // Mode names or globs, e.g. ["Literal", "Note*"]
+    #[serde(default)]
+    pub language: Option<StringOrList>,
+    #[serde(default)]
+    pub min_duration_secs: Option<f64>,
+    #[serde(default)]
+    pub max_duration_secs: Option<f64>,
+    #[serde(default)]
+    pub min_length: Option<usize>,  // Result text length in characters
+    #[serde(default)]
+    pub max_length: Option<usize>,
+    #[serde(default)]
+    pub text_regex: Option<String>,  // Regex the result text must match
+}
+
+/// A routing rule that sends matching results through their own pipeline
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct RouteConfig {
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub mode: Option<String>,  // Shorthand for a mode name rule
+    #[serde(default)]
+    pub filter: Option<FilterConfig>,
+    #[serde(default)]
+    pub cleaning: Option<String>,  // Name of a [cleaning_profiles.<name>] table
+    #[serde(default)]
+    pub keyphrase_set: Option<String>,  // Name of a [keyphrase_sets.<name>] table
+    #[serde(default)]
+    pub clipboard: Option<bool>,  // Copy to the clipboard, defaults to true
+    #[serde(default)]
+    pub clipboard_format: Option<String>,
+    #[serde(default)]
+    pub append_file: Option<String>,  // Append each result to this file
+    #[serde(default)]
+    pub note_dir: Option<String>,  // Write each result to a new file in this directory
+    #[serde(default)]
+    pub fallthrough: bool,  // Keep checking later routes after this one matches
+}
+
+/// A watched directory with its own processing profile
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct WatchConfig {
+    #[serde(default)]
+    pub name: Option<String>,
+    pub path: String,
+    #[serde(default)]
+    pub pattern: Option<String>,  // Glob for file names, defaults to "meta.json"
+    #[serde(default)]
+    pub regex: Option<String>,  // Regex for file names, instead of a glob pattern
+    #[serde(default)]
+    pub recursive: Option<bool>,  // Watch subdirectories (default true)
+    #[serde(default)]
+    pub max_depth: Option<usize>,  // 1 = files directly in the watch directory
+    #[serde(default)]
+    pub watcher_backend: Option<String>,  // Overrides the global watcher_backend
+    #[serde(default)]
+    pub poll_interval_ms: Option<u64>,  // Overrides the global poll_interval_ms
+    #[serde(default)]
+    pub result_field_preference: Option<StringOrList>,
+    #[serde(default)]
+    pub clipboard_format: Option<String>,
+    #[serde(default)]
+    pub keyphrase_set: Option<String>,  // Name of a [keyphrase_sets.<name>] table
+    #[serde(default)]
+    pub filter: Option<FilterConfig>,  // Replaces the global [filter] table
+    #[serde(default)]
+    pub mode_name: Option<String>,
+    #[serde(default)]
+    pub route: Option<Vec<RouteConfig>>,  // Replaces the global [[route]] list
+    #[serde(default)]
+    pub file_stability: Option<FileStabilityConfig>,  // Replaces the global [file_stability] table
+}
+
+/// Startup backfill options
+#[derive(Debug, Clone, Deserialize)]
+pub struct BackfillConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default)]
+    pub since: Option<String>,  // "last_run", a duration like "2h", or a timestamp
+}
+
+/// How to decide that a watched file has been completely written
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct FileStabilityConfig {
+    #[serde(default)]
+    pub stable_ms: Option<u64>,  // Size and mtime must stay unchanged this long, defaults to 250
+    #[serde(default)]
+    pub check_interval_ms: Option<u64>,  // How often to check, defaults to 50
+    #[serde(default)]
+    pub timeout_ms: Option<u64>,  // Give up waiting after this long, defaults to 10000
+    #[serde(default)]
+    pub sentinel: Option<String>,  // Sibling file that must exist, e.g. "output.wav"
+    #[serde(default)]
+    pub required_key: Option<String>,  // JSON key that must be present, e.g. "result"
+    #[serde(default)]
+    pub max_retries: Option<u32>,  // Attempts to read and parse the file, defaults to 5
+    #[serde(default)]
+    pub retry_delay_ms: Option<u64>,  // Delay between attempts, defaults to 500
+}
+
+/// Options for building the transcript from timed segments
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct SegmentsConfig {
+    #[serde(default)]
+    pub enabled: bool,  // Copy text built from the segments instead of the result field
+    #[serde(default)]
+    pub timestamps: bool,  // One line per segment, prefixed with [mm:ss]
+    #[serde(default)]
+    pub paragraph_gap_secs: Option<f64>,  // New paragraph after a pause this long
+    #[serde(default)]
+    pub subtitles: Option<String>,  // "none", "srt", "vtt", or "both"
+}
+
+/// Background worker queue options
+#[derive(Debug, Clone, Deserialize)]
+pub struct QueueConfig {
+    #[serde(default)]
+    pub workers: Option<usize>,  // Defaults to 2
+    #[serde(default)]
+    pub depth: Option<usize>,  // Pending jobs per worker, defaults to 64
+    #[serde(default)]
+    pub overflow: Option<String>,  // "block", "drop_newest", or "drop_oldest"
+    #[serde(default)]
+    pub shutdown_timeout_secs: Option<u64>,  // How long to wait for queued files on exit, defaults to 10
+}
+
+/// Processed-file ledger options
+#[derive(Debug, Clone, Deserialize)]
+pub struct LedgerConfig {
+    #[serde(default)]
+    pub enabled: Option<bool>,  // Defaults to true
+    #[serde(default)]
+    pub retention_days: Option<u64>,  // Defaults to 30
+}
+
+/// Configuration structure for the application
+#[derive(Debug, Clone, Default, Deserialize)]
 pub struct AppConfig {
     pub watch_dir: Option<String>,
+    pub pattern: Option<String>,  // File name glob for watch_dir, defaults to "meta.json"
+    pub regex: Option<String>,    // Or a file name regex for watch_dir
+    pub max_depth: Option<usize>, // Recursion limit for watch_dir, 1 = files directly in it
     pub log_file: Option<String>,
     pub log_level: Option<String>,
     pub echo_to_stdout: Option<bool>,
     pub detect_keyphrases: Option<bool>,
-    pub keyphrases: Option<HashMap<String, String>>,
+    pub keyphrases: Option<HashMap<String, KeyphraseEntry>>,
+    pub keyphrase: Option<Vec<KeyphraseDefinition>>,  // [[keyphrase]] entries, checked before [keyphrases]
     pub dry_run: Option<bool>,
     pub disable_logs: Option<bool>,
     pub disable_clipboard: Option<bool>,
     pub clipboard_format: Option<String>,
-    pub result_field_preference: Option<String>, // "llm", "raw", "intermediate", or "auto"
+    pub result_field_preference: Option<StringOrList>, // "llm", "raw", "intermediate", "auto", JSON Pointers or JSONPaths
     pub text_cleaning: Option<TextCleaningOptions>,
     pub keyphrase_settings: Option<KeyphraseConfig>,
     pub mode_name: Option<String>, // Filter processing by mode name
+    pub filter: Option<FilterConfig>,
+    pub state_dir: Option<String>,
+    pub backfill: Option<BackfillConfig>,
+    pub ledger: Option<LedgerConfig>,
+    pub file_stability: Option<FileStabilityConfig>,
+    pub segments: Option<SegmentsConfig>,
+    pub queue: Option<QueueConfig>,
+    pub watch: Option<Vec<WatchConfig>>,
+    pub watcher_backend: Option<String>, // "auto", "native", or "poll"
+    pub poll_interval_ms: Option<u64>,   // Polling interval for the poll backend
+    pub keyphrase_sets: Option<HashMap<String, HashMap<String, KeyphraseEntry>>>,
+    pub cleaning_profiles: Option<HashMap<String, TextCleaningOptions>>,
+    pub route: Option<Vec<RouteConfig>>,
 }
 
 /// sw-catcher: Monitors a directory for meta.json files and copies LLM results to clipboard
-#[derive(Parser)]
+#[derive(Parser, Debug, Clone, Default)]
 #[command(name = "sw-catcher", about, long_about = None, version)]
 pub struct Opts {
     /// Directory to watch for new meta.json files
@@ -81,13 +399,25 @@ pub struct Opts {
     #[arg(short = 'c', long, value_name = "FORMAT")]
     pub clipboard_format: Option<String>,
     
-    /// Result field to use (llm, raw, intermediate, auto)
+    /// Result field to use (llm, raw, intermediate, auto, a JSON Pointer or a JSONPath)
     #[arg(short = 'r', long, value_name = "FIELD")]
     pub result_field: Option<String>,
     
     /// Disable logging completely (equivalent to logging to /dev/null)
     #[arg(long)]
     pub disable_logs: bool,
+
+    /// Process existing meta.json files newer than CUTOFF on startup ("last_run", "2h", or a timestamp)
+    #[arg(short = 'b', long, value_name = "CUTOFF")]
+    pub backfill_since: Option<String>,
+
+    /// Print the processed-file ledger and exit
+    #[arg(long)]
+    pub show_ledger: bool,
+
+    /// Clear the processed-file ledger and exit
+    #[arg(long)]
+    pub clear_ledger: bool,
 }
 
 /// Application state shared between components
@@ -112,6 +442,8 @@ pub fn create_default_config_file(path: &str) -> std::io::Result<()> {
 # Uncomment and modify the options you want to change
 
 # watch_dir = "/path/to/directory"
+# pattern = "meta.json"           # File names to process in watch_dir, or regex = "..."
+# max_depth = 2                   # 1 = only files directly in watch_dir
 # log_file = "sw-catcher.log"
 log_level = "info"                # error, warn, info, debug, trace
 echo_to_stdout = true
@@ -120,21 +452,157 @@ detect_keyphrases = true          # enable keyphrase detection
 # disable_logs = false            # Disable logging completely
 clipboard_format = "plaintext"    # plaintext, richtext, markdown
 result_field_preference = "auto"  # llm, raw, intermediate, auto
+# result_field_preference = ["/segments/*/text", "$.output.choices[0].text", "auto"]  # Or JSON Pointers/JSONPaths, tried in order
 # disable_clipboard = false       # Disable copying to clipboard
 # mode_name = "Literal"           # Optional: Filter processing by mode name
+# state_dir = "/path/to/state"    # Where sw-catcher keeps its run state
+watcher_backend = "auto"          # auto, native, poll (use poll for NFS/SMB/sshfs mounts)
+# poll_interval_ms = 2000         # How often the poll backend scans for changes
+
+[queue]
+workers = 2                       # Worker threads; files for the same output run in order
+depth = 64                        # Pending files per worker
+overflow = "block"                # block, drop_newest, drop_oldest
+shutdown_timeout_secs = 10        # On Ctrl-C/SIGTERM, wait this long for queued files
+
+[file_stability]
+stable_ms = 250                   # Process once size and mtime stop changing for this long
+timeout_ms = 10000                # Give up waiting after this long
+# sentinel = "output.wav"         # Also wait for this sibling file to appear
+# required_key = "result"         # Also wait for this JSON key to be present
+max_retries = 5                   # Attempts to read and parse the file
+retry_delay_ms = 500
+
+# Only process some recordings; rules combine with match = "all" or "any"
+# [filter]
+# match = "all"
+# mode = ["Literal", "Note*"]     # Mode names or globs
+# language = ["en", "de"]
+# min_duration_secs = 1.0
+# max_duration_secs = 600
+# min_length = 5                  # Result text length in characters
+# text_regex = "(?i)^note"
+
+[segments]
+enabled = false                   # Build the copied text from the timed segments
+timestamps = false                # One line per segment, prefixed with [mm:ss]
+paragraph_gap_secs = 2.0          # Start a new paragraph after a pause this long
+subtitles = "none"                # none, srt, vtt, both - written next to the recording when enabled
+
+[ledger]
+enabled = true                    # Remember processed files across restarts
+retention_days = 30
+
+[backfill]
+enabled = false                   # Process recordings that arrived while sw-catcher was down
+since = "last_run"                # last_run, a duration like "2h", or "2024-05-01T09:00:00Z"
+
+# Routing rules, checked in order; the first match wins unless it sets fallthrough.
+# Results that match no route use the settings above.
+# [[route]]
+# name = "email"
+# mode = "Email"                  # Shorthand for [route.filter] mode = "Email"
+# clipboard_format = "markdown"
+# append_file = "~/Documents/dictated-emails.md"
+# cleaning = "prose"              # Uses [cleaning_profiles.prose]
+#
+# [[route]]
+# name = "notes"
+# clipboard = false
+# note_dir = "~/Obsidian/Inbox"   # One new file per recording
+# keyphrase_set = "notes"
+# fallthrough = false
+# [route.filter]                  # Full filter rules, like [filter]
+# mode = "Note*"
+#
+# [cleaning_profiles.prose]
+# trim_whitespace = true
+# capitalize_sentences = true
+
+# Additional watched directories, each with its own profile
+# [[watch]]
+# name = "other-tool"
+# path = "/path/to/other/recordings"
+# pattern = "*.result.json"       # Glob for file names (default "meta.json")
+# regex = "^transcript-\\d+\\.json$"  # Or a regex instead of a glob
+# recursive = true                # Watch subdirectories
+# max_depth = 2                   # 1 = only files directly in path
+# watcher_backend = "poll"        # Per-directory backend override
+# result_field_preference = "raw"
+# clipboard_format = "markdown"
+# keyphrase_set = "notes"         # Uses [keyphrase_sets.notes] instead of [keyphrases]
+# mode_name = "Note"
+# [watch.filter]                  # Replaces the global [filter] for this directory
+# language = "en"
+# [[watch.route]]                 # Replaces the global [[route]] list for this directory
+# note_dir = "~/Notes/other-tool"
+# [watch.file_stability]          # Replaces the global [file_stability] for this directory
+# sentinel = "output.wav"
+
+# [keyphrase_sets.notes]
+# "open notes" = "Notes"
 
 [keyphrases]
 # Application examples
 # "open browser" = "Firefox"
 # "start notepad" = "notepad"
 
-# Web service examples
-# "search google" = "https://www.google.com/search?q="
-# "search wikipedia" = "https://en.wikipedia.org/wiki/Special:Search?search="
+# Web service examples; {query} is replaced by the URL-encoded text after the keyphrase:
+# {query} rest of the sentence, {query:words:3} next 3 words, {query:segment} up to the next keyphrase
+# "search google" = "https://www.google.com/search?q={query}"
+# "search wikipedia" = "https://en.wikipedia.org/wiki/Special:Search?search={query:segment}"
+
+# Regex keyphrases start with "re:"; named groups fill {name} in the action
+# "re:remind me in (?P<n>\\d+) minutes" = "https://example.com/remind?minutes={n}"
+# "re:open (obsidian|notes)" = "Obsidian"
+
+# A table sets options: the highest priority (then the longest phrase) wins where
+# keyphrases overlap, and strip = false leaves the phrase in the copied text
+# "open notes" = { action = "Notes", priority = 10, strip = false }
+
+# position limits where a keyphrase counts: start, end, alone (a sentence by itself), anywhere
+# "send that" = { action = "Mail", position = "end" }
+
+# [[keyphrase]] entries keep their order and take more options; they are checked
+# before [keyphrases]
+# [[keyphrase]]
+# phrase = "open notes"
+# aliases = ["show my notes"]
+# action = ["Obsidian", "https://example.com/notes"]  # A list runs in order
+# matching_strategy = "wholeword"  # Overrides [keyphrase_settings]
+# punctuation_handling = "all"     # After the phrase: ignore, sentence, all
+# strip = true                     # Remove the phrase from the copied text
+# enabled = true
+# description = "Opens my notes"
+
+# A [[keyphrase]] can run steps instead of an action. Each step is an action, clipboard
+# text (copied instead of the dictated text) or a value; save_as makes its output {name}
+# for later steps. Steps and actions can use {text} (the copied text), {segment_after}
+# and {query}.
+# [[keyphrase]]
+# phrase = "to obsidian"
+# steps = [
+#     { action = "Obsidian" },
+#     { clipboard = "{segment_after}", delay_ms = 500, on_failure = "continue" },
+# ]
+
+# exec runs a command without a shell. Arguments, stdin and env values can use {text},
+# {segment_after}, {query}, {file} and the recording's {mode}, {language}, {duration},
+# {datetime}, {model}, {prompt} or any other meta.json field by name, e.g. {modelKey};
+# replace_text copies the output instead
+# [[keyphrase]]
+# phrase = "summarize"
+# exec = { argv = ["llm", "-s", "Summarize"], stdin = "{text}", replace_text = true }
+# # Also cwd, env = { KEY = "value" }, timeout_ms = 10000, on_timeout = "kill" (terminate, detach)
 
 [keyphrase_settings]
-matching_strategy = "simple"     # simple, wholeword, exact
+matching_strategy = "simple"     # simple, wholeword, exact, regex, fuzzy
 punctuation_handling = "sentence" # ignore, sentence, all
+# fuzzy_threshold = 1             # Fuzzy: edits allowed per word ("solver" for "soulver")
+# phonetic = false                # Fuzzy: also accept words that sound alike (Soundex)
+# min_confidence = 0.8            # Fuzzy: weaker matches are logged but not acted on
+# normalization = "nfc"           # nfc, nfkc (also matches "²" to "2", fullwidth letters), none
+# escape_word = "literal"         # "literal open browser" types "open browser" instead of acting
 
 [text_cleaning]
 trim_whitespace = true
@@ -152,40 +620,13 @@ capitalize_sentences = false
 
 /// Load configuration from file and command line arguments
 pub fn load_config() -> NotifyResult<AppState> {
-    let opts = Opts::parse();
+    load_config_with_opts(Opts::parse())
+}
 
+/// Load configuration from file, combined with already parsed command line options
+pub fn load_config_with_opts(opts: Opts) -> NotifyResult<AppState> {
     // Load configuration file
-    let config_path = "config.toml";
-    let file_config = if std::path::Path::new(config_path).exists() {
-        let config_content = std::fs::read_to_string(config_path)
-            .map_err(|e| NotifyError::generic(&format!("Failed to read {}: {}", config_path, e)))?;
-
-        toml::from_str::<AppConfig>(&config_content)
-            .map_err(|e| NotifyError::generic(&format!("Invalid TOML in {}: {}", config_path, e)))?
-    } else {
-        debug!("No config file found at {}, using defaults", config_path);
-        // Create a default configuration with application keyphrases
-        let mut keyphrases = HashMap::new();
-        keyphrases.insert("open browser".to_string(), "firefox".to_string());
-        keyphrases.insert("search google".to_string(), "https://www.google.com/search?q=".to_string());
-        
-        AppConfig {
-            watch_dir: None,
-            log_file: None,
-            log_level: None,
-            echo_to_stdout: None,
-            detect_keyphrases: Some(true), // Enable keyphrases by default
-            keyphrases: Some(keyphrases),  // Add default keyphrases
-            dry_run: None,
-            clipboard_format: None,
-            result_field_preference: None,
-            text_cleaning: None,
-            disable_logs: None,
-            disable_clipboard: None,
-            keyphrase_settings: None,
-            mode_name: None,  // No default mode filter
-        }
-    };
+    let file_config = load_config_file(CONFIG_FILE)?;
 
     // Set up app state by combining file config and command line options
     let dry_run = opts.dry_run || file_config.dry_run.unwrap_or(false);
@@ -198,50 +639,38 @@ pub fn load_config() -> NotifyResult<AppState> {
     );
 
     // Override result_field_preference from command line if specified
-    let config = if opts.result_field.is_some() {
+    let mut config = if opts.result_field.is_some() {
         let mut updated_config = file_config.clone();
-        updated_config.result_field_preference = opts.result_field;
+        updated_config.result_field_preference = opts.result_field.map(StringOrList::One);
         updated_config
     } else {
         file_config
     };
 
-    // Validate watch path
-    if let Some(ref watch_path) = opts
-        .watch_dir
-        .clone()
-        .or_else(|| config.watch_dir.as_ref().map(PathBuf::from))
-    {
-        if !watch_path.exists() || !watch_path.is_dir() {
-            error!("Watch directory does not exist: {:?}", watch_path);
-            return Err(NotifyError::generic(&format!(
-                "Watch directory does not exist: {:?}",
-                watch_path
-            )));
-        }
+    // A backfill cutoff on the command line enables backfill for this run
+    if let Some(since) = opts.backfill_since {
+        config.backfill = Some(BackfillConfig {
+            enabled: true,
+            since: Some(since),
+        });
+    }
 
-        // Check if directory is readable
-        match std::fs::read_dir(watch_path) {
-            Ok(_) => {
-                debug!("Watch directory is readable: {:?}", watch_path);
-            }
-            Err(e) => {
-                error!(
-                    "Watch directory exists but cannot be read: {:?} ({})",
-                    watch_path, e
-                );
-                return Err(NotifyError::generic(&format!(
-                    "Watch directory exists but cannot be read: {:?} ({})",
-                    watch_path, e
-                )));
-            }
-        }
-    } else {
+    // A watch directory on the command line replaces the one from the config file
+    if let Some(watch_dir) = opts.watch_dir {
+        config.watch_dir = Some(watch_dir.to_string_lossy().to_string());
+    }
+
+    // Validate watch paths
+    let watch_paths = get_watch_paths(&config);
+    if watch_paths.is_empty() {
         error!("No watch directory specified in command line or config file");
         return Err(NotifyError::generic(
             "No watch directory specified in command line or config file",
         ));
     }
+    for watch_path in &watch_paths {
+        validate_watch_path(watch_path)?;
+    }
 
     Ok(AppState {
         config,
@@ -251,6 +680,58 @@ pub fn load_config() -> NotifyResult<AppState> {
     })
 }
 
+/// Check that a watch directory exists and is readable
+fn validate_watch_path(watch_path: &Path) -> NotifyResult<()> {
+    if !watch_path.exists() || !watch_path.is_dir() {
+        error!("Watch directory does not exist: {:?}", watch_path);
+        return Err(NotifyError::generic(&format!(
+            "Watch directory does not exist: {:?}",
+            watch_path
+        )));
+    }
+
+    // Check if directory is readable
+    match std::fs::read_dir(watch_path) {
+        Ok(_) => {
+            debug!("Watch directory is readable: {:?}", watch_path);
+            Ok(())
+        }
+        Err(e) => {
+            error!(
+                "Watch directory exists but cannot be read: {:?} ({})",
+                watch_path, e
+            );
+            Err(NotifyError::generic(&format!(
+                "Watch directory exists but cannot be read: {:?} ({})",
+                watch_path, e
+            )))
+        }
+    }
+}
+
+/// Read and parse a config file, falling back to defaults if it doesn't exist
+pub fn load_config_file(config_path: &str) -> NotifyResult<AppConfig> {
+    if Path::new(config_path).exists() {
+        let config_content = std::fs::read_to_string(config_path)
+            .map_err(|e| NotifyError::generic(&format!("Failed to read {}: {}", config_path, e)))?;
+
+        toml::from_str::<AppConfig>(&config_content)
+            .map_err(|e| NotifyError::generic(&format!("Invalid TOML in {}: {}", config_path, e)))
+    } else {
+        debug!("No config file found at {}, using defaults", config_path);
+        // Create a default configuration with application keyphrases
+        let mut keyphrases = HashMap::new();
+        keyphrases.insert("open browser".to_string(), "firefox".into());
+        keyphrases.insert("search google".to_string(), "https://www.google.com/search?q={query}".into());
+
+        Ok(AppConfig {
+            detect_keyphrases: Some(true), // Enable keyphrases by default
+            keyphrases: Some(keyphrases),  // Add default keyphrases
+            ..Default::default()
+        })
+    }
+}
+
 /// Parse a string into a log level
 pub fn parse_log_level(level: &str) -> LevelFilter {
     match level.to_lowercase().as_str() {
@@ -280,15 +761,108 @@ pub fn parse_result_field_preference(preference: &str) -> &'static str {
     }
 }
 
+/// Parse a string into a WatcherBackend
+pub fn parse_watcher_backend(backend: &str) -> WatcherBackend {
+    match backend.to_lowercase().as_str() {
+        "native" | "notify" => WatcherBackend::Native,
+        "poll" | "polling" => WatcherBackend::Poll,
+        "auto" => WatcherBackend::Auto,
+        _ => {
+            debug!("Invalid watcher backend '{}', defaulting to 'auto'", backend);
+            WatcherBackend::Auto
+        }
+    }
+}
+
+/// Parse a string into an OverflowPolicy
+pub fn parse_overflow_policy(policy: &str) -> OverflowPolicy {
+    match policy.to_lowercase().as_str() {
+        "drop_newest" | "drop-newest" | "dropnewest" => OverflowPolicy::DropNewest,
+        "drop_oldest" | "drop-oldest" | "dropoldest" => OverflowPolicy::DropOldest,
+        "block" => OverflowPolicy::Block,
+        _ => {
+            debug!("Invalid queue overflow policy '{}', defaulting to 'block'", policy);
+            OverflowPolicy::Block
+        }
+    }
+}
+
+/// Parse a string into a FilterCombine
+pub fn parse_filter_combine(combine: &str) -> FilterCombine {
+    match combine.to_lowercase().as_str() {
+        "any" | "or" => FilterCombine::Any,
+        "all" | "and" => FilterCombine::All,
+        _ => {
+            debug!("Invalid filter match '{}', defaulting to 'all'", combine);
+            FilterCombine::All
+        }
+    }
+}
+
+/// Parse a string into the subtitle formats to write
+pub fn parse_subtitle_formats(formats: &str) -> Vec<SubtitleFormat> {
+    match formats.to_lowercase().as_str() {
+        "srt" => vec![SubtitleFormat::Srt],
+        "vtt" | "webvtt" => vec![SubtitleFormat::Vtt],
+        "both" => vec![SubtitleFormat::Srt, SubtitleFormat::Vtt],
+        "none" => Vec::new(),
+        _ => {
+            debug!("Invalid subtitle format '{}', defaulting to 'none'", formats);
+            Vec::new()
+        }
+    }
+}
+
 /// Parse a string into a KeyphraseMatchingStrategy
 pub fn parse_keyphrase_matching_strategy(strategy: &str) -> KeyphraseMatchingStrategy {
     match strategy.to_lowercase().as_str() {
         "wholeword" | "whole_word" | "whole-word" => KeyphraseMatchingStrategy::WholeWord,
         "exact" => KeyphraseMatchingStrategy::Exact,
+        "regex" => KeyphraseMatchingStrategy::Regex,
+        "fuzzy" => KeyphraseMatchingStrategy::Fuzzy,
         _ => KeyphraseMatchingStrategy::Simple,  // Default to simple matching
     }
 }
 
+/// Parse a string into a Normalization
+pub fn parse_normalization(normalization: &str) -> Normalization {
+    match normalization.to_lowercase().as_str() {
+        "none" => Normalization::None,
+        "nfkc" => Normalization::Nfkc,
+        _ => Normalization::Nfc,  // Default to canonical equivalence
+    }
+}
+
+/// Get the configured keyphrase matching strategy
+pub fn configured_matching_strategy(config: &AppConfig) -> KeyphraseMatchingStrategy {
+    config
+        .keyphrase_settings
+        .as_ref()
+        .and_then(|settings| settings.matching_strategy.as_deref())
+        .map(parse_keyphrase_matching_strategy)
+        .unwrap_or(KeyphraseMatchingStrategy::Simple)
+}
+
+/// Get the configured Unicode normalization for keyphrase matching
+pub fn configured_normalization(config: &AppConfig) -> Normalization {
+    config
+        .keyphrase_settings
+        .as_ref()
+        .and_then(|settings| settings.normalization.as_deref())
+        .map(parse_normalization)
+        .unwrap_or(Normalization::Nfc)
+}
+
+/// Parse a string into a KeyphrasePosition
+pub fn parse_keyphrase_position(position: &str) -> KeyphrasePosition {
+    match position.to_lowercase().as_str() {
+        "start" => KeyphrasePosition::Start,
+        "end" => KeyphrasePosition::End,
+        "alone" => KeyphrasePosition::Alone,
+        _ => KeyphrasePosition::Anywhere,  // Default to anywhere in the text
+    }
+}
+
 /// Parse a string into a PunctuationHandling
 pub fn parse_punctuation_handling(handling: &str) -> PunctuationHandling {
     match handling.to_lowercase().as_str() {
@@ -298,6 +872,14 @@ pub fn parse_punctuation_handling(handling: &str) -> PunctuationHandling {
     }
 }
 
+/// Expand a leading `~/` to the home directory
+pub fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), dirs::home_dir()) {
+        (Some(rest), Some(home)) => home.join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
 /// Get the default log directory based on platform
 pub fn get_default_log_directory() -> PathBuf {
     #[cfg(target_os = "macos")]
@@ -311,6 +893,27 @@ pub fn get_default_log_directory() -> PathBuf {
     }
 }
 
+/// Get the default directory for persistent run state
+pub fn get_default_state_directory() -> PathBuf {
+    dirs::data_local_dir()
+        .map(|dir| dir.join("sw-catcher"))
+        .unwrap_or_else(|| PathBuf::from("."))
+}
+
+/// Get the configured state directory
+pub fn get_state_directory(config: &AppConfig) -> PathBuf {
+    config
+        .state_dir
+        .as_ref()
+        .map(PathBuf::from)
+        .unwrap_or_else(get_default_state_directory)
+}
+
+/// Get the path of the processed-file ledger
+pub fn get_ledger_path(config: &AppConfig) -> PathBuf {
+    get_state_directory(config).join(crate::ledger::LEDGER_FILE)
+}
+
 /// Get the configured log file path
 pub fn get_log_file_path(opts: &Opts, config: &AppConfig) -> String {
     // If logging is disabled, return /dev/null
@@ -349,6 +952,16 @@ pub fn get_watch_path(opts: &Opts, config: &AppConfig) -> Option<PathBuf> {
         .or_else(|| config.watch_dir.as_ref().map(PathBuf::from))
 }
 
+/// Get all configured watch directories: `watch_dir` followed by any `[[watch]]` entries
+pub fn get_watch_paths(config: &AppConfig) -> Vec<PathBuf> {
+    config
+        .watch_dir
+        .iter()
+        .map(PathBuf::from)
+        .chain(config.watch.iter().flatten().map(|w| PathBuf::from(&w.path)))
+        .collect()
+}
+
 /// Get the configured log level
 pub fn get_log_level(opts: &Opts, config: &AppConfig) -> LevelFilter {
     parse_log_level(
@@ -366,6 +979,7 @@ pub fn print_usage_guide() {
     eprintln!("  2. OR create a config.toml with configuration options:");
     eprintln!("     Example config.toml:");
     eprintln!("     watch_dir = \"/path/to/directory\"");
+    eprintln!("     pattern = \"meta.json\"  # Optional: glob, or regex = \"...\", with max_depth = 2");
     eprintln!("     log_file = \"sw-catcher.log\"");
     eprintln!("     log_level = \"info\"  # error, warn, info, debug, trace");
     eprintln!("     echo_to_stdout = true");
@@ -374,15 +988,83 @@ pub fn print_usage_guide() {
     eprintln!("     disable_logs = false  # Disable logging completely");
     eprintln!("     clipboard_format = \"plaintext\"  # plaintext, richtext, markdown");
     eprintln!("     result_field_preference = \"auto\"  # llm, raw, intermediate, auto");
+    eprintln!("     # or a list of JSON Pointer/JSONPath expressions tried in order:");
+    eprintln!("     # result_field_preference = [\"/segments/*/text\", \"auto\"]");
     eprintln!("     mode_name = \"Literal\"  # Optional: Filter processing by mode name");
+    eprintln!("     watcher_backend = \"auto\"  # auto, native, poll (for network mounts)");
+    eprintln!("     poll_interval_ms = 2000");
+    eprintln!("     [queue]");
+    eprintln!("     workers = 2");
+    eprintln!("     depth = 64");
+    eprintln!("     overflow = \"block\"  # block, drop_newest, drop_oldest");
+    eprintln!("     shutdown_timeout_secs = 10  # Wait for queued files on Ctrl-C/SIGTERM");
+    eprintln!("     [file_stability]");
+    eprintln!("     stable_ms = 250  # Wait for size and mtime to settle");
+    eprintln!("     sentinel = \"output.wav\"  # Optional: wait for a sibling file");
+    eprintln!("     required_key = \"result\"  # Optional: wait for a JSON key");
+    eprintln!("     max_retries = 5");
+    eprintln!("     retry_delay_ms = 500");
+    eprintln!("     [filter]  # Optional: only process matching recordings");
+    eprintln!("     match = \"all\"  # all, any");
+    eprintln!("     mode = [\"Literal\", \"Note*\"]");
+    eprintln!("     language = \"en\"");
+    eprintln!("     min_duration_secs = 1.0");
+    eprintln!("     text_regex = \"(?i)^note\"");
+    eprintln!("     [[route]]  # Optional: send matching results to their own pipeline");
+    eprintln!("     mode = \"Note\"");
+    eprintln!("     clipboard = false");
+    eprintln!("     note_dir = \"~/Obsidian/Inbox\"  # Also: append_file, cleaning, keyphrase_set");
+    eprintln!("     fallthrough = false");
+    eprintln!("     [segments]");
+    eprintln!("     enabled = true  # Build the text from the timed segments");
+    eprintln!("     timestamps = true  # Prefix lines with [mm:ss]");
+    eprintln!("     paragraph_gap_secs = 2.0");
+    eprintln!("     subtitles = \"srt\"  # none, srt, vtt, both");
+    eprintln!("     [ledger]");
+    eprintln!("     enabled = true  # Remember processed files across restarts");
+    eprintln!("     retention_days = 30");
+    eprintln!("     [backfill]");
+    eprintln!("     enabled = true");
+    eprintln!("     since = \"last_run\"  # last_run, a duration like \"2h\", or a timestamp");
+    eprintln!("     [[watch]]  # Optional: more directories, each with its own profile");
+    eprintln!("     path = \"/path/to/other/directory\"");
+    eprintln!("     pattern = \"*.result.json\"  # Glob, or regex = \"...\" (default meta.json)");
+    eprintln!("     max_depth = 2  # Optional: limit recursion, recursive = false for top level only");
+    eprintln!("     keyphrase_set = \"notes\"  # Uses [keyphrase_sets.notes]");
+    eprintln!("     [[watch.route]]  # Optional: routes for this directory instead of [[route]]");
+    eprintln!("     note_dir = \"~/Notes/other\"");
+    eprintln!("     [watch.file_stability]  # Optional: instead of [file_stability], e.g. a sentinel only here");
     eprintln!("     [keyphrases]");
     eprintln!("     # Keyphrase examples:");
     eprintln!("     \"open browser\" = \"https://www.example.com\"");
     eprintln!("     \"send email\" = \"mailto:user@example.com\"");
     eprintln!("     \"start notepad\" = \"notepad\"");
+    eprintln!("     \"search google\" = \"https://www.google.com/search?q={{query}}\"  # Rest of the sentence");
+    eprintln!("     \"look up\" = \"https://example.com/?q={{query:words:3}}\"  # Or {{query:segment}}");
+    eprintln!("     \"re:remind me in (?P<n>\\\\d+) minutes\" = \"https://example.com/?m={{n}}\"  # Regex keyphrase");
+    eprintln!("     \"open notes\" = {{ action = \"Notes\", priority = 10, strip = false }}");
+    eprintln!("     \"send that\" = {{ action = \"Mail\", position = \"end\" }}  # Or start, alone, anywhere");
     eprintln!("     \"important reminder\" = \"\"  # Empty action, just detect");
+    eprintln!("     [[keyphrase]]  # Structured form, checked first and in order");
+    eprintln!("     phrase = \"open notes\"");
+    eprintln!("     aliases = [\"show my notes\"]");
+    eprintln!("     action = [\"Obsidian\", \"https://example.com/notes\"]  # Run in order");
+    eprintln!("     matching_strategy = \"wholeword\"  # Also punctuation_handling, strip, enabled, description");
+    eprintln!("     [[keyphrase]]  # A macro: steps run in order");
+    eprintln!("     phrase = \"to obsidian\"");
+    eprintln!("     steps = [{{ action = \"Obsidian\" }}, {{ clipboard = \"{{segment_after}}\", delay_ms = 500 }}]");
+    eprintln!("     # Steps also take on_failure = \"continue\", value = \"...\" and save_as = \"name\" for {{name}}");
+    eprintln!("     [[keyphrase]]  # Run a command, no shell involved");
+    eprintln!("     phrase = \"summarize\"");
+    eprintln!("     exec = {{ argv = [\"llm\", \"-s\", \"Summarize\"], stdin = \"{{text}}\", replace_text = true }}");
+    eprintln!("     # Also {{segment_after}}, {{file}}, {{mode}}, {{language}}, {{duration}}, {{model}} and other meta.json fields");
+    eprintln!("     # cwd, env, timeout_ms, on_timeout = \"kill\"");
     eprintln!("     [keyphrase_settings]");
-    eprintln!("     matching_strategy = \"simple\"  # simple, wholeword, exact");
+    eprintln!("     matching_strategy = \"simple\"  # simple, wholeword, exact, regex, fuzzy");
+    eprintln!("     fuzzy_threshold = 1  # Fuzzy: edits allowed per word, phonetic = true for sound-alikes");
+    eprintln!("     min_confidence = 0.8  # Fuzzy: weaker matches are logged but not acted on");
+    eprintln!("     normalization = \"nfc\"  # Unicode form used with case folding: nfc, nfkc, none");
+    eprintln!("     escape_word = \"literal\"  # \"literal open browser\" keeps the phrase as text");
     eprintln!("     punctuation_handling = \"sentence\"  # ignore, sentence, all");
     eprintln!("     [text_cleaning]");
     eprintln!("     trim_whitespace = true");
@@ -413,9 +1095,19 @@ mod tests {
         assert_eq!(parse_keyphrase_matching_strategy("whole_word"), KeyphraseMatchingStrategy::WholeWord);
         assert_eq!(parse_keyphrase_matching_strategy("whole-word"), KeyphraseMatchingStrategy::WholeWord);
         assert_eq!(parse_keyphrase_matching_strategy("exact"), KeyphraseMatchingStrategy::Exact);
+        assert_eq!(parse_keyphrase_matching_strategy("Regex"), KeyphraseMatchingStrategy::Regex);
+        assert_eq!(parse_keyphrase_matching_strategy("fuzzy"), KeyphraseMatchingStrategy::Fuzzy);
         assert_eq!(parse_keyphrase_matching_strategy("invalid"), KeyphraseMatchingStrategy::Simple);
     }
     
+    #[test]
+    fn test_parse_normalization() {
+        assert_eq!(parse_normalization("NFKC"), Normalization::Nfkc);
+        assert_eq!(parse_normalization("none"), Normalization::None);
+        assert_eq!(parse_normalization("nfc"), Normalization::Nfc);
+        assert_eq!(parse_normalization("invalid"), Normalization::Nfc);
+    }
+
     #[test]
     fn test_parse_punctuation_handling() {
         assert_eq!(parse_punctuation_handling("ignore"), PunctuationHandling::IgnorePunctuation);
@@ -426,6 +1118,77 @@ mod tests {
         assert_eq!(parse_punctuation_handling("invalid"), PunctuationHandling::RemoveSentenceEnding);
     }
 
+    #[test]
+    fn test_parse_watcher_backend() {
+        assert_eq!(parse_watcher_backend("native"), WatcherBackend::Native);
+        assert_eq!(parse_watcher_backend("POLL"), WatcherBackend::Poll);
+        assert_eq!(parse_watcher_backend("polling"), WatcherBackend::Poll);
+        assert_eq!(parse_watcher_backend("auto"), WatcherBackend::Auto);
+        assert_eq!(parse_watcher_backend("invalid"), WatcherBackend::Auto);
+    }
+
+    #[test]
+    fn test_parse_overflow_policy() {
+        assert_eq!(parse_overflow_policy("block"), OverflowPolicy::Block);
+        assert_eq!(parse_overflow_policy("drop_newest"), OverflowPolicy::DropNewest);
+        assert_eq!(parse_overflow_policy("drop-oldest"), OverflowPolicy::DropOldest);
+        assert_eq!(parse_overflow_policy("invalid"), OverflowPolicy::Block);
+    }
+
+    #[test]
+    fn test_parse_filter_combine() {
+        assert_eq!(parse_filter_combine("any"), FilterCombine::Any);
+        assert_eq!(parse_filter_combine("ALL"), FilterCombine::All);
+        assert_eq!(parse_filter_combine("invalid"), FilterCombine::All);
+    }
+
+    #[test]
+    fn test_parse_subtitle_formats() {
+        assert_eq!(parse_subtitle_formats("srt"), vec![SubtitleFormat::Srt]);
+        assert_eq!(parse_subtitle_formats("WebVTT"), vec![SubtitleFormat::Vtt]);
+        assert_eq!(parse_subtitle_formats("both"), vec![SubtitleFormat::Srt, SubtitleFormat::Vtt]);
+        assert!(parse_subtitle_formats("none").is_empty());
+        assert!(parse_subtitle_formats("invalid").is_empty());
+    }
+
+    #[test]
+    fn test_field_preference_string_or_list() {
+        let config: AppConfig = toml::from_str(r#"result_field_preference = "raw""#).unwrap();
+        assert_eq!(config.result_field_preference.unwrap().entries(), vec!["raw"]);
+
+        let config: AppConfig =
+            toml::from_str(r#"result_field_preference = ["/segments/*/text", "auto"]"#).unwrap();
+        assert_eq!(
+            config.result_field_preference.unwrap().entries(),
+            vec!["/segments/*/text", "auto"]
+        );
+    }
+
+    #[test]
+    fn test_keyphrase_entries() {
+        let config: AppConfig = toml::from_str(
+            r#"
+            [keyphrases]
+            "open browser" = "firefox"
+            "open notes" = { action = "Notes", priority = 10, strip = false }
+            "send that" = { action = "Mail", position = "end" }
+            "open mail" = { action = "Mail", keep_phrase = true }
+            "#,
+        )
+        .unwrap();
+        let keyphrases = config.keyphrases.unwrap();
+
+        assert_eq!(keyphrases["open browser"].options().action, "firefox");
+        let notes = keyphrases["open notes"].options();
+        assert_eq!((notes.action.as_str(), notes.priority, notes.strip), ("Notes", 10, Some(false)));
+        assert_eq!(keyphrases["send that"].options().position.as_deref(), Some("end"));
+        assert_eq!(keyphrases["open notes"].definition("open notes").strip, Some(false));
+        assert_eq!(keyphrases["open mail"].definition("open mail").strip, Some(false));
+        assert_eq!(keyphrases["open browser"].definition("open browser").strip, None);
+        assert_eq!(parse_keyphrase_position("End"), KeyphrasePosition::End);
+        assert_eq!(parse_keyphrase_position("everywhere"), KeyphrasePosition::Anywhere);
+    }
+
     #[test]
     fn test_parse_result_field_preference() {
         assert_eq!(parse_result_field_preference("llm"), "llm");
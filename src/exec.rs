@@ -0,0 +1,252 @@
+use crate::actions::{query_capture_in, QueryCapture};
+use crate::clipboard::truncate;
+use crate::config::{expand_home, ExecConfig};
+use log::{debug, info, warn};
+use std::io::{Read, Write};
+use std::path::PathBuf;
+use std::process::{Child, Command, Stdio};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+use wait_timeout::ChildExt;
+
+/// Default time a command may run before its timeout policy applies
+pub const DEFAULT_EXEC_TIMEOUT_MS: u64 = 10_000;
+
+/// How long a terminated command gets to exit before it is killed
+const TERMINATE_GRACE: Duration = Duration::from_secs(2);
+
+/// What to do with a command that runs past its timeout
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TimeoutPolicy {
+    /// Kill it straight away
+    ///
+    /// Only the command's own process is killed. Anything it started, such as the
+    /// programs run by a `sh -c` wrapper, keeps running.
+    Kill,
+    /// Ask it to stop (SIGTERM on Unix), then kill it if it hasn't after a grace period
+    Terminate,
+    /// Leave it running and carry on without its output
+    Detach,
+}
+
+/// A command run directly, without a shell, by an `exec` action
+///
+/// Every argument, the stdin payload and the environment values can use the same
+/// `{name}` placeholders as other actions, such as `{text}`, `{segment_after}`,
+/// `{file}` and the recording's `{mode}` or `{language}`. Each placeholder stays
+/// within its argument, so dictated text can't add arguments or run other commands.
+#[derive(Debug, Clone)]
+pub struct ExecAction {
+    /// Program followed by its arguments
+    pub argv: Vec<String>,
+    /// Text written to the command's standard input
+    pub stdin: Option<String>,
+    pub cwd: Option<PathBuf>,
+    pub env: Vec<(String, String)>,
+    pub timeout: Duration,
+    pub on_timeout: TimeoutPolicy,
+    /// Copy the command's output instead of the dictated text
+    pub replace_text: bool,
+}
+
+impl ExecAction {
+    /// The capture asked for by a `{query}` placeholder in the arguments or stdin
+    pub fn query_capture(&self) -> Option<QueryCapture> {
+        self.argv
+            .iter()
+            .chain(&self.stdin)
+            .find_map(|template| query_capture_in(template))
+    }
+
+    /// Rewrite the arguments, stdin and environment values
+    pub(crate) fn map_templates(&self, rewrite: &dyn Fn(&str) -> String) -> ExecAction {
+        ExecAction {
+            argv: self.argv.iter().map(|arg| rewrite(arg)).collect(),
+            stdin: self.stdin.as_deref().map(rewrite),
+            env: self
+                .env
+                .iter()
+                .map(|(name, value)| (name.clone(), rewrite(value)))
+                .collect(),
+            ..self.clone()
+        }
+    }
+}
+
+/// Resolve an `exec` table from the config
+pub fn resolve_exec(config: &ExecConfig) -> Result<ExecAction, String> {
+    if config.argv.first().is_none_or(|program| program.trim().is_empty()) {
+        return Err("exec needs a program in argv".to_string());
+    }
+
+    let mut env: Vec<(String, String)> = config.env.clone().unwrap_or_default().into_iter().collect();
+    env.sort();
+
+    Ok(ExecAction {
+        argv: config.argv.clone(),
+        stdin: config.stdin.clone(),
+        cwd: config.cwd.as_deref().map(expand_home),
+        env,
+        timeout: Duration::from_millis(config.timeout_ms.unwrap_or(DEFAULT_EXEC_TIMEOUT_MS)),
+        on_timeout: config
+            .on_timeout
+            .as_deref()
+            .map(parse_timeout_policy)
+            .unwrap_or(TimeoutPolicy::Kill),
+        replace_text: config.replace_text,
+    })
+}
+
+/// Parse a string into a TimeoutPolicy
+pub fn parse_timeout_policy(policy: &str) -> TimeoutPolicy {
+    match policy.to_lowercase().as_str() {
+        "terminate" => TimeoutPolicy::Terminate,
+        "detach" => TimeoutPolicy::Detach,
+        _ => TimeoutPolicy::Kill,  // Default to killing the command
+    }
+}
+
+/// Run the command and return what it wrote to standard output
+///
+/// Fails if the command can't start, exits unsuccessfully, or times out. A detached
+/// command that times out gives no output instead.
+pub fn run_exec(exec: &ExecAction) -> Result<String, Box<dyn std::error::Error>> {
+    let (program, args) = exec.argv.split_first().ok_or("exec action has no program")?;
+    info!("Running command: {:?}", exec.argv);
+
+    let mut command = Command::new(program);
+    command
+        .args(args)
+        .envs(exec.env.iter().map(|(name, value)| (name, value)))
+        .stdin(if exec.stdin.is_some() { Stdio::piped() } else { Stdio::null() })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    if let Some(cwd) = &exec.cwd {
+        command.current_dir(cwd);
+    }
+    let mut child = command.spawn()?;
+
+    // Feed stdin and drain the output on their own threads, so a full pipe can't stall the command
+    if let (Some(input), Some(mut pipe)) = (exec.stdin.clone(), child.stdin.take()) {
+        thread::spawn(move || {
+            if let Err(e) = pipe.write_all(input.as_bytes()) {
+                debug!("Command stopped reading stdin: {}", e);
+            }
+        });
+    }
+    let stdout = child.stdout.take().map(read_to_end);
+    let stderr = child.stderr.take().map(read_to_end);
+
+    let status = match child.wait_timeout(exec.timeout)? {
+        Some(status) => status,
+        None => {
+            stop_after_timeout(&mut child, exec.on_timeout)?;
+            if exec.on_timeout == TimeoutPolicy::Detach {
+                warn!("{} is still running after {:?}, leaving it running", program, exec.timeout);
+                // Reap it whenever it does exit
+                thread::spawn(move || child.wait());
+                return Ok(String::new());
+            }
+            return Err(format!("{} timed out after {:?}", program, exec.timeout).into());
+        }
+    };
+
+    let stdout = join_output(stdout);
+    let stderr = join_output(stderr);
+    if !status.success() {
+        return Err(format!("{} failed ({}): {}", program, status, truncate(stderr.trim(), 200)).into());
+    }
+
+    debug!("{} wrote: {}", program, truncate(stdout.trim(), 60));
+    Ok(stdout.trim_end().to_string())
+}
+
+/// Apply the timeout policy to a command that is still running
+fn stop_after_timeout(child: &mut Child, policy: TimeoutPolicy) -> std::io::Result<()> {
+    match policy {
+        TimeoutPolicy::Detach => return Ok(()),
+        TimeoutPolicy::Terminate => {
+            terminate(child);
+            if child.wait_timeout(TERMINATE_GRACE)?.is_some() {
+                return Ok(());
+            }
+        }
+        TimeoutPolicy::Kill => {}
+    }
+    child.kill()?;
+    child.wait()?;
+    Ok(())
+}
+
+/// Ask the command to stop
+#[cfg(unix)]
+fn terminate(child: &Child) {
+    // SAFETY: kill only sends a signal to the child's process ID, which we haven't reaped yet
+    unsafe {
+        libc::kill(child.id() as libc::pid_t, libc::SIGTERM);
+    }
+}
+
+/// Ask the command to stop (other platforms have no gentler signal, so this waits for the kill)
+#[cfg(not(unix))]
+fn terminate(_child: &Child) {}
+
+fn read_to_end(mut pipe: impl Read + Send + 'static) -> JoinHandle<Vec<u8>> {
+    thread::spawn(move || {
+        let mut output = Vec::new();
+        let _ = pipe.read_to_end(&mut output);
+        output
+    })
+}
+
+fn join_output(reader: Option<JoinHandle<Vec<u8>>>) -> String {
+    reader
+        .and_then(|reader| reader.join().ok())
+        .map(|output| String::from_utf8_lossy(&output).into_owned())
+        .unwrap_or_default()
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    fn exec(argv: &[&str]) -> ExecAction {
+        resolve_exec(&ExecConfig {
+            argv: argv.iter().map(|arg| arg.to_string()).collect(),
+            ..Default::default()
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_run_exec_output() {
+        let mut upper = exec(&["tr", "a-z", "A-Z"]);
+        upper.stdin = Some("{text}".to_string());
+        let upper = upper.map_templates(&|template| template.replace("{text}", "hello; rm -rf ~"));
+        assert_eq!(run_exec(&upper).unwrap(), "HELLO; RM -RF ~");
+
+        let mut env = exec(&["sh", "-c", "echo \"$GREETING from $(pwd)\""]);
+        env.env = vec![("GREETING".to_string(), "hi".to_string())];
+        env.cwd = Some(PathBuf::from("/"));
+        assert_eq!(run_exec(&env).unwrap(), "hi from /");
+
+        let err = run_exec(&exec(&["sh", "-c", "echo oops >&2; exit 3"])).unwrap_err();
+        assert!(err.to_string().contains("oops"));
+        assert!(resolve_exec(&ExecConfig::default()).is_err());
+    }
+
+    #[test]
+    fn test_timeout_policies() {
+        let mut slow = exec(&["sleep", "5"]);
+        slow.timeout = Duration::from_millis(100);
+
+        for policy in [TimeoutPolicy::Kill, TimeoutPolicy::Terminate, TimeoutPolicy::Detach] {
+            slow.on_timeout = policy;
+            let started = Instant::now();
+            let result = run_exec(&slow);
+            assert!(started.elapsed() < Duration::from_secs(3), "{:?} waited too long", policy);
+            assert_eq!(result.is_ok(), policy == TimeoutPolicy::Detach, "{:?}", policy);
+        }
+    }
+}
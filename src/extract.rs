@@ -0,0 +1,264 @@
+use crate::config::parse_result_field_preference;
+use serde_json::Value;
+
+/// One step through a JSON document
+#[derive(Debug, Clone, PartialEq)]
+enum Step {
+    /// Object key, or array index if the token is a number (JSON Pointer style)
+    Key(String),
+    /// Array index (JSONPath `[0]`)
+    Index(usize),
+    /// Every element of an array or value of an object
+    Wildcard,
+}
+
+/// A single field expression, e.g. `/output/choices/0/text` or `$.segments[*].text`
+#[derive(Debug, Clone)]
+pub struct FieldPath {
+    expr: String,
+    steps: Vec<Step>,
+}
+
+/// An ordered list of field expressions; the first one that yields text wins
+#[derive(Debug, Clone)]
+pub struct FieldChain {
+    paths: Vec<FieldPath>,
+}
+
+impl FieldChain {
+    /// Parse a list of entries from `result_field_preference`
+    ///
+    /// Entries can be the named fields (`llm`, `raw`, `intermediate`, `auto`), JSON
+    /// Pointers starting with `/`, or JSONPath expressions starting with `$`. Both
+    /// pointer and path syntax accept `*` to match every element.
+    pub fn parse<S: AsRef<str>>(entries: &[S]) -> Result<FieldChain, String> {
+        let mut paths = Vec::new();
+        for entry in entries {
+            paths.extend(parse_entry(entry.as_ref().trim())?);
+        }
+
+        if paths.is_empty() {
+            return Err("result_field_preference is empty".to_string());
+        }
+        Ok(FieldChain { paths })
+    }
+
+    /// Find the result text, returning it with the expression that matched
+    ///
+    /// When an expression matches several values they are trimmed and joined with spaces.
+    pub fn extract(&self, value: &Value) -> Option<(String, &str)> {
+        self.paths
+            .iter()
+            .find_map(|path| path.extract(value).map(|text| (text, path.expr.as_str())))
+    }
+
+    /// Human readable form for logging
+    pub fn describe(&self) -> String {
+        self.paths
+            .iter()
+            .map(|path| path.expr.as_str())
+            .collect::<Vec<_>>()
+            .join(" -> ")
+    }
+}
+
+impl Default for FieldChain {
+    fn default() -> Self {
+        FieldChain::parse(&["auto"]).expect("auto is a valid field chain")
+    }
+}
+
+impl FieldPath {
+    fn extract(&self, value: &Value) -> Option<String> {
+        let mut matches = Vec::new();
+        select(value, &self.steps, &mut matches);
+
+        let texts: Vec<String> = matches.into_iter().filter_map(scalar_text).collect();
+        match texts.as_slice() {
+            [] => None,
+            [text] if !text.trim().is_empty() => Some(text.clone()),
+            [_] => None,
+            _ => {
+                let joined = texts
+                    .iter()
+                    .map(|text| text.trim())
+                    .filter(|text| !text.is_empty())
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                (!joined.is_empty()).then_some(joined)
+            }
+        }
+    }
+}
+
+/// Expand one configured entry into field paths
+fn parse_entry(entry: &str) -> Result<Vec<FieldPath>, String> {
+    let pointer = |expr: &str| FieldPath {
+        expr: expr.to_string(),
+        steps: parse_pointer(expr),
+    };
+
+    if entry.is_empty() {
+        return Err("Empty entry in result_field_preference".to_string());
+    }
+    if entry.starts_with('/') {
+        return Ok(vec![pointer(entry)]);
+    }
+    if entry.starts_with('$') {
+        let steps = parse_json_path(entry)?;
+        return Ok(vec![FieldPath {
+            expr: entry.to_string(),
+            steps,
+        }]);
+    }
+
+    // Named fields, kept for compatibility with the original settings
+    Ok(match parse_result_field_preference(entry) {
+        "llm" => vec![pointer("/llmResult")],
+        "raw" => vec![pointer("/rawResult")],
+        "intermediate" => vec![pointer("/result")],
+        _ => vec![pointer("/llmResult"), pointer("/result"), pointer("/rawResult")],
+    })
+}
+
+/// Parse a JSON Pointer (RFC 6901), treating a `*` token as a wildcard
+fn parse_pointer(expr: &str) -> Vec<Step> {
+    expr.split('/')
+        .skip(1)
+        .map(|token| match token {
+            "*" => Step::Wildcard,
+            _ => Step::Key(token.replace("~1", "/").replace("~0", "~")),
+        })
+        .collect()
+}
+
+/// Parse the JSONPath subset used for field selection: `$.a.b`, `$.a[0]`, `$.a[*]`, `$['a']`
+fn parse_json_path(expr: &str) -> Result<Vec<Step>, String> {
+    let invalid = |reason: &str| format!("Invalid JSONPath '{}': {}", expr, reason);
+    let mut rest = expr
+        .strip_prefix('$')
+        .ok_or_else(|| invalid("must start with '$'"))?;
+    let mut steps = Vec::new();
+
+    while !rest.is_empty() {
+        if let Some(after_dot) = rest.strip_prefix('.') {
+            if after_dot.starts_with('.') {
+                return Err(invalid("recursive descent ('..') is not supported"));
+            }
+            let end = after_dot.find(['.', '[']).unwrap_or(after_dot.len());
+            let name = &after_dot[..end];
+            steps.push(match name {
+                "" => return Err(invalid("empty field name")),
+                "*" => Step::Wildcard,
+                _ => Step::Key(name.to_string()),
+            });
+            rest = &after_dot[end..];
+        } else if let Some(after_bracket) = rest.strip_prefix('[') {
+            let end = after_bracket.find(']').ok_or_else(|| invalid("missing ']'"))?;
+            let inner = after_bracket[..end].trim();
+            let quoted = inner
+                .strip_prefix('\'')
+                .and_then(|s| s.strip_suffix('\''))
+                .or_else(|| inner.strip_prefix('"').and_then(|s| s.strip_suffix('"')));
+
+            steps.push(if inner == "*" {
+                Step::Wildcard
+            } else if let Some(name) = quoted {
+                Step::Key(name.to_string())
+            } else {
+                Step::Index(
+                    inner
+                        .parse()
+                        .map_err(|_| invalid(&format!("unsupported selector '[{}]'", inner)))?,
+                )
+            });
+            rest = &after_bracket[end + 1..];
+        } else {
+            return Err(invalid(&format!("unexpected '{}'", rest)));
+        }
+    }
+
+    Ok(steps)
+}
+
+/// Collect every value the steps lead to
+fn select<'a>(value: &'a Value, steps: &[Step], out: &mut Vec<&'a Value>) {
+    let (step, rest) = match steps.split_first() {
+        Some(split) => split,
+        None => {
+            out.push(value);
+            return;
+        }
+    };
+
+    match (step, value) {
+        (Step::Wildcard, Value::Array(items)) => items.iter().for_each(|item| select(item, rest, out)),
+        (Step::Wildcard, Value::Object(map)) => map.values().for_each(|item| select(item, rest, out)),
+        (Step::Key(key), Value::Object(map)) => {
+            if let Some(item) = map.get(key) {
+                select(item, rest, out);
+            }
+        }
+        (Step::Key(key), Value::Array(items)) => {
+            if let Some(item) = key.parse::<usize>().ok().and_then(|i| items.get(i)) {
+                select(item, rest, out);
+            }
+        }
+        (Step::Index(index), Value::Array(items)) => {
+            if let Some(item) = items.get(*index) {
+                select(item, rest, out);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Text for a matched value; objects, arrays and nulls have none
+fn scalar_text(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        Value::Bool(b) => Some(b.to_string()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_pointer_and_path_extraction() {
+        let doc = json!({
+            "segments": [{"text": "Hello"}, {"text": " there"}, {"text": "friend. "}],
+            "output": {"choices": [{"text": "First"}, {"text": "Second"}]},
+            "a/b": "escaped"
+        });
+        let extract = |entry: &str| FieldChain::parse(&[entry]).unwrap().extract(&doc).map(|(text, _)| text);
+
+        assert_eq!(extract("/segments/*/text").as_deref(), Some("Hello there friend."));
+        assert_eq!(extract("/output/choices/1/text").as_deref(), Some("Second"));
+        assert_eq!(extract("/a~1b").as_deref(), Some("escaped"));
+        assert_eq!(extract("$.segments[*].text").as_deref(), Some("Hello there friend."));
+        assert_eq!(extract("$.output.choices[0].text").as_deref(), Some("First"));
+        assert_eq!(extract("$['output']['choices'][1]['text']").as_deref(), Some("Second"));
+        assert_eq!(extract("/missing"), None);
+    }
+
+    #[test]
+    fn test_chain_falls_through() {
+        let doc = json!({"llmResult": null, "result": "", "rawResult": "raw text"});
+        let chain = FieldChain::parse(&["/transcript", "auto"]).unwrap();
+        assert_eq!(chain.extract(&doc), Some(("raw text".to_string(), "/rawResult")));
+        assert_eq!(chain.describe(), "/transcript -> /llmResult -> /result -> /rawResult");
+    }
+
+    #[test]
+    fn test_invalid_json_path() {
+        assert!(FieldChain::parse(&["$..text"]).is_err());
+        assert!(FieldChain::parse(&["$.segments[last]"]).is_err());
+        assert!(FieldChain::parse(&["$.segments[0"]).is_err());
+        assert!(FieldChain::parse::<&str>(&[]).is_err());
+    }
+}
@@ -0,0 +1,304 @@
+use crate::config::{parse_filter_combine, FilterConfig};
+use crate::meta::Meta;
+use globset::{GlobBuilder, GlobMatcher};
+use regex::Regex;
+
+/// How the rules of a filter combine
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FilterCombine {
+    /// Every rule must match
+    All,
+    /// At least one rule must match
+    Any,
+}
+
+/// A single condition a recording has to meet
+#[derive(Debug, Clone)]
+enum Rule {
+    /// Mode name matches one of the globs
+    Mode(Vec<GlobMatcher>),
+    /// Language is one of the listed ones
+    Language(Vec<String>),
+    /// Recording length in seconds is within the range
+    Duration { min: Option<f64>, max: Option<f64> },
+    /// Result text length in characters is within the range
+    Length { min: Option<usize>, max: Option<usize> },
+    /// Result text matches the regex
+    TextRegex(Regex),
+    /// Another filter with its own combine mode
+    Nested(Box<Filter>),
+}
+
+/// Decides which recordings a watch processes
+#[derive(Debug, Clone)]
+pub struct Filter {
+    combine: FilterCombine,
+    rules: Vec<Rule>,
+}
+
+impl Default for Filter {
+    fn default() -> Self {
+        Filter {
+            combine: FilterCombine::All,
+            rules: Vec::new(),
+        }
+    }
+}
+
+impl Filter {
+    /// Build a filter from a `[filter]` table and the legacy `mode_name` setting
+    ///
+    /// `mode_name` is an extra rule that must always match, whatever `match` says. As
+    /// before `[filter]` existed, it is compared exactly rather than as a glob.
+    pub fn from_config(config: Option<&FilterConfig>, mode_name: Option<&str>) -> Result<Filter, String> {
+        let mode_rule = mode_name
+            .map(|mode| compile_globs(&[globset::escape(mode)], false))
+            .transpose()?;
+        Filter::with_mode_rule(config, mode_rule.map(Rule::Mode))
+    }
+
+    /// Build a route's filter from its `[route.filter]` table and `mode` shorthand
+    ///
+    /// The shorthand is a glob like `[filter].mode`, and must always match as well.
+    pub fn from_route_config(config: Option<&FilterConfig>, mode: Option<&str>) -> Result<Filter, String> {
+        let mode_rule = mode.map(|mode| compile_globs(&[mode.to_string()], true)).transpose()?;
+        Filter::with_mode_rule(config, mode_rule.map(Rule::Mode))
+    }
+
+    fn with_mode_rule(config: Option<&FilterConfig>, mode_rule: Option<Rule>) -> Result<Filter, String> {
+        let mut filter = Filter::default();
+
+        if let Some(config) = config {
+            filter.combine = config
+                .combine
+                .as_deref()
+                .map(parse_filter_combine)
+                .unwrap_or(FilterCombine::All);
+
+            if let Some(modes) = &config.mode {
+                filter.rules.push(Rule::Mode(compile_globs(&modes.entries(), true)?));
+            }
+            if let Some(languages) = &config.language {
+                filter.rules.push(Rule::Language(
+                    languages.entries().iter().map(|l| l.to_lowercase()).collect(),
+                ));
+            }
+            if config.min_duration_secs.is_some() || config.max_duration_secs.is_some() {
+                filter.rules.push(Rule::Duration {
+                    min: config.min_duration_secs,
+                    max: config.max_duration_secs,
+                });
+            }
+            if config.min_length.is_some() || config.max_length.is_some() {
+                filter.rules.push(Rule::Length {
+                    min: config.min_length,
+                    max: config.max_length,
+                });
+            }
+            if let Some(pattern) = &config.text_regex {
+                let regex = Regex::new(pattern).map_err(|e| format!("Invalid text_regex '{}': {}", pattern, e))?;
+                filter.rules.push(Rule::TextRegex(regex));
+            }
+        }
+
+        if let Some(mode_rule) = mode_rule {
+            if filter.combine == FilterCombine::All || filter.rules.is_empty() {
+                filter.rules.push(mode_rule);
+            } else {
+                // Keep `mode_name` mandatory when the table combines with "any"
+                filter = Filter {
+                    combine: FilterCombine::All,
+                    rules: vec![mode_rule, Rule::Nested(Box::new(filter))],
+                };
+            }
+        }
+
+        Ok(filter)
+    }
+
+    /// True if the filter lets everything through
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// True if any rule looks at the result text
+    pub fn uses_text(&self) -> bool {
+        self.rules.iter().any(Rule::uses_text)
+    }
+
+    /// Check a recording, returning the reason when it should be skipped
+    ///
+    /// Text rules fail when there is no text.
+    pub fn check(&self, meta: &Meta, text: Option<&str>) -> Result<(), String> {
+        let mut reasons = Vec::new();
+        for rule in &self.rules {
+            match rule.check(meta, text) {
+                Ok(()) if self.combine == FilterCombine::Any => return Ok(()),
+                Ok(()) => {}
+                Err(reason) if self.combine == FilterCombine::All => return Err(reason),
+                Err(reason) => reasons.push(reason),
+            }
+        }
+
+        if reasons.is_empty() {
+            Ok(())
+        } else {
+            Err(format!("no filter rule matched ({})", reasons.join("; ")))
+        }
+    }
+}
+
+impl Rule {
+    fn uses_text(&self) -> bool {
+        match self {
+            Rule::Length { .. } | Rule::TextRegex(_) => true,
+            Rule::Nested(filter) => filter.uses_text(),
+            _ => false,
+        }
+    }
+
+    fn check(&self, meta: &Meta, text: Option<&str>) -> Result<(), String> {
+        match self {
+            Rule::Mode(globs) => {
+                let mode = meta.mode_name.as_deref().ok_or("no mode name")?;
+                if globs.iter().any(|glob| glob.is_match(mode)) {
+                    Ok(())
+                } else {
+                    Err(format!("mode '{}' does not match {:?}", mode, glob_patterns(globs)))
+                }
+            }
+            Rule::Language(languages) => {
+                let language = meta.language.as_deref().ok_or("no language")?;
+                if languages.contains(&language.to_lowercase()) {
+                    Ok(())
+                } else {
+                    Err(format!("language '{}' is not one of {:?}", language, languages))
+                }
+            }
+            Rule::Duration { min, max } => {
+                let duration = meta.duration_secs().ok_or("no duration")?;
+                check_range("duration", duration, *min, *max, "s")
+            }
+            Rule::Length { min, max } => {
+                let length = text.ok_or("no text")?.chars().count();
+                check_range("text length", length, *min, *max, " characters")
+            }
+            Rule::TextRegex(regex) => {
+                if regex.is_match(text.ok_or("no text")?) {
+                    Ok(())
+                } else {
+                    Err(format!("text does not match /{}/", regex.as_str()))
+                }
+            }
+            Rule::Nested(filter) => filter.check(meta, text),
+        }
+    }
+}
+
+/// Check that a value is within an optional range
+fn check_range<T: PartialOrd + std::fmt::Display>(
+    name: &str,
+    value: T,
+    min: Option<T>,
+    max: Option<T>,
+    unit: &str,
+) -> Result<(), String> {
+    if let Some(min) = min.filter(|min| value < *min) {
+        return Err(format!("{} {}{} is below {}{}", name, value, unit, min, unit));
+    }
+    if let Some(max) = max.filter(|max| value > *max) {
+        return Err(format!("{} {}{} is above {}{}", name, value, unit, max, unit));
+    }
+    Ok(())
+}
+
+/// Compile mode name globs
+fn compile_globs(patterns: &[String], case_insensitive: bool) -> Result<Vec<GlobMatcher>, String> {
+    patterns
+        .iter()
+        .map(|pattern| {
+            GlobBuilder::new(pattern)
+                .case_insensitive(case_insensitive)
+                .build()
+                .map(|glob| glob.compile_matcher())
+                .map_err(|e| format!("Invalid mode pattern '{}': {}", pattern, e))
+        })
+        .collect()
+}
+
+fn glob_patterns(globs: &[GlobMatcher]) -> Vec<&str> {
+    globs.iter().map(|glob| glob.glob().glob()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::StringOrList;
+
+    fn meta(mode: &str, language: &str, duration_ms: f64) -> Meta {
+        Meta {
+            mode_name: Some(mode.to_string()),
+            language: Some(language.to_string()),
+            duration: Some(duration_ms),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_all_rules() {
+        let config = FilterConfig {
+            mode: Some(StringOrList::Many(vec!["Literal".to_string(), "Note*".to_string()])),
+            language: Some(StringOrList::One("en".to_string())),
+            min_duration_secs: Some(1.0),
+            max_length: Some(20),
+            ..Default::default()
+        };
+        let filter = Filter::from_config(Some(&config), None).unwrap();
+        assert!(filter.uses_text());
+
+        assert!(filter.check(&meta("notes", "EN", 4000.0), Some("Short text")).is_ok());
+        assert!(filter.check(&meta("Email", "en", 4000.0), Some("Short text")).unwrap_err().contains("mode 'Email'"));
+        assert!(filter.check(&meta("Literal", "de", 4000.0), Some("Short text")).is_err());
+        assert!(filter.check(&meta("Literal", "en", 500.0), Some("Short text")).unwrap_err().contains("below"));
+        assert!(filter.check(&meta("Literal", "en", 4000.0), Some("This text is far too long")).is_err());
+        assert!(filter.check(&meta("Literal", "en", 4000.0), None).is_err());
+    }
+
+    #[test]
+    fn test_any_rules_and_mode_name() {
+        let config = FilterConfig {
+            combine: Some("any".to_string()),
+            language: Some(StringOrList::One("de".to_string())),
+            text_regex: Some("(?i)^note".to_string()),
+            ..Default::default()
+        };
+        let filter = Filter::from_config(Some(&config), None).unwrap();
+        assert!(filter.check(&meta("Literal", "de", 1000.0), Some("Hello")).is_ok());
+        assert!(filter.check(&meta("Literal", "en", 1000.0), Some("Note to self")).is_ok());
+        assert!(filter
+            .check(&meta("Literal", "en", 1000.0), Some("Hello"))
+            .unwrap_err()
+            .starts_with("no filter rule matched"));
+
+        // mode_name still has to match when the table uses "any"
+        let filter = Filter::from_config(Some(&config), Some("Literal")).unwrap();
+        assert!(filter.check(&meta("Literal", "de", 1000.0), Some("Hello")).is_ok());
+        assert!(filter.check(&meta("Email", "de", 1000.0), Some("Hello")).is_err());
+
+        assert!(Filter::from_config(None, None).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_mode_name_matches_exactly() {
+        let filter = Filter::from_config(None, Some("Meeting [Draft]")).unwrap();
+        assert!(filter.check(&meta("Meeting [Draft]", "en", 1000.0), None).is_ok());
+        assert!(filter.check(&meta("Meeting D", "en", 1000.0), None).is_err());
+
+        let filter = Filter::from_config(None, Some("Note")).unwrap();
+        assert!(filter.check(&meta("note", "en", 1000.0), None).is_err());
+
+        // A route's mode shorthand is a glob, like [filter].mode
+        let filter = Filter::from_route_config(None, Some("Note*")).unwrap();
+        assert!(filter.check(&meta("notes", "en", 1000.0), None).is_ok());
+    }
+}
@@ -0,0 +1,180 @@
+use crate::casefold::{fold, Normalization};
+use strsim::{levenshtein, normalized_levenshtein};
+use unicode_normalization::char::canonical_combining_class;
+
+/// Keyphrase words this short must be heard exactly (or sound the same)
+const SHORT_WORD_LEN: usize = 3;
+
+/// Settings for fuzzy keyphrase matching
+#[derive(Debug, Clone, PartialEq)]
+pub struct FuzzyOptions {
+    /// Largest edit distance allowed between a heard word and a keyphrase word
+    pub max_distance: usize,
+    /// Also accept words with the same Soundex code
+    pub phonetic: bool,
+}
+
+impl Default for FuzzyOptions {
+    fn default() -> Self {
+        FuzzyOptions {
+            max_distance: 1,
+            phonetic: false,
+        }
+    }
+}
+
+/// Find every run of words in `text` close to `keyphrase`, without overlaps
+///
+/// Returns the byte range of each run of matched words and a confidence between 0 and 1,
+/// the average similarity of each word to the keyphrase word it stands for. Splits and
+/// folds the text once, so searching a long text stays linear.
+pub fn find_all_fuzzy(
+    text: &str,
+    keyphrase: &str,
+    options: &FuzzyOptions,
+    normalization: Normalization,
+) -> Vec<(usize, usize, f64)> {
+    let wanted: Vec<String> = words(keyphrase, normalization).into_iter().map(|(_, _, word)| word).collect();
+    let heard = words(text, normalization);
+    let mut found = Vec::new();
+    if wanted.is_empty() {
+        return found;
+    }
+
+    let mut i = 0;
+    while i + wanted.len() <= heard.len() {
+        match window_match(&heard[i..i + wanted.len()], &wanted, options) {
+            Some(m) => {
+                found.push(m);
+                i += wanted.len();
+            }
+            None => i += 1,
+        }
+    }
+    found
+}
+
+/// Byte range and confidence of a run of heard words, if each is close to its keyphrase word
+fn window_match(window: &[(usize, usize, String)], wanted: &[String], options: &FuzzyOptions) -> Option<(usize, usize, f64)> {
+    let mut total = 0.0;
+    for ((_, _, heard), target) in window.iter().zip(wanted) {
+        total += word_similarity(heard, target, options)?;
+    }
+    Some((window[0].0, window[window.len() - 1].1, total / wanted.len() as f64))
+}
+
+/// Similarity of a heard word to a keyphrase word, or `None` if it is too different
+fn word_similarity(heard: &str, target: &str, options: &FuzzyOptions) -> Option<f64> {
+    let allowed = if target.chars().count() > SHORT_WORD_LEN {
+        options.max_distance
+    } else {
+        0
+    };
+
+    let sounds_alike = || options.phonetic && soundex(heard).is_some() && soundex(heard) == soundex(target);
+    if levenshtein(heard, target) <= allowed || sounds_alike() {
+        Some(normalized_levenshtein(heard, target))
+    } else {
+        None
+    }
+}
+
+/// Case folded words in the text with their byte ranges
+fn words(text: &str, normalization: Normalization) -> Vec<(usize, usize, String)> {
+    let mut words = Vec::new();
+    let mut current: Option<usize> = None;
+
+    for (i, c) in text.char_indices().chain(std::iter::once((text.len(), ' '))) {
+        // Combining accents belong to the word they follow
+        let in_word = c.is_alphanumeric() || c == '\'' || (current.is_some() && canonical_combining_class(c) != 0);
+        match (current, in_word) {
+            (None, true) => current = Some(i),
+            (Some(start), false) => {
+                words.push((start, i, fold(&text[start..i], normalization)));
+                current = None;
+            }
+            _ => {}
+        }
+    }
+
+    words
+}
+
+/// American Soundex code of a word, e.g. "soulver" and "solver" are both S416
+pub fn soundex(word: &str) -> Option<String> {
+    let code = |c: char| match c {
+        'B' | 'F' | 'P' | 'V' => '1',
+        'C' | 'G' | 'J' | 'K' | 'Q' | 'S' | 'X' | 'Z' => '2',
+        'D' | 'T' => '3',
+        'L' => '4',
+        'M' | 'N' => '5',
+        'R' => '6',
+        _ => '0',
+    };
+
+    let mut letters = word.chars().filter(char::is_ascii_alphabetic).map(|c| c.to_ascii_uppercase());
+    let first = letters.next()?;
+    let mut result = first.to_string();
+    let mut last = code(first);
+
+    for c in letters {
+        let digit = code(c);
+        if digit != '0' && digit != last {
+            result.push(digit);
+            if result.len() == 4 {
+                break;
+            }
+        }
+        // H and W don't separate letters with the same code
+        if c != 'H' && c != 'W' {
+            last = digit;
+        }
+    }
+
+    while result.len() < 4 {
+        result.push('0');
+    }
+    Some(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_all_fuzzy() {
+        let options = FuzzyOptions::default();
+        let text = "Please open solver, then add it up.";
+        let find = |keyphrase: &str| find_all_fuzzy(text, keyphrase, &options, Normalization::Nfc);
+
+        let found = find("open soulver");
+        assert_eq!(found.len(), 1);
+        let (start, end, confidence) = found[0];
+        assert_eq!(&text[start..end], "open solver");
+        assert!(confidence > 0.9 && confidence < 1.0);
+
+        assert_eq!(find("then add").iter().map(|m| m.2).collect::<Vec<_>>(), vec![1.0]);
+        // Short words must match exactly
+        assert!(find("then odd").is_empty());
+        assert!(find("open calculator").is_empty());
+
+        let repeated = find_all_fuzzy("open solver, then open soulver", "open soulver", &options, Normalization::Nfc);
+        assert_eq!(repeated.iter().map(|m| m.0).collect::<Vec<_>>(), vec![0, 18]);
+    }
+
+    #[test]
+    fn test_phonetic() {
+        assert_eq!(soundex("soulver").as_deref(), Some("S416"));
+        assert_eq!(soundex("Robert"), soundex("Rupert"));
+        assert_eq!(soundex("Ashcraft").as_deref(), Some("A261"));
+        assert_eq!(soundex("42"), None);
+
+        let strict = FuzzyOptions::default();
+        let phonetic = FuzzyOptions {
+            phonetic: true,
+            ..Default::default()
+        };
+        assert!(find_all_fuzzy("call rupert now", "call robert", &strict, Normalization::Nfc).is_empty());
+        assert_eq!(find_all_fuzzy("call rupert now", "call robert", &phonetic, Normalization::Nfc).len(), 1);
+    }
+}
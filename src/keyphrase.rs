@@ -1,15 +1,142 @@
-use crate::actions::{execute_action, parse_action, ActionType};
-use crate::config::AppConfig;
+use crate::actions::{execute_action_output, parse_actions, ActionType, QueryCapture};
+use crate::automaton::{is_whole_word, KeyphraseAutomaton};
+use crate::casefold::{fold, FoldedText, Normalization};
+use crate::config::{
+    configured_matching_strategy, parse_keyphrase_matching_strategy, parse_keyphrase_position,
+    parse_punctuation_handling, AppConfig, KeyphraseDefinition, KeyphraseEntry, StringOrList,
+};
+use crate::fuzzy::{find_all_fuzzy, FuzzyOptions};
+use crate::exec::resolve_exec;
+use crate::macros::resolve_macro;
 use log::{debug, info, warn};
-use regex::Regex;
+use regex::{Regex, RegexBuilder};
+use std::collections::HashMap;
 
 /// Keyphrase with associated action
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct KeyphraseAction {
     pub keyphrase: String,
     pub action: ActionType,
+    /// Compiled pattern for regex keyphrases, which ignore the global matching strategy
+    pub pattern: Option<Regex>,
+    /// Wins over overlapping keyphrases with a lower priority
+    pub priority: i32,
+    /// Leave the phrase in the text instead of removing it
+    pub keep_phrase: bool,
+    /// Where in the dictation the keyphrase may appear
+    pub position: KeyphrasePosition,
+    /// Matching strategy for this keyphrase instead of the global one
+    pub strategy: Option<KeyphraseMatchingStrategy>,
+    /// Punctuation to remove after this keyphrase, instead of the default single mark
+    pub punctuation_handling: Option<PunctuationHandling>,
+}
+
+/// Where in the dictation a keyphrase may appear
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum KeyphrasePosition {
+    /// Anywhere in the text
+    #[default]
+    Anywhere,
+    /// Only as the first words of the dictation
+    Start,
+    /// Only as the last words of the dictation
+    End,
+    /// Only as a sentence of its own
+    Alone,
+}
+
+/// Characters that end a sentence
+const SENTENCE_ENDINGS: &[char] = &['.', '!', '?', '…'];
+
+impl KeyphrasePosition {
+    /// True if a match at `start..end` of the text is in this position
+    ///
+    /// Whitespace and punctuation around the match don't count, so in
+    /// "…done. Send that." the keyphrase "send that" is at the end, and alone.
+    pub fn allows(self, text: &str, start: usize, end: usize) -> bool {
+        let before = text[..start].trim_end();
+        let after = text[end..].trim_start();
+        let no_words = |s: &str| !s.chars().any(char::is_alphanumeric);
+
+        match self {
+            KeyphrasePosition::Anywhere => true,
+            KeyphrasePosition::Start => no_words(before),
+            KeyphrasePosition::End => no_words(after),
+            KeyphrasePosition::Alone => {
+                (before.is_empty() || before.ends_with(SENTENCE_ENDINGS))
+                    && (after.is_empty() || after.starts_with(SENTENCE_ENDINGS))
+            }
+        }
+    }
+}
+
+/// A list of keyphrases together with the automaton that finds them
+#[derive(Debug, Clone, Default)]
+pub struct KeyphraseSet {
+    actions: Vec<KeyphraseAction>,
+    automaton: Option<KeyphraseAutomaton>,
+}
+
+impl KeyphraseSet {
+    /// Compile the keyphrases for a matching strategy and normalization
+    pub fn new(
+        actions: Vec<KeyphraseAction>,
+        strategy: &KeyphraseMatchingStrategy,
+        normalization: Normalization,
+    ) -> KeyphraseSet {
+        let automaton = KeyphraseAutomaton::build(&actions, strategy, normalization);
+        KeyphraseSet { actions, automaton }
+    }
+
+    pub fn actions(&self) -> &[KeyphraseAction] {
+        &self.actions
+    }
+
+    pub fn len(&self) -> usize {
+        self.actions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.actions.is_empty()
+    }
+
+    /// Detect all keyphrases in a text along with their positions
+    ///
+    /// Literal keyphrases are found in one pass by the automaton; without one (fuzzy
+    /// matching, or options for another strategy) each keyphrase is searched for in turn,
+    /// as are keyphrases with a strategy of their own.
+    pub fn detect(&self, text: &str, options: &KeyphraseProcessingOptions) -> Vec<KeyphraseMatch> {
+        let automaton = match &self.automaton {
+            Some(automaton)
+                if *automaton.strategy() == options.matching_strategy
+                    && automaton.normalization() == options.normalization =>
+            {
+                automaton
+            }
+            _ => return detect_all_keyphrases(text, &self.actions, options),
+        };
+
+        let mut matches = automaton.find_all(text, &self.actions);
+        for ka in &self.actions {
+            if let Some(pattern) = &ka.pattern {
+                matches.extend(find_regex_matches(text, ka, pattern));
+            } else if !automaton.covers(ka) {
+                matches.extend(find_literal_matches(text, ka, options));
+            }
+        }
+        sort_and_log_matches(&mut matches);
+        matches
+    }
+
+    /// Detect keyphrases, act on them and return the text with them removed
+    pub fn process(&self, text: &str, dry_run: bool, options: &KeyphraseProcessingOptions) -> String {
+        act_on_matches(text, self.detect(text, options), dry_run, options)
+    }
 }
 
+/// Prefix that marks a keyphrase as a regex, whatever the global strategy
+pub const REGEX_KEYPHRASE_PREFIX: &str = "re:";
+
 /// Strategy for matching keyphrases in text
 #[derive(Debug, Clone, PartialEq)]
 pub enum KeyphraseMatchingStrategy {
@@ -19,6 +146,10 @@ pub enum KeyphraseMatchingStrategy {
     WholeWord,
     /// Exact case-sensitive match
     Exact,
+    /// Case-insensitive regex, with named groups available to the action as `{name}`
+    Regex,
+    /// Word by word, allowing small edit distances (and optionally words that sound alike)
+    Fuzzy,
 }
 
 /// How to handle punctuation after keyphrases
@@ -37,24 +168,73 @@ pub enum PunctuationHandling {
 pub struct KeyphraseProcessingOptions {
     pub matching_strategy: KeyphraseMatchingStrategy,
     pub punctuation_handling: PunctuationHandling,
+    pub fuzzy: FuzzyOptions,
+    /// Matches below this confidence are logged but not acted on
+    pub min_confidence: f64,
+    /// Unicode normalization used along with case folding
+    pub normalization: Normalization,
+    /// Word that makes the keyphrase after it plain text, e.g. "literal open browser"
+    pub escape_word: Option<String>,
+    /// Extra `{name}` variables for actions, such as the recording's `{mode}` and `{file}`
+    pub context: Vec<(String, String)>,
 }
 
+/// Default minimum confidence for acting on a fuzzy match
+pub const DEFAULT_MIN_CONFIDENCE: f64 = 0.8;
+
 impl Default for KeyphraseProcessingOptions {
     fn default() -> Self {
         Self {
             matching_strategy: KeyphraseMatchingStrategy::Simple,
             punctuation_handling: PunctuationHandling::RemoveSentenceEnding,
+            fuzzy: FuzzyOptions::default(),
+            min_confidence: DEFAULT_MIN_CONFIDENCE,
+            normalization: Normalization::Nfc,
+            escape_word: None,
+            context: Vec::new(),
         }
     }
 }
 
 /// A matched keyphrase with position information
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct KeyphraseMatch {
     pub keyphrase: String,
     pub action: ActionType,
     pub start_pos: usize,
     pub end_pos: usize,
+    /// How sure the match is, from 0 to 1 (only fuzzy matches are below 1)
+    pub confidence: f64,
+    pub priority: i32,
+    pub keep_phrase: bool,
+    pub punctuation_handling: Option<PunctuationHandling>,
+    /// Named regex captures, filled into the action together with the other variables
+    pub captures: Vec<(String, String)>,
+}
+
+impl KeyphraseMatch {
+    /// A match of a keyphrase at a position, with the keyphrase's options
+    pub fn new(ka: &KeyphraseAction, action: ActionType, start_pos: usize, end_pos: usize, confidence: f64) -> Self {
+        KeyphraseMatch {
+            keyphrase: ka.keyphrase.clone(),
+            action,
+            start_pos,
+            end_pos,
+            confidence,
+            priority: ka.priority,
+            keep_phrase: ka.keep_phrase,
+            punctuation_handling: ka.punctuation_handling.clone(),
+            captures: Vec::new(),
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.end_pos - self.start_pos
+    }
+
+    fn overlaps(&self, other: &KeyphraseMatch) -> bool {
+        self.start_pos < other.end_pos && other.start_pos < self.end_pos
+    }
 }
 
 /// A segment of text between keyphrases
@@ -66,22 +246,112 @@ pub struct TextSegment {
 }
 
 /// Extract keyphrase actions from configuration
-pub fn parse_keyphrases(config: &AppConfig) -> Vec<KeyphraseAction> {
+pub fn parse_keyphrases(config: &AppConfig) -> Result<Vec<KeyphraseAction>, String> {
+    if let Some(true) = config.detect_keyphrases {
+        return parse_configured_keyphrases(config, &configured_matching_strategy(config));
+    }
+
+    Ok(Vec::new())
+}
+
+/// Build the global keyphrase actions: `[[keyphrase]]` entries in order, then `[keyphrases]`
+pub fn parse_configured_keyphrases(
+    config: &AppConfig,
+    strategy: &KeyphraseMatchingStrategy,
+) -> Result<Vec<KeyphraseAction>, String> {
+    let mut keyphrases = parse_keyphrase_definitions(config.keyphrase.as_deref().unwrap_or_default(), strategy)?;
+    if let Some(kp_map) = &config.keyphrases {
+        keyphrases.extend(parse_keyphrase_map(kp_map, strategy)?);
+    }
+    Ok(keyphrases)
+}
+
+/// Build keyphrase actions from a map of keyphrase to action string
+///
+/// The map is read in order of its keyphrases, so the result doesn't depend on hashing.
+pub fn parse_keyphrase_map(
+    kp_map: &HashMap<String, KeyphraseEntry>,
+    strategy: &KeyphraseMatchingStrategy,
+) -> Result<Vec<KeyphraseAction>, String> {
+    let mut definitions: Vec<KeyphraseDefinition> =
+        kp_map.iter().map(|(phrase, entry)| entry.definition(phrase)).collect();
+    definitions.sort_by(|a, b| a.phrase.cmp(&b.phrase));
+    parse_keyphrase_definitions(&definitions, strategy)
+}
+
+/// Build keyphrase actions from `[[keyphrase]]` entries, one for each phrase and alias
+///
+/// Disabled entries are skipped. Regex keyphrases (a `re:` prefix, or every keyphrase
+/// with the regex strategy) are compiled here so a bad pattern is reported when the
+/// config loads.
+pub fn parse_keyphrase_definitions(
+    definitions: &[KeyphraseDefinition],
+    strategy: &KeyphraseMatchingStrategy,
+) -> Result<Vec<KeyphraseAction>, String> {
     let mut keyphrases = Vec::new();
 
-    if let Some(true) = config.detect_keyphrases {
-        if let Some(kp_map) = &config.keyphrases {
-            for (phrase, action_str) in kp_map {
-                let action = parse_action(action_str);
-                keyphrases.push(KeyphraseAction {
-                    keyphrase: phrase.clone(),
-                    action,
-                });
+    for definition in definitions {
+        if !definition.enabled.unwrap_or(true) {
+            debug!("Keyphrase '{}' is disabled", definition.phrase);
+            continue;
+        }
+        if let Some(description) = &definition.description {
+            debug!("Keyphrase '{}': {}", definition.phrase, description);
+        }
+
+        let entry_strategy = definition
+            .matching_strategy
+            .as_deref()
+            .map(parse_keyphrase_matching_strategy);
+        let action = match (&definition.action, &definition.steps, &definition.exec) {
+            (None, Some(steps), None) => ActionType::Macro(
+                resolve_macro(steps).map_err(|e| format!("Keyphrase '{}': {}", definition.phrase, e))?,
+            ),
+            (None, None, Some(exec)) => ActionType::Exec(
+                resolve_exec(exec).map_err(|e| format!("Keyphrase '{}': {}", definition.phrase, e))?,
+            ),
+            (action, None, None) => parse_actions(&action.as_ref().map(StringOrList::entries).unwrap_or_default()),
+            _ => {
+                return Err(format!(
+                    "Keyphrase '{}' sets more than one of action, steps and exec, use only one",
+                    definition.phrase
+                ));
             }
+        };
+
+        for phrase in std::iter::once(&definition.phrase).chain(&definition.aliases) {
+            let (keyphrase, is_regex) = match phrase.strip_prefix(REGEX_KEYPHRASE_PREFIX) {
+                Some(pattern) => (pattern.to_string(), true),
+                None => (
+                    phrase.clone(),
+                    *entry_strategy.as_ref().unwrap_or(strategy) == KeyphraseMatchingStrategy::Regex,
+                ),
+            };
+
+            let pattern = if is_regex {
+                let regex = RegexBuilder::new(&keyphrase)
+                    .case_insensitive(true)
+                    .build()
+                    .map_err(|e| format!("Keyphrase '{}' is not a valid regex: {}", phrase, e))?;
+                Some(regex)
+            } else {
+                None
+            };
+
+            keyphrases.push(KeyphraseAction {
+                keyphrase,
+                action: action.clone(),
+                pattern,
+                priority: definition.priority,
+                keep_phrase: !definition.strip.unwrap_or(true),
+                position: definition.position.as_deref().map(parse_keyphrase_position).unwrap_or_default(),
+                strategy: entry_strategy.clone(),
+                punctuation_handling: definition.punctuation_handling.as_deref().map(parse_punctuation_handling),
+            });
         }
     }
 
-    keyphrases
+    Ok(keyphrases)
 }
 
 /// Process text to detect and act on keyphrases
@@ -105,37 +375,149 @@ pub fn process_keyphrases_enhanced(
 ) -> String {
     // Detect all keyphrases in the text
     let matches = detect_all_keyphrases(text, keyphrases, options);
+    act_on_matches(text, matches, dry_run, options)
+}
+
+/// Act on detected keyphrases and return the text with them removed
+fn act_on_matches(
+    text: &str,
+    matches: Vec<KeyphraseMatch>,
+    dry_run: bool,
+    options: &KeyphraseProcessingOptions,
+) -> String {
+    // Leave uncertain matches alone
+    let (matches, uncertain): (Vec<_>, Vec<_>) = matches
+        .into_iter()
+        .partition(|m| m.confidence >= options.min_confidence);
+    for m in &uncertain {
+        info!(
+            "Not acting on \"{}\" for keyphrase \"{}\": confidence {:.2} is below {:.2}",
+            &text[m.start_pos..m.end_pos],
+            m.keyphrase,
+            m.confidence,
+            options.min_confidence
+        );
+    }
+
+    let matches = resolve_overlaps(matches);
+    let (text, matches) = match &options.escape_word {
+        Some(escape_word) => apply_escapes(text, matches, escape_word, options.normalization),
+        None => (text.to_string(), matches),
+    };
 
     // If no keyphrases found, return the original text
     if matches.is_empty() {
-        return text.to_string();
+        return text;
     }
 
     // Process the chained actions
-    process_chained_actions(text, &matches, dry_run)
+    process_chained_actions_with_context(&text, &matches, dry_run, &options.context)
 }
 
-/// Find a keyphrase in text based on matching strategy
-fn find_keyphrase(
+/// Drop keyphrases said right after the escape word and remove the escape word itself
+///
+/// "literal open browser" leaves "open browser" in the text without running its
+/// action. `matches` must be in text order without overlaps; the positions of the
+/// ones returned are moved to match the returned text.
+fn apply_escapes(
     text: &str,
-    keyphrase: &str,
-    options: &KeyphraseProcessingOptions,
-) -> Option<usize> {
-    match options.matching_strategy {
-        KeyphraseMatchingStrategy::Simple => text.to_lowercase().find(&keyphrase.to_lowercase()),
-        KeyphraseMatchingStrategy::WholeWord => {
-            // Pattern that matches the phrase as whole words
-            if let Ok(pattern) = Regex::new(&format!("(?i)\\b{}\\b", regex::escape(keyphrase))) {
-                pattern.find(text).map(|m| m.start())
-            } else {
-                None
+    matches: Vec<KeyphraseMatch>,
+    escape_word: &str,
+    normalization: Normalization,
+) -> (String, Vec<KeyphraseMatch>) {
+    let mut output = String::with_capacity(text.len());
+    let mut kept = Vec::with_capacity(matches.len());
+    let mut copied = 0; // End of the text already copied to the output
+    let mut previous_end = 0;
+
+    for mut m in matches {
+        let (start, end) = (m.start_pos, m.end_pos);
+        match escape_start(&text[previous_end..start], escape_word, normalization) {
+            Some(offset) => {
+                info!("Keyphrase \"{}\" is escaped, leaving it in the text", m.keyphrase);
+                output.push_str(&text[copied..previous_end + offset]);
+                copied = start;
+            }
+            None => {
+                // Shift by what has been removed so far
+                let removed = copied - output.len();
+                m.start_pos -= removed;
+                m.end_pos -= removed;
+                kept.push(m);
             }
         }
+        previous_end = end;
+    }
+    output.push_str(&text[copied..]);
+
+    (output, kept)
+}
+
+/// Where the escape word starts, if `before` ends with it
+///
+/// Whitespace and a comma or colon may follow the escape word ("quote, open browser").
+fn escape_start(before: &str, escape_word: &str, normalization: Normalization) -> Option<usize> {
+    let trimmed = before.trim_end_matches(|c: char| c.is_whitespace() || c == ',' || c == ':');
+    let chars = escape_word.chars().count();
+    let start = trimmed.char_indices().rev().nth(chars.checked_sub(1)?)?.0;
+
+    let whole_word = !trimmed[..start].chars().next_back().is_some_and(char::is_alphanumeric);
+    (whole_word && fold(&trimmed[start..], normalization) == fold(escape_word, normalization)).then_some(start)
+}
+
+/// Find every match of a keyphrase in text based on matching strategy
+///
+/// Returns the start and end of each match and its confidence, without overlaps.
+fn find_keyphrase_all(
+    text: &str,
+    keyphrase: &str,
+    strategy: &KeyphraseMatchingStrategy,
+    options: &KeyphraseProcessingOptions,
+) -> Vec<(usize, usize, f64)> {
+    match strategy {
+        KeyphraseMatchingStrategy::Simple => find_folded(text, keyphrase, options.normalization, false),
+        KeyphraseMatchingStrategy::WholeWord => find_folded(text, keyphrase, options.normalization, true),
+        KeyphraseMatchingStrategy::Exact if keyphrase.is_empty() => Vec::new(),
         KeyphraseMatchingStrategy::Exact => {
             // Exact case-sensitive match
-            text.find(keyphrase)
+            text.match_indices(keyphrase)
+                .map(|(pos, _)| (pos, pos + keyphrase.len(), 1.0))
+                .collect()
+        }
+        // Regex keyphrases carry their compiled pattern and are matched in detect_all_keyphrases
+        KeyphraseMatchingStrategy::Regex => find_folded(text, keyphrase, options.normalization, false),
+        KeyphraseMatchingStrategy::Fuzzy => find_all_fuzzy(text, keyphrase, &options.fuzzy, options.normalization),
+    }
+}
+
+/// Case-insensitive search that compares case folded, normalized text
+///
+/// The text is folded once and searched forward from the end of each match. A match
+/// that isn't a whole word is skipped one character at a time, so an overlapping one
+/// that is ("a a" in "aa a a") is still found. Matches are returned as ranges of the
+/// original text, which may differ in length from the keyphrase ("STRAẞE" matches
+/// "strasse").
+fn find_folded(
+    text: &str,
+    keyphrase: &str,
+    normalization: Normalization,
+    whole_word: bool,
+) -> Vec<(usize, usize, f64)> {
+    let folded = FoldedText::new(text, normalization);
+    let needle = fold(keyphrase, normalization);
+    let mut matches = Vec::new();
+
+    let mut from = 0;
+    while let Some((start, end, next)) = folded.find(&needle, from) {
+        if !whole_word || is_whole_word(text, start, end) {
+            matches.push((start, end, 1.0));
+            from = next;
+        } else {
+            let folded_start = next - needle.len();
+            from = folded_start + folded.as_str()[folded_start..].chars().next().map_or(1, char::len_utf8);
         }
     }
+    matches
 }
 
 /// Detect all keyphrases in a text along with their positions
@@ -147,21 +529,61 @@ pub fn detect_all_keyphrases(
     let mut matches = Vec::new();
 
     for ka in keyphrases {
-        // Find all instances of this keyphrase in the text
-        let mut start = 0;
-        while let Some(pos) = find_keyphrase(&text[start..], &ka.keyphrase, options) {
-            let absolute_pos = start + pos;
-            matches.push(KeyphraseMatch {
-                keyphrase: ka.keyphrase.clone(),
-                action: ka.action.clone(),
-                start_pos: absolute_pos,
-                end_pos: absolute_pos + ka.keyphrase.len(),
-            });
-            start = absolute_pos + ka.keyphrase.len(); // Move past this match
+        match &ka.pattern {
+            Some(pattern) => matches.extend(find_regex_matches(text, ka, pattern)),
+            None => matches.extend(find_literal_matches(text, ka, options)),
         }
     }
 
-    // Sort matches by position to ensure correct order of execution
+    sort_and_log_matches(&mut matches);
+    matches
+}
+
+/// Find all instances of a literal keyphrase, using its own strategy if it has one
+fn find_literal_matches(text: &str, ka: &KeyphraseAction, options: &KeyphraseProcessingOptions) -> Vec<KeyphraseMatch> {
+    let strategy = ka.strategy.as_ref().unwrap_or(&options.matching_strategy);
+    find_keyphrase_all(text, &ka.keyphrase, strategy, options)
+        .into_iter()
+        .filter(|&(start, end, _)| in_position(text, ka, start, end))
+        .map(|(start, end, confidence)| KeyphraseMatch::new(ka, ka.action.clone(), start, end, confidence))
+        .collect()
+}
+
+/// Drop matches that overlap a better one, so each piece of text triggers one keyphrase
+///
+/// A higher priority wins, then the longer match, then the earlier one. This is the
+/// only overlap rule between keyphrases. The automaton reports every occurrence, even
+/// ones overlapping the same keyphrase; the keyphrase by keyphrase search only finds
+/// the occurrences of each keyphrase that don't overlap an earlier accepted one, which
+/// this rule would drop anyway. Both drop whole-word rejects before they get here.
+/// The kept matches are returned in text order.
+pub fn resolve_overlaps(mut matches: Vec<KeyphraseMatch>) -> Vec<KeyphraseMatch> {
+    matches.sort_by(|a, b| {
+        b.priority
+            .cmp(&a.priority)
+            .then(b.len().cmp(&a.len()))
+            .then(a.start_pos.cmp(&b.start_pos))
+            .then(b.confidence.total_cmp(&a.confidence))
+            .then_with(|| a.keyphrase.cmp(&b.keyphrase))
+    });
+
+    let mut kept: Vec<KeyphraseMatch> = Vec::with_capacity(matches.len());
+    for m in matches {
+        match kept.iter().find(|k| k.overlaps(&m)) {
+            Some(winner) => debug!(
+                "Ignoring \"{}\" at position {}, it overlaps \"{}\"",
+                m.keyphrase, m.start_pos, winner.keyphrase
+            ),
+            None => kept.push(m),
+        }
+    }
+
+    kept.sort_by_key(|m| m.start_pos);
+    kept
+}
+
+/// Sort matches by position to ensure correct order of execution, and log them
+fn sort_and_log_matches(matches: &mut [KeyphraseMatch]) {
     matches.sort_by_key(|m| m.start_pos);
 
     // Log the detected keyphrases in order
@@ -169,15 +591,49 @@ pub fn detect_all_keyphrases(
         debug!("Detected {} keyphrases in order:", matches.len());
         for (i, m) in matches.iter().enumerate() {
             debug!(
-                "  {}. \"{}\" at position {}",
+                "  {}. \"{}\" at position {} (confidence {:.2})",
                 i + 1,
                 m.keyphrase,
-                m.start_pos
+                m.start_pos,
+                m.confidence
             );
         }
     }
+}
 
-    matches
+/// Find every match of a regex keyphrase, keeping its named captures for the action
+fn find_regex_matches(text: &str, ka: &KeyphraseAction, pattern: &Regex) -> Vec<KeyphraseMatch> {
+    pattern
+        .captures_iter(text)
+        .filter_map(|caps| {
+            let whole = caps.get(0).filter(|m| !m.is_empty())?;
+            if !in_position(text, ka, whole.start(), whole.end()) {
+                return None;
+            }
+            let named: Vec<(String, String)> = pattern
+                .capture_names()
+                .flatten()
+                .filter_map(|name| caps.name(name).map(|m| (name.to_string(), m.as_str().to_string())))
+                .collect();
+
+            Some(KeyphraseMatch {
+                captures: named,
+                ..KeyphraseMatch::new(ka, ka.action.clone(), whole.start(), whole.end(), 1.0)
+            })
+        })
+        .collect()
+}
+
+/// True if the keyphrase's position allows a match at `start..end`, logging it if not
+pub(crate) fn in_position(text: &str, ka: &KeyphraseAction, start: usize, end: usize) -> bool {
+    let allowed = ka.position.allows(text, start, end);
+    if !allowed {
+        debug!(
+            "Ignoring \"{}\" at position {}, it only counts {:?}",
+            ka.keyphrase, start, ka.position
+        );
+    }
+    allowed
 }
 
 /// Split text into segments between keyphrases
@@ -216,8 +672,41 @@ pub fn segment_text(text: &str, keyphrase_matches: &[KeyphraseMatch]) -> Vec<Tex
 }
 
 /// Process and execute chained actions in the order they appear in text
+///
+/// Text captured by a `{query}` placeholder is passed to the action and removed from the
+/// result, unless the keyphrase keeps its phrase. Actions can also use `{text}`, the
+/// returned text, and `{segment_after}`, the words between the keyphrase and the next
+/// one. Matches must not overlap (see `resolve_overlaps`).
 pub fn process_chained_actions(text: &str, matches: &[KeyphraseMatch], dry_run: bool) -> String {
-    // Split text into segments
+    process_chained_actions_with_context(text, matches, dry_run, &[])
+}
+
+/// Process and execute chained actions, with extra `{name}` variables such as `{mode}` and `{file}`
+///
+/// A command that replaces the text (`replace_text`) has its output returned instead.
+pub fn process_chained_actions_with_context(
+    text: &str,
+    matches: &[KeyphraseMatch],
+    dry_run: bool,
+    context: &[(String, String)],
+) -> String {
+    // Capture the text for `{query}` placeholders, noting where each keyphrase's captured text ends
+    let resolved: Vec<(Option<String>, usize)> = matches
+        .iter()
+        .enumerate()
+        .map(|(i, km)| {
+            let Some(capture) = km.action.query_capture() else {
+                return (None, km.end_pos);
+            };
+            let limit = matches.get(i + 1).map_or(text.len(), |next| next.start_pos).max(km.end_pos);
+            let (query, end) = capture_query(text, km.end_pos, limit, capture);
+            debug!("Keyphrase \"{}\" captured \"{}\"", km.keyphrase, query);
+            (Some(query), end)
+        })
+        .collect();
+
+    // The text without keyphrases, which actions can use as {text}
+    let cleaned = remove_keyphrases(text, matches, &resolved);
 
     // Log the execution sequence
     if !matches.is_empty() {
@@ -228,12 +717,29 @@ pub fn process_chained_actions(text: &str, matches: &[KeyphraseMatch], dry_run:
     }
 
     // Execute actions in sequence
-    for (i, km) in matches.iter().enumerate() {
+    let mut replacement = None;
+    for (i, (km, (query, _))) in matches.iter().zip(&resolved).enumerate() {
+        let next_start = matches.get(i + 1).map_or(text.len(), |next| next.start_pos).max(km.end_pos);
+        let segment_after = text[km.end_pos..next_start]
+            .trim_start_matches(|c: char| c.is_whitespace() || c.is_ascii_punctuation())
+            .trim_end();
+
+        // Every placeholder is filled in one pass, so no value is expanded again
+        let mut variables = context.to_vec();
+        variables.push(("text".to_string(), cleaned.clone()));
+        variables.push(("segment_after".to_string(), segment_after.to_string()));
+        variables.extend(km.captures.iter().cloned());
+        if let Some(query) = query {
+            variables.push(("query".to_string(), query.clone()));
+        }
+        let action = km.action.with_captures(&variables);
+
         if dry_run {
             info!(
-                "DRY-RUN: Would execute action #{} for keyphrase: \"{}\"",
+                "DRY-RUN: Would execute action #{} for keyphrase: \"{}\" ({:?})",
                 i + 1,
-                km.keyphrase
+                km.keyphrase,
+                action
             );
         } else {
             info!(
@@ -243,12 +749,16 @@ pub fn process_chained_actions(text: &str, matches: &[KeyphraseMatch], dry_run:
             );
 
             // Execute the action
-            match execute_action(&km.action) {
-                Ok(_) => {
+            match execute_action_output(&action) {
+                Ok(output) => {
                     info!(
                         "Successfully executed action for keyphrase: \"{}\"",
                         km.keyphrase
                     );
+                    if output.is_some() {
+                        info!("Copying the output of \"{}\" instead of the dictated text", km.keyphrase);
+                        replacement = output;
+                    }
                 }
                 Err(e) => {
                     warn!(
@@ -260,7 +770,11 @@ pub fn process_chained_actions(text: &str, matches: &[KeyphraseMatch], dry_run:
         }
     }
 
-    // Construct the cleaned text (without keyphrases)
+    replacement.unwrap_or(cleaned)
+}
+
+/// Rebuild the text without its keyphrases and the text they captured
+fn remove_keyphrases(text: &str, matches: &[KeyphraseMatch], resolved: &[(Option<String>, usize)]) -> String {
     let mut result = String::new();
 
     // List of common punctuation characters to check for
@@ -268,7 +782,12 @@ pub fn process_chained_actions(text: &str, matches: &[KeyphraseMatch], dry_run:
 
     // We need to reconstruct the original text without the keyphrases
     let mut last_end = 0;
-    for km in matches {
+    for (km, (_, consumed_end)) in matches.iter().zip(resolved) {
+        // Kept phrases stay in place, along with any text they captured
+        if km.keep_phrase {
+            continue;
+        }
+
         // Add text from last end to current start, handling punctuation
         if km.start_pos > last_end {
             let mut pre_text = text[last_end..km.start_pos].to_string();
@@ -284,17 +803,20 @@ pub fn process_chained_actions(text: &str, matches: &[KeyphraseMatch], dry_run:
             result.push_str(&pre_text);
         }
 
-        // Skip the keyphrase
-        last_end = km.end_pos;
-
-        // Skip any punctuation immediately after the keyphrase
-        if last_end < text.len() {
-            if let Some(next_char) = text[last_end..].chars().next() {
-                if punctuation_chars.contains(&next_char) {
-                    last_end += next_char.len_utf8();
-                }
+        // Skip the keyphrase and any text it captured
+        last_end = (*consumed_end).max(last_end);
+
+        // Skip punctuation immediately after the keyphrase, one mark unless the keyphrase says otherwise
+        let after = &text[last_end..];
+        let single = |is_skipped: &dyn Fn(char) -> bool| after.chars().next().filter(|&c| is_skipped(c)).map_or(0, char::len_utf8);
+        last_end += match &km.punctuation_handling {
+            None => single(&|c| punctuation_chars.contains(&c)),
+            Some(PunctuationHandling::IgnorePunctuation) => 0,
+            Some(PunctuationHandling::RemoveSentenceEnding) => single(&|c| matches!(c, '.' | '!' | '?')),
+            Some(PunctuationHandling::RemoveAllPunctuation) => {
+                after.len() - after.trim_start_matches(|c| punctuation_chars.contains(&c)).len()
             }
-        }
+        };
     }
 
     // Add any remaining text
@@ -336,6 +858,42 @@ pub fn process_chained_actions(text: &str, matches: &[KeyphraseMatch], dry_run:
     normalized.trim().to_string()
 }
 
+/// Capture the parameter for a keyphrase from the text between `start` and `limit`
+///
+/// Returns the trimmed parameter and the position where the captured text ends.
+fn capture_query(text: &str, start: usize, limit: usize, capture: QueryCapture) -> (String, usize) {
+    let region = &text[start..limit];
+    let end = match capture {
+        QueryCapture::Sentence => region.find(['.', '!', '?', '\n']).unwrap_or(region.len()),
+        QueryCapture::UntilNextKeyphrase => region.len(),
+        QueryCapture::Words(count) => {
+            let mut words = 0;
+            let mut in_word = false;
+            let mut end = region.len();
+            for (i, c) in region.char_indices() {
+                if c.is_whitespace() {
+                    if in_word {
+                        words += 1;
+                        in_word = false;
+                        if words == count {
+                            end = i;
+                            break;
+                        }
+                    }
+                } else {
+                    in_word = true;
+                }
+            }
+            end
+        }
+    };
+
+    let query = region[..end]
+        .trim_matches(|c: char| c.is_whitespace() || matches!(c, ',' | ';' | ':' | '.' | '!' | '?'))
+        .to_string();
+    (query, start + end)
+}
+
 /// Get list of keyphrases only (for display/logging purposes)
 pub fn get_keyphrase_list(keyphrases: &[KeyphraseAction]) -> Vec<String> {
     keyphrases.iter().map(|ka| ka.keyphrase.clone()).collect()
@@ -344,35 +902,23 @@ pub fn get_keyphrase_list(keyphrases: &[KeyphraseAction]) -> Vec<String> {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::collections::HashMap;
+    use crate::actions::parse_action;
 
     #[test]
     fn test_parse_keyphrases() {
         let mut keyphrases_map = HashMap::new();
         keyphrases_map.insert(
             String::from("open browser"),
-            String::from("https://example.com"),
+            "https://example.com".into(),
         );
 
         let config = AppConfig {
             detect_keyphrases: Some(true),
             keyphrases: Some(keyphrases_map),
-            watch_dir: None,
-            log_file: None,
-            log_level: None,
-            echo_to_stdout: None,
-            dry_run: None,
-            clipboard_format: None,
-            text_cleaning: None,
-            disable_logs: None,
-            keyphrase_settings: None,
-            // Add missing fields
-            disable_clipboard: None,
-            mode_name: None,
-            result_field_preference: None,
+            ..Default::default()
         };
 
-        let keyphrases = parse_keyphrases(&config);
+        let keyphrases = parse_keyphrases(&config).unwrap();
         assert_eq!(keyphrases.len(), 1);
 
         // Verify the keyphrases were parsed correctly
@@ -385,6 +931,7 @@ mod tests {
         let keyphrases = vec![KeyphraseAction {
             keyphrase: String::from("open notes"),
             action: ActionType::OpenApplication(String::from("Notes")),
+            ..Default::default()
         }];
 
         let text = "I need to open notes for this meeting.";
@@ -404,6 +951,8 @@ mod tests {
             action: ActionType::OpenApplication(String::from("Notes")),
             start_pos: 10,
             end_pos: 20,
+            confidence: 1.0,
+            ..Default::default()
         }];
 
         let text = "I need to open notes for this meeting.";
@@ -422,6 +971,8 @@ mod tests {
             action: ActionType::None, // Use None for testing
             start_pos: 10,
             end_pos: 20,
+            confidence: 1.0,
+            ..Default::default()
         }];
 
         let text = "I need to open notes for this meeting.";
@@ -432,16 +983,337 @@ mod tests {
         assert_eq!(result, "I need to for this meeting.");
     }
 
+    #[test]
+    fn test_query_capture() {
+        let keyphrases = vec![
+            KeyphraseAction {
+                keyphrase: String::from("search google"),
+                action: parse_action("https://www.google.com/search?q={query}"),
+                ..Default::default()
+            },
+            KeyphraseAction {
+                keyphrase: String::from("look up"),
+                action: parse_action("https://example.com/{query:words:2}"),
+                ..Default::default()
+            },
+        ];
+        let options = KeyphraseProcessingOptions::default();
+
+        let text = "Search google best pizza in town. Then look up rust traits today.";
+        let matches = detect_all_keyphrases(text, &keyphrases, &options);
+        assert_eq!(capture_query(text, matches[0].end_pos, matches[1].start_pos, QueryCapture::Sentence).0, "best pizza in town");
+        assert_eq!(capture_query(text, matches[1].end_pos, text.len(), QueryCapture::Words(2)).0, "rust traits");
+        assert_eq!(
+            capture_query(text, matches[0].end_pos, matches[1].start_pos, QueryCapture::UntilNextKeyphrase).0,
+            "best pizza in town. Then"
+        );
+
+        // Captured text is removed along with the keyphrase
+        let result = process_keyphrases_enhanced(text, &keyphrases, true, &options);
+        assert_eq!(result, "Then today.");
+    }
+
+    #[test]
+    fn test_overlapping_keyphrases() {
+        let kp_map = HashMap::from([
+            ("open notes".to_string(), KeyphraseEntry::from("")),
+            ("notes".to_string(), KeyphraseEntry::from("")),
+            ("notes app".to_string(), KeyphraseEntry::from("")),
+        ]);
+        let keyphrases = parse_keyphrase_map(&kp_map, &KeyphraseMatchingStrategy::Simple).unwrap();
+        let options = KeyphraseProcessingOptions::default();
+        let text = "Please open notes app now";
+
+        // The longest match wins, whichever order the keyphrases come in
+        let kept = resolve_overlaps(detect_all_keyphrases(text, &keyphrases, &options));
+        assert_eq!(kept.len(), 1);
+        assert_eq!(kept[0].keyphrase, "open notes");
+        assert_eq!(process_keyphrases_enhanced(text, &keyphrases, true, &options), "Please app now");
+
+        // An explicit priority beats length, and strip = false leaves the text alone
+        let kp_map = HashMap::from([
+            ("open notes".to_string(), KeyphraseEntry::from("")),
+            (
+                "notes app".to_string(),
+                KeyphraseEntry::Options(crate::config::KeyphraseEntryOptions {
+                    priority: 5,
+                    strip: Some(false),
+                    ..Default::default()
+                }),
+            ),
+        ]);
+        let keyphrases = parse_keyphrase_map(&kp_map, &KeyphraseMatchingStrategy::Simple).unwrap();
+        let set = KeyphraseSet::new(keyphrases, &KeyphraseMatchingStrategy::Simple, Normalization::Nfc);
+        let kept = resolve_overlaps(set.detect(text, &options));
+        assert_eq!(kept.len(), 1);
+        assert_eq!(kept[0].keyphrase, "notes app");
+        assert_eq!(set.process(text, true, &options), text);
+    }
+
+    #[test]
+    fn test_fuzzy_keyphrases() {
+        let keyphrases = vec![KeyphraseAction {
+            keyphrase: String::from("open soulver"),
+            action: ActionType::None,
+            ..Default::default()
+        }];
+        let mut options = KeyphraseProcessingOptions {
+            matching_strategy: KeyphraseMatchingStrategy::Fuzzy,
+            ..Default::default()
+        };
+
+        let text = "Open solver and add the totals.";
+        let matches = detect_all_keyphrases(text, &keyphrases, &options);
+        assert_eq!(matches.len(), 1);
+        assert_eq!(&text[matches[0].start_pos..matches[0].end_pos], "Open solver");
+        assert!(matches[0].confidence < 1.0);
+        assert_eq!(process_keyphrases_enhanced(text, &keyphrases, true, &options), "and add the totals.");
+
+        // Below the confidence threshold the text is left alone
+        options.min_confidence = 0.99;
+        assert_eq!(process_keyphrases_enhanced(text, &keyphrases, true, &options), text);
+    }
+
+    #[test]
+    fn test_keyphrase_definitions() {
+        let config: AppConfig = toml::from_str(
+            r#"
+            detect_keyphrases = true
+
+            [keyphrases]
+            "zebra" = "Zebra"
+            "apple" = "Apple"
+
+            [[keyphrase]]
+            phrase = "open notes"
+            aliases = ["show my notes"]
+            action = ["Obsidian", "https://example.com/notes"]
+            punctuation_handling = "all"
+
+            [[keyphrase]]
+            phrase = "note"
+            matching_strategy = "wholeword"
+            strip = false
+
+            [[keyphrase]]
+            phrase = "old command"
+            action = "Old"
+            enabled = false
+            "#,
+        )
+        .unwrap();
+
+        let keyphrases = parse_keyphrases(&config).unwrap();
+        let phrases: Vec<&str> = keyphrases.iter().map(|ka| ka.keyphrase.as_str()).collect();
+        // Structured entries in order, then the flat table sorted
+        assert_eq!(phrases, vec!["open notes", "show my notes", "note", "apple", "zebra"]);
+        assert!(matches!(&keyphrases[1].action, ActionType::Sequence(actions) if actions.len() == 2));
+        assert_eq!(keyphrases[2].strategy, Some(KeyphraseMatchingStrategy::WholeWord));
+
+        // "note" only matches as a whole word, and stays in the text
+        let options = KeyphraseProcessingOptions::default();
+        let set = KeyphraseSet::new(keyphrases, &options.matching_strategy, options.normalization);
+        let text = "Show my notes?! Then a note.";
+        let found: Vec<String> = set.detect(text, &options).into_iter().map(|m| m.keyphrase).collect();
+        assert_eq!(found, vec!["show my notes", "note"]);
+        assert_eq!(set.process(text, true, &options), "Then a note.");
+    }
+
+    #[test]
+    fn test_keyphrase_macros() {
+        let parse = |toml: &str| {
+            let config: AppConfig = toml::from_str(toml).unwrap();
+            parse_keyphrase_definitions(&config.keyphrase.unwrap(), &KeyphraseMatchingStrategy::Simple)
+        };
+
+        let keyphrases = parse(
+            r#"
+            [[keyphrase]]
+            phrase = "to obsidian"
+            steps = [
+                { action = "Obsidian" },
+                { clipboard = "{segment_after}", delay_ms = 500, on_failure = "continue" },
+            ]
+            "#,
+        )
+        .unwrap();
+        assert!(matches!(&keyphrases[0].action, ActionType::Macro(steps) if steps.steps.len() == 2));
+
+        let err = parse(
+            r#"
+            [[keyphrase]]
+            phrase = "to obsidian"
+            action = "Obsidian"
+            steps = [{ action = "Obsidian" }]
+            "#,
+        )
+        .unwrap_err();
+        assert!(err.contains("more than one of action, steps and exec"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_exec_keyphrase() {
+        let config: AppConfig = toml::from_str(
+            r#"
+            [[keyphrase]]
+            phrase = "shout"
+            exec = { argv = ["tr", "a-z", "A-Z"], stdin = "{segment_after}", replace_text = true }
+
+            [[keyphrase]]
+            phrase = "tag it"
+            exec = { argv = ["sh", "-c", "echo \"$1\" > \"$2\"", "sh", "{mode}: {text}", "{file}"] }
+            "#,
+        )
+        .unwrap();
+        let keyphrases =
+            parse_keyphrase_definitions(&config.keyphrase.unwrap(), &KeyphraseMatchingStrategy::Simple).unwrap();
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let file = temp_dir.path().join("out.txt");
+        let options = KeyphraseProcessingOptions {
+            context: vec![
+                ("mode".to_string(), "Note".to_string()),
+                ("file".to_string(), file.display().to_string()),
+            ],
+            ..Default::default()
+        };
+
+        // Arguments are filled in without a shell, so dictated quotes stay text
+        let text = "Buy \"milk\" $(date). Tag it.";
+        assert_eq!(process_keyphrases_enhanced(text, &keyphrases, false, &options), "Buy \"milk\" $(date).");
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "Note: Buy \"milk\" $(date).\n");
+
+        // The command's output replaces the copied text
+        assert_eq!(process_keyphrases_enhanced("Shout. hello there", &keyphrases, false, &options), "HELLO THERE");
+    }
+
+    #[test]
+    fn test_keyphrase_positions() {
+        let keyphrase = |phrase: &str, position| KeyphraseAction {
+            keyphrase: phrase.to_string(),
+            action: ActionType::None,
+            position,
+            ..Default::default()
+        };
+        let keyphrases = vec![
+            keyphrase("send that", KeyphrasePosition::End),
+            keyphrase("note", KeyphrasePosition::Start),
+            keyphrase("scratch that", KeyphrasePosition::Alone),
+        ];
+        let options = KeyphraseProcessingOptions::default();
+        let found = |text: &str| -> Vec<String> {
+            let set = KeyphraseSet::new(keyphrases.clone(), &options.matching_strategy, options.normalization);
+            let from_set: Vec<String> = set.detect(text, &options).into_iter().map(|m| m.keyphrase).collect();
+            let scanned: Vec<String> = detect_all_keyphrases(text, &keyphrases, &options)
+                .into_iter()
+                .map(|m| m.keyphrase)
+                .collect();
+            assert_eq!(from_set, scanned);
+            scanned
+        };
+
+        assert_eq!(found("Note: the report is done. Send that."), vec!["note", "send that"]);
+        assert!(found("Please send that note to Sam and scratch that idea.").is_empty());
+        assert_eq!(found("Lunch at noon. Scratch that! Send that"), vec!["scratch that", "send that"]);
+    }
+
+    #[test]
+    fn test_escape_word() {
+        let keyphrases = vec![
+            KeyphraseAction {
+                keyphrase: String::from("open browser"),
+                action: ActionType::None,
+                ..Default::default()
+            },
+            KeyphraseAction {
+                keyphrase: String::from("send that"),
+                action: ActionType::None,
+                ..Default::default()
+            },
+        ];
+        let options = KeyphraseProcessingOptions {
+            escape_word: Some(String::from("literal")),
+            ..Default::default()
+        };
+
+        let text = "Tell Sam to say Literal, open browser first. Send that";
+        assert_eq!(
+            process_keyphrases_enhanced(text, &keyphrases, true, &options),
+            "Tell Sam to say open browser first."
+        );
+        // Only directly before a keyphrase, and only as a whole word
+        let text = "Open browser with the literal text. Illiteral send that";
+        assert_eq!(
+            process_keyphrases_enhanced(text, &keyphrases, true, &options),
+            "with the literal text. Illiteral"
+        );
+    }
+
+    #[test]
+    fn test_unicode_keyphrases() {
+        let keyphrases = vec![KeyphraseAction {
+            keyphrase: String::from("große straße"),
+            action: ActionType::None,
+            ..Default::default()
+        }];
+
+        // Folding "İ" and "ẞ" changes their length, which used to shift or break the match
+        let text = "İn der GROSSE STRAẞE links.";
+        for strategy in [KeyphraseMatchingStrategy::Simple, KeyphraseMatchingStrategy::WholeWord] {
+            let options = KeyphraseProcessingOptions {
+                matching_strategy: strategy.clone(),
+                ..Default::default()
+            };
+            let matches = detect_all_keyphrases(text, &keyphrases, &options);
+            assert_eq!(&text[matches[0].start_pos..matches[0].end_pos], "GROSSE STRAẞE");
+
+            let set = KeyphraseSet::new(keyphrases.clone(), &strategy, Normalization::Nfc);
+            assert_eq!(set.process(text, true, &options), "İn der links.");
+        }
+    }
+
+    #[test]
+    fn test_regex_keyphrases() {
+        let kp_map = HashMap::from([
+            (
+                "re:remind me in (?P<n>\\d+) minutes".to_string(),
+                "https://example.com/remind?minutes={n}".into(),
+            ),
+            ("re:open (obsidian|notes)".to_string(), "Obsidian".into()),
+        ]);
+        let keyphrases = parse_keyphrase_map(&kp_map, &KeyphraseMatchingStrategy::Simple).unwrap();
+        let options = KeyphraseProcessingOptions::default();
+
+        let text = "Open Notes and remind me in 15 minutes to call back.";
+        let matches = detect_all_keyphrases(text, &keyphrases, &options);
+        assert_eq!(matches.len(), 2);
+        assert_eq!((matches[0].start_pos, matches[0].end_pos), (0, 10));
+        assert_eq!(matches[1].captures, vec![("n".to_string(), "15".to_string())]);
+        match matches[1].action.with_captures(&matches[1].captures) {
+            ActionType::OpenUrl(url) => assert_eq!(url, "https://example.com/remind?minutes=15"),
+            other => panic!("Expected OpenUrl, got {:?}", other),
+        }
+        assert_eq!(process_keyphrases_enhanced(text, &keyphrases, true, &options), "and to call back.");
+
+        // Invalid patterns are reported by keyphrase when the config loads
+        let bad = HashMap::from([("re:open (notes".to_string(), KeyphraseEntry::from("Notes"))]);
+        let err = parse_keyphrase_map(&bad, &KeyphraseMatchingStrategy::Simple).unwrap_err();
+        assert!(err.contains("'re:open (notes'"));
+    }
+
     #[test]
     fn test_chained_actions_realistic_example() {
         let keyphrases = vec![
             KeyphraseAction {
                 keyphrase: String::from("open notes"),
                 action: ActionType::None, // Use None for testing
+                ..Default::default()
             },
             KeyphraseAction {
                 keyphrase: String::from("create reminder"),
                 action: ActionType::None, // Use None for testing
+                ..Default::default()
             },
         ];
 
@@ -0,0 +1,474 @@
+use crate::config::{get_ledger_path, AppConfig};
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Name of the ledger file in the state directory
+pub const LEDGER_FILE: &str = "ledger.json";
+
+/// Minimum number of journal records before the ledger is compacted
+const MIN_COMPACT_RECORDS: usize = 64;
+
+/// Shared handle to the processed-file ledger
+pub type SharedLedger = Arc<Mutex<Ledger>>;
+
+/// Result of processing a meta.json file, as recorded in the ledger
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProcessOutcome {
+    /// Text was copied to the clipboard
+    Copied,
+    /// Text was processed but clipboard operations are disabled
+    ClipboardDisabled,
+    /// Text was processed but could not be copied to the clipboard
+    ClipboardFailed,
+    /// Text was written by a route's file sinks only
+    Delivered,
+    /// A route's file sink could not be written
+    DeliveryFailed,
+    /// File was deliberately skipped (e.g. mode filter); checked again next time
+    Skipped,
+    /// No text was found in the configured result field
+    NoText,
+    /// The file never parsed as valid JSON
+    ParseError,
+}
+
+impl ProcessOutcome {
+    /// Whether this outcome means the file should not be processed again
+    ///
+    /// Skipped files are not final, so a changed or reloaded filter gets to see them again.
+    pub fn is_final(&self) -> bool {
+        matches!(
+            self,
+            ProcessOutcome::Copied | ProcessOutcome::ClipboardDisabled | ProcessOutcome::Delivered
+        )
+    }
+}
+
+impl fmt::Display for ProcessOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ProcessOutcome::Copied => "copied",
+            ProcessOutcome::ClipboardDisabled => "clipboard_disabled",
+            ProcessOutcome::ClipboardFailed => "clipboard_failed",
+            ProcessOutcome::Delivered => "delivered",
+            ProcessOutcome::DeliveryFailed => "delivery_failed",
+            ProcessOutcome::Skipped => "skipped",
+            ProcessOutcome::NoText => "no_text",
+            ProcessOutcome::ParseError => "parse_error",
+        };
+        f.pad(name)
+    }
+}
+
+/// A single ledger record for a processed file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LedgerEntry {
+    pub hash: String,
+    pub processed_at: u64, // Seconds since the unix epoch
+    pub outcome: ProcessOutcome,
+    #[serde(flatten)]
+    pub delivery: Delivery,
+}
+
+/// What earlier attempts already did with a file, so a retry doesn't do it again
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Delivery {
+    /// Sinks the text already went out to
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub delivered: Vec<String>,
+    /// Text after keyphrase processing, per keyphrase set, including any action output
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub prepared: BTreeMap<String, String>,
+}
+
+impl Delivery {
+    /// Whether the keyphrase actions already ran for this content
+    pub fn actions_ran(&self) -> bool {
+        // Entries written before `prepared` existed only list their sinks
+        !self.prepared.is_empty() || !self.delivered.is_empty()
+    }
+}
+
+/// On-disk layout of the ledger file
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct LedgerFile {
+    #[serde(default)]
+    entries: BTreeMap<PathBuf, LedgerEntry>,
+}
+
+/// Persistent record of processed files, keyed by path and content hash
+///
+/// New records are appended to a journal next to the ledger file, which is folded
+/// back into the ledger once it grows as large as the ledger itself.
+#[derive(Debug)]
+pub struct Ledger {
+    path: Option<PathBuf>,
+    entries: BTreeMap<PathBuf, LedgerEntry>,
+    journal_records: usize,
+}
+
+impl Ledger {
+    /// Open the ledger at the given path, starting empty if it doesn't exist yet
+    pub fn open(path: &Path) -> Ledger {
+        let mut entries = match fs::read_to_string(path) {
+            Ok(content) => match serde_json::from_str::<LedgerFile>(&content) {
+                Ok(file) => file.entries,
+                Err(e) => {
+                    warn!("Ignoring unreadable ledger {}: {}", path.display(), e);
+                    BTreeMap::new()
+                }
+            },
+            Err(_) => {
+                debug!("No ledger found at {}, starting a new one", path.display());
+                BTreeMap::new()
+            }
+        };
+
+        // Replay records appended since the ledger was last compacted
+        let mut journal_records = 0;
+        if let Ok(journal) = fs::read_to_string(journal_path(path)) {
+            for line in journal.lines().filter(|line| !line.trim().is_empty()) {
+                match serde_json::from_str::<(PathBuf, LedgerEntry)>(line) {
+                    Ok((file, entry)) => {
+                        entries.insert(file, entry);
+                        journal_records += 1;
+                    }
+                    // A crash mid-write can leave a partial last line
+                    Err(e) => warn!("Ignoring unreadable ledger journal record: {}", e),
+                }
+            }
+        }
+
+        Ledger {
+            path: Some(path.to_path_buf()),
+            entries,
+            journal_records,
+        }
+    }
+
+    /// A ledger that never persists or skips anything
+    pub fn disabled() -> Ledger {
+        Ledger {
+            path: None,
+            entries: BTreeMap::new(),
+            journal_records: 0,
+        }
+    }
+
+    /// Whether the ledger is backed by a file
+    pub fn is_enabled(&self) -> bool {
+        self.path.is_some()
+    }
+
+    /// Check if this exact file content was already processed to a final outcome
+    pub fn is_processed(&self, path: &Path, hash: &str) -> bool {
+        self.entries
+            .get(path)
+            .is_some_and(|entry| entry.hash == hash && entry.outcome.is_final())
+    }
+
+    /// What earlier attempts already did with this exact file content
+    pub fn delivery(&self, path: &Path, hash: &str) -> Delivery {
+        self.entries
+            .get(path)
+            .filter(|entry| entry.hash == hash)
+            .map(|entry| entry.delivery.clone())
+            .unwrap_or_default()
+    }
+
+    /// Record the outcome for a file and persist it to the journal
+    pub fn record(&mut self, path: &Path, hash: String, outcome: ProcessOutcome) {
+        self.record_delivery(path, hash, outcome, Delivery::default());
+    }
+
+    /// Record the outcome for a file along with what was delivered so far
+    ///
+    /// The delivery is only kept while the file may be retried, so the transcripts of
+    /// finished files don't pile up in the state directory.
+    pub fn record_delivery(&mut self, path: &Path, hash: String, outcome: ProcessOutcome, delivery: Delivery) {
+        if !self.is_enabled() {
+            return;
+        }
+        let delivery = if outcome.is_final() { Delivery::default() } else { delivery };
+
+        let entry = LedgerEntry {
+            hash,
+            processed_at: unix_now(),
+            outcome,
+            delivery,
+        };
+        let appended = self.append_to_journal(path, &entry);
+        self.entries.insert(path.to_path_buf(), entry);
+
+        let result = match appended {
+            Ok(()) if self.journal_records < MIN_COMPACT_RECORDS.max(self.entries.len()) => Ok(()),
+            // Fold a large journal back into the ledger, or fall back to a full save
+            _ => self.save(),
+        };
+        if let Err(e) = result {
+            warn!("Failed to save ledger: {}", e);
+        }
+    }
+
+    /// Append one record to the journal
+    fn append_to_journal(&mut self, path: &Path, entry: &LedgerEntry) -> std::io::Result<()> {
+        let ledger_path = match &self.path {
+            Some(ledger_path) => ledger_path,
+            None => return Ok(()),
+        };
+
+        if let Some(parent) = ledger_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let line = serde_json::to_string(&(path, entry)).map_err(std::io::Error::other)?;
+        let mut journal = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(journal_path(ledger_path))?;
+        writeln!(journal, "{}", line)?;
+        self.journal_records += 1;
+        Ok(())
+    }
+
+    /// Drop entries older than the retention period, returning how many were removed
+    pub fn prune(&mut self, retention: Duration) -> usize {
+        let cutoff = unix_now().saturating_sub(retention.as_secs());
+        let old_len = self.entries.len();
+        self.entries.retain(|_, entry| entry.processed_at >= cutoff);
+        old_len - self.entries.len()
+    }
+
+    /// Remove all entries and persist the empty ledger
+    pub fn clear(&mut self) -> std::io::Result<()> {
+        self.entries.clear();
+        self.save()
+    }
+
+    /// All recorded entries, ordered by path
+    pub fn entries(&self) -> &BTreeMap<PathBuf, LedgerEntry> {
+        &self.entries
+    }
+
+    /// Write the whole ledger to disk atomically (write to a temp file, then rename)
+    /// and empty the journal
+    pub fn save(&mut self) -> std::io::Result<()> {
+        let path = match &self.path {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let file = LedgerFile {
+            entries: self.entries.clone(),
+        };
+        let content = serde_json::to_string_pretty(&file).map_err(std::io::Error::other)?;
+
+        let tmp_path = path.with_extension("json.tmp");
+        fs::write(&tmp_path, content)?;
+        fs::rename(&tmp_path, path)?;
+
+        match fs::remove_file(journal_path(path)) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e),
+            _ => {}
+        }
+        self.journal_records = 0;
+        Ok(())
+    }
+}
+
+/// Journal of records appended since the ledger file was last written
+fn journal_path(ledger_path: &Path) -> PathBuf {
+    ledger_path.with_extension("journal")
+}
+
+/// Open the ledger described by the configuration, pruning expired entries
+pub fn open_configured_ledger(config: &AppConfig) -> Ledger {
+    let ledger_config = config.ledger.as_ref();
+    if !ledger_config.and_then(|l| l.enabled).unwrap_or(true) {
+        info!("Processed-file ledger disabled");
+        return Ledger::disabled();
+    }
+
+    let path = get_ledger_path(config);
+    let mut ledger = Ledger::open(&path);
+
+    let retention_days = ledger_config.and_then(|l| l.retention_days).unwrap_or(30);
+    let pruned = ledger.prune(Duration::from_secs(retention_days * 24 * 60 * 60));
+    if pruned > 0 {
+        debug!("Pruned {} expired ledger entries", pruned);
+        if let Err(e) = ledger.save() {
+            warn!("Failed to save ledger: {}", e);
+        }
+    }
+
+    info!("Using processed-file ledger at {} ({} entries)", path.display(), ledger.entries().len());
+    ledger
+}
+
+/// Hash file content for ledger comparisons (hex-encoded SHA-256)
+pub fn content_hash(content: &[u8]) -> String {
+    Sha256::digest(content)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Print the ledger contents to stdout
+pub fn print_ledger(ledger: &Ledger, path: &Path) {
+    println!("Ledger: {} ({} entries)", path.display(), ledger.entries().len());
+    for (file, entry) in ledger.entries() {
+        let processed_at = UNIX_EPOCH + Duration::from_secs(entry.processed_at);
+        println!(
+            "  {}  {:<18}  {}  {}",
+            humantime::format_rfc3339_seconds(processed_at),
+            entry.outcome,
+            &entry.hash[..12.min(entry.hash.len())],
+            file.display()
+        );
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_content_hash() {
+        assert_eq!(content_hash(b"abc"), content_hash(b"abc"));
+        assert_ne!(content_hash(b"abc"), content_hash(b"abd"));
+        assert_eq!(content_hash(b"").len(), 64);
+    }
+
+    #[test]
+    fn test_ledger_persists_across_opens() {
+        let temp_dir = tempdir().unwrap();
+        let ledger_path = temp_dir.path().join(LEDGER_FILE);
+        let meta_path = Path::new("/recordings/1/meta.json");
+
+        let mut ledger = Ledger::open(&ledger_path);
+        assert!(!ledger.is_processed(meta_path, "abc"));
+        ledger.record(meta_path, "abc".to_string(), ProcessOutcome::Copied);
+
+        let reopened = Ledger::open(&ledger_path);
+        assert!(reopened.is_processed(meta_path, "abc"));
+        // Changed content at the same path is processed again
+        assert!(!reopened.is_processed(meta_path, "def"));
+    }
+
+    #[test]
+    fn test_non_final_outcomes_are_retried() {
+        let temp_dir = tempdir().unwrap();
+        let mut ledger = Ledger::open(&temp_dir.path().join(LEDGER_FILE));
+        let meta_path = Path::new("/recordings/1/meta.json");
+
+        ledger.record(meta_path, "abc".to_string(), ProcessOutcome::ParseError);
+        assert!(!ledger.is_processed(meta_path, "abc"));
+
+        // Skipped files are checked again, in case the filter changed
+        ledger.record(meta_path, "abc".to_string(), ProcessOutcome::Skipped);
+        assert!(!ledger.is_processed(meta_path, "abc"));
+    }
+
+    #[test]
+    fn test_delivery_persists() {
+        let temp_dir = tempdir().unwrap();
+        let ledger_path = temp_dir.path().join(LEDGER_FILE);
+        let meta_path = Path::new("/recordings/1/meta.json");
+
+        let delivery = Delivery {
+            delivered: vec!["clipboard".to_string()],
+            prepared: BTreeMap::from([("watch".to_string(), "Output".to_string())]),
+        };
+        let mut ledger = Ledger::open(&ledger_path);
+        ledger.record_delivery(meta_path, "abc".to_string(), ProcessOutcome::ClipboardFailed, delivery.clone());
+
+        let reopened = Ledger::open(&ledger_path);
+        assert_eq!(reopened.delivery(meta_path, "abc"), delivery);
+        assert_eq!(reopened.delivery(meta_path, "def"), Delivery::default());
+        assert!(!Delivery::default().actions_ran());
+
+        // Once the file is done, its text isn't kept
+        ledger.record_delivery(meta_path, "abc".to_string(), ProcessOutcome::Copied, delivery);
+        assert_eq!(Ledger::open(&ledger_path).delivery(meta_path, "abc"), Delivery::default());
+        assert!(!fs::read_to_string(journal_path(&ledger_path)).unwrap().lines().last().unwrap().contains("Output"));
+
+        // Records from before the prepared text was kept still count as having run
+        let entry: LedgerEntry = serde_json::from_str(
+            r#"{"hash": "abc", "processed_at": 0, "outcome": "delivery_failed", "delivered": ["log/clipboard"]}"#,
+        )
+        .unwrap();
+        assert!(entry.delivery.prepared.is_empty());
+        assert!(entry.delivery.actions_ran());
+    }
+
+    #[test]
+    fn test_records_go_to_journal_until_compacted() {
+        let temp_dir = tempdir().unwrap();
+        let ledger_path = temp_dir.path().join(LEDGER_FILE);
+        let mut ledger = Ledger::open(&ledger_path);
+
+        ledger.record(Path::new("/a/meta.json"), "a".to_string(), ProcessOutcome::Copied);
+        ledger.record(Path::new("/a/meta.json"), "b".to_string(), ProcessOutcome::Copied);
+        assert!(!ledger_path.exists());
+        assert!(journal_path(&ledger_path).exists());
+
+        // The journal is replayed in order on open
+        let reopened = Ledger::open(&ledger_path);
+        assert!(reopened.is_processed(Path::new("/a/meta.json"), "b"));
+        assert!(!reopened.is_processed(Path::new("/a/meta.json"), "a"));
+
+        for i in 0..MIN_COMPACT_RECORDS {
+            let path = PathBuf::from(format!("/{}/meta.json", i));
+            ledger.record(&path, i.to_string(), ProcessOutcome::Copied);
+        }
+        assert!(ledger_path.exists());
+        assert!(ledger.journal_records < MIN_COMPACT_RECORDS);
+        assert_eq!(Ledger::open(&ledger_path).entries().len(), MIN_COMPACT_RECORDS + 1);
+    }
+
+    #[test]
+    fn test_prune_and_clear() {
+        let temp_dir = tempdir().unwrap();
+        let ledger_path = temp_dir.path().join(LEDGER_FILE);
+        let mut ledger = Ledger::open(&ledger_path);
+
+        ledger.record(Path::new("/a/meta.json"), "a".to_string(), ProcessOutcome::Copied);
+        ledger.entries.get_mut(Path::new("/a/meta.json")).unwrap().processed_at = 0;
+        ledger.record(Path::new("/b/meta.json"), "b".to_string(), ProcessOutcome::Copied);
+
+        assert_eq!(ledger.prune(Duration::from_secs(3600)), 1);
+        assert_eq!(ledger.entries().len(), 1);
+
+        ledger.clear().unwrap();
+        assert!(Ledger::open(&ledger_path).entries().is_empty());
+    }
+
+    #[test]
+    fn test_disabled_ledger() {
+        let mut ledger = Ledger::disabled();
+        let meta_path = Path::new("/recordings/1/meta.json");
+        ledger.record(meta_path, "abc".to_string(), ProcessOutcome::Copied);
+        assert!(!ledger.is_processed(meta_path, "abc"));
+    }
+}
@@ -5,71 +5,90 @@
 //!
 //! ## Features
 //!
-//! - Monitors directories for new meta.json files
-//! - Extracts LLM results from various json field formats
+//! - Monitors one or more directories for new meta.json files, each with its own profile
+//! - Falls back to polling for network and FUSE mounts without native change events
+//! - Extracts LLM results from various json field formats, or any field via JSON Pointer/JSONPath
 //! - Copies results to clipboard in different formats (plaintext, richtext, markdown)
-//! - Detects keyphrases and executes associated actions
+//! - Detects keyphrases, literal or regex with named captures, and executes associated actions
+//! - Tolerates misheard keyphrases with fuzzy and phonetic matching
+//! - Finds all literal keyphrases in a single pass with a precompiled Aho-Corasick automaton
+//! - Matches keyphrases case-insensitively with full Unicode case folding and normalization
+//! - Limits keyphrases to the start or end of a dictation, or to a sentence on their own
+//! - Lets an escape word ("literal open browser") dictate a keyphrase as plain text
+//! - Defines keyphrases as ordered `[[keyphrase]]` entries with aliases, action lists and per-entry options
+//! - Runs multi-step keyphrase macros with delays, variables and per-step failure handling
+//! - Runs commands with templated arguments through `exec` actions, optionally copying their output
 //! - Supports chained actions through natural dictation
+//! - Passes the words after a keyphrase to its action through a `{query}` placeholder
 //! - Configurable text cleaning options
+//! - Routes results by mode or content to their own keyphrases, cleaning and outputs
+//! - Builds transcripts from timed segments, with optional SRT/WebVTT subtitles
 //! - Supports dry-run mode for testing actions
+//! - Optionally backfills recordings that arrived while it was not running
+//! - Remembers processed files across restarts in an on-disk ledger
+//! - Processes files on a bounded background worker queue
+//! - Reloads keyphrases and settings when config.toml changes, no restart needed
+//! - Shuts down gracefully on Ctrl-C or SIGTERM, draining queued files first
 //!
 //! ## Example
 //!
 //! ```no_run
 //! use sw_catcher::{
-//!     config::{load_config, Opts},
+//!     config::{load_config_with_opts, Opts},
 //!     logging::setup_logging,
 //!     watcher::start_watcher,
 //! };
+//! use clap::Parser;
 //!
 //! fn main() {
-//!     let app_state = load_config().expect("Failed to load configuration");
+//!     let opts = Opts::parse();
+//!     let app_state = load_config_with_opts(opts.clone()).expect("Failed to load configuration");
 //!     setup_logging(&app_state).expect("Failed to set up logging");
-//!     start_watcher(app_state).expect("Failed to start file watcher");
+//!     start_watcher(app_state, opts).expect("Failed to start file watcher");
 //! }
 //! ```
 
 // Define all modules
 pub mod actions;
+pub mod automaton;
+pub mod backend;
+pub mod backfill;
+pub mod casefold;
 pub mod clipboard;
 pub mod config;
+pub mod exec;
+pub mod extract;
+pub mod filter;
+pub mod fuzzy;
 pub mod keyphrase;
+pub mod ledger;
 pub mod logging;
+pub mod macros;
+pub mod meta;
 pub mod meta_processor;
+pub mod profile;
+pub mod queue;
+pub mod reload;
+pub mod route;
+pub mod stability;
 pub mod text_processing;
 pub mod watcher;
 
-// Define the Meta type here to avoid circular dependencies
-mod meta {
-    use serde::Deserialize;
-
-    /// Flexible Meta structure that looks for different possible keys
-    #[derive(Debug, Deserialize)]
-    pub struct Meta {
-        #[serde(rename = "llmResult", default)]
-        pub llm_result: Option<String>,
-        #[serde(default)]
-        pub result: Option<String>,
-        #[serde(rename = "rawResult", default)]
-        pub raw_result: Option<String>,
-        #[serde(rename = "modeName", default)]
-        pub mode_name: Option<String>,
-    }
-}
-
 // Re-export key types and functions
 pub use actions::{
     execute_action, ActionType
 };
 pub use clipboard::{copy_to_clipboard_with_format, ensure_clipboard_content_with_monitoring, ClipboardFormat};
-pub use config::{load_config, create_default_config_file, AppConfig, AppState, Opts};
+pub use config::{load_config, load_config_with_opts, create_default_config_file, AppConfig, AppState, Opts};
 pub use keyphrase::{
     detect_all_keyphrases, process_keyphrases, process_keyphrases_enhanced,
     KeyphraseAction, KeyphraseProcessingOptions, KeyphraseMatch, TextSegment,
 };
+pub use ledger::{Ledger, ProcessOutcome};
 pub use logging::setup_logging;
-pub use meta::Meta;
+pub use meta::{Meta, Segment};
 pub use meta_processor::{process_meta_file, LastProcessedMap};
+pub use profile::WatchProfile;
 pub use text_processing::apply_text_cleaning;
 pub use watcher::start_watcher;
 
@@ -78,30 +97,24 @@ pub const VERSION: &str = env!("CARGO_PKG_VERSION");
 pub const AUTHOR: &str = env!("CARGO_PKG_AUTHORS");
 
 /// Extract text from meta.json based on user preference
+///
+/// The preference is a named field (llm, raw, intermediate, auto), a JSON Pointer or a JSONPath.
 pub fn extract_text_by_preference(meta: &Meta, preference: &str) -> Option<String> {
-    match preference.to_lowercase().as_str() {
-        "llm" => meta.llm_result.clone(),
-        "raw" => meta.raw_result.clone(),
-        "intermediate" => meta.result.clone(),
-        _ => {
-            // "auto" or any other value - try each field in order, with cloning to avoid ownership issues
-            meta.llm_result.clone()
-                .or_else(|| meta.result.clone())
-                .or_else(|| meta.raw_result.clone())
-        }
-    }
+    let chain = extract::FieldChain::parse(&[preference]).ok()?;
+    chain.extract(&meta.to_value()).map(|(text, _)| text)
 }
 
 /// Run the application
 pub fn run() -> Result<(), Box<dyn std::error::Error>> {
     // Load configuration
-    let app_state = load_config()?;
+    let opts = <Opts as clap::Parser>::parse();
+    let app_state = load_config_with_opts(opts.clone())?;
 
     // Setup logging
     setup_logging(&app_state)?;
 
     // Start watching for files
-    start_watcher(app_state)?;
+    start_watcher(app_state, opts)?;
 
     Ok(())
 }
@@ -118,6 +131,7 @@ mod tests {
             result: Some("This is the intermediate result".to_string()),
             raw_result: Some("This is the raw result".to_string()),
             mode_name: Some("Literal".to_string()),
+            ..Default::default()
         };
 
         // Test each preference
@@ -133,6 +147,7 @@ mod tests {
             result: Some("This is the intermediate result".to_string()),
             raw_result: Some("This is the raw result".to_string()),
             mode_name: Some("Literal".to_string()),
+            ..Default::default()
         };
 
         assert_eq!(extract_text_by_preference(&meta_partial, "llm"), None);
@@ -143,6 +158,7 @@ mod tests {
             result: None,
             raw_result: Some("This is the raw result".to_string()),
             mode_name: None,
+            ..Default::default()
         };
 
         assert_eq!(extract_text_by_preference(&meta_minimal, "auto"), Some("This is the raw result".to_string()));
@@ -1,4 +1,4 @@
-use crate::config::{parse_log_level, AppState, get_default_log_directory};
+use crate::config::{parse_log_level, AppState, get_default_log_directory, get_watch_paths};
 use log::{debug, LevelFilter, warn};
 use notify::Error as NotifyError;
 use notify::Result as NotifyResult;
@@ -72,8 +72,7 @@ fn setup_null_logging() -> NotifyResult<()> {
         return Ok(());
     }
 
-    CombinedLogger::init(loggers)
-        .map_err(|e| NotifyError::generic(&format!("Failed to initialize logger: {}", e)))?;
+    init_loggers(loggers);
 
     debug!("Null logging initialized (file logging disabled)");
     
@@ -113,14 +112,24 @@ pub fn setup_logging_with_params(
         debug!("Terminal logging enabled");
     }
 
-    CombinedLogger::init(loggers)
-        .map_err(|e| NotifyError::generic(&format!("Failed to initialize logger: {}", e)))?;
+    init_loggers(loggers);
 
     debug!("Logging initialized at level {}", level);
 
     Ok(())
 }
 
+/// Install the combined logger, tolerating a logger that is already in place
+///
+/// The `log` crate only allows one global logger per process, so a second
+/// initialization (e.g. from tests sharing a process) keeps the existing one.
+fn init_loggers(loggers: Vec<Box<dyn SharedLogger>>) {
+    if CombinedLogger::init(loggers).is_err() {
+        // SetLoggerError is only returned when a logger has already been set
+        debug!("Logger already initialized, keeping the existing logger");
+    }
+}
+
 /// Log application startup information
 pub fn log_startup_info(app_state: &AppState) {
     debug!("sw-catcher starting up");
@@ -135,8 +144,8 @@ pub fn log_startup_info(app_state: &AppState) {
 
     debug!("Using clipboard format: {:?}", app_state.clipboard_format);
 
-    if let Some(watch_dir) = &app_state.config.watch_dir {
-        debug!("Watching for meta.json files in: {}", watch_dir);
+    for watch_path in get_watch_paths(&app_state.config) {
+        debug!("Watching for meta.json files in: {}", watch_path.display());
     }
 }
 
@@ -148,7 +157,6 @@ pub fn log_separator() {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::path::PathBuf;
     use tempfile::tempdir;
 
     #[test]
@@ -0,0 +1,289 @@
+use crate::actions::{execute_action_output, fill_placeholders, parse_action, query_capture_in, ActionType, QueryCapture};
+use crate::clipboard::truncate;
+use crate::config::MacroStepConfig;
+use crate::exec::{resolve_exec, run_exec};
+use log::{debug, info, warn};
+use std::thread;
+use std::time::Duration;
+
+/// Steps run in order for one keyphrase
+///
+/// Each step can wait before it runs, save its output as a `{name}` variable for the
+/// steps after it, and either stop the macro or let it carry on when it fails.
+#[derive(Debug, Clone, Default)]
+pub struct Macro {
+    pub steps: Vec<MacroStep>,
+    /// Variables from the keyphrase, such as `{text}` and `{query}`, available to every step
+    pub variables: Vec<(String, String)>,
+}
+
+/// One step of a macro
+#[derive(Debug, Clone)]
+pub struct MacroStep {
+    pub kind: StepKind,
+    /// Wait before running the step
+    pub delay: Duration,
+    /// Carry on with the next step if this one fails
+    pub continue_on_failure: bool,
+    /// Save the step's output as `{name}` for later steps
+    pub save_as: Option<String>,
+}
+
+/// What a macro step does
+#[derive(Debug, Clone)]
+pub enum StepKind {
+    /// Run an action; its output is what a command printed, or the URL or application opened
+    Action(ActionType),
+    /// Copy text instead of the dictated text; its output is the text
+    Clipboard(String),
+    /// Only fill in a template, to save it for later steps
+    Value(String),
+}
+
+impl Macro {
+    /// The capture asked for by the first `{query}` placeholder in the steps
+    pub fn query_capture(&self) -> Option<QueryCapture> {
+        self.steps.iter().find_map(|step| match &step.kind {
+            StepKind::Action(action) => action.query_capture(),
+            StepKind::Clipboard(template) | StepKind::Value(template) => query_capture_in(template),
+        })
+    }
+
+    /// Add variables for the steps, filled in together with the steps' own saved outputs
+    pub fn with_variables(&self, variables: &[(String, String)]) -> Macro {
+        let mut with_variables = self.clone();
+        with_variables.variables.extend(variables.iter().cloned());
+        with_variables
+    }
+}
+
+/// Resolve the `steps` of a `[[keyphrase]]` entry
+pub fn resolve_macro(steps: &[MacroStepConfig]) -> Result<Macro, String> {
+    let steps = steps
+        .iter()
+        .enumerate()
+        .map(|(index, step)| {
+            let kind = match (&step.action, &step.exec, &step.clipboard, &step.value) {
+                (Some(action), None, None, None) => StepKind::Action(parse_action(action)),
+                (None, Some(exec), None, None) => StepKind::Action(ActionType::Exec(
+                    resolve_exec(exec).map_err(|e| format!("Macro step {}: {}", index + 1, e))?,
+                )),
+                (None, None, Some(text), None) => StepKind::Clipboard(text.clone()),
+                (None, None, None, Some(value)) => StepKind::Value(value.clone()),
+                _ => {
+                    return Err(format!(
+                        "Macro step {} needs exactly one of action, exec, clipboard or value",
+                        index + 1
+                    ))
+                }
+            };
+
+            Ok(MacroStep {
+                kind,
+                delay: Duration::from_millis(step.delay_ms.unwrap_or(0)),
+                continue_on_failure: step.on_failure.as_deref().map(parse_on_failure).unwrap_or(false),
+                save_as: step.save_as.clone(),
+            })
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+
+    Ok(Macro {
+        steps,
+        variables: Vec::new(),
+    })
+}
+
+/// Parse an `on_failure` setting: true to continue with the next step
+fn parse_on_failure(on_failure: &str) -> bool {
+    on_failure.eq_ignore_ascii_case("continue")  // Default to stopping the macro
+}
+
+/// Run a macro's steps in order
+///
+/// Stops at the first failing step unless that step continues on failure. Returns the
+/// text to copy instead of the dictated text, if a clipboard or command step replaces it.
+pub fn run_macro(steps: &Macro) -> Result<Option<String>, Box<dyn std::error::Error>> {
+    let mut variables = steps.variables.clone();
+    let mut replacement = None;
+
+    for (i, step) in steps.steps.iter().enumerate() {
+        if !step.delay.is_zero() {
+            debug!("Macro step {}: waiting {:?}", i + 1, step.delay);
+            thread::sleep(step.delay);
+        }
+
+        match run_step(&step.kind, &variables, &mut replacement) {
+            Ok(output) => {
+                if let Some(name) = &step.save_as {
+                    debug!("Macro step {}: {{{}}} = \"{}\"", i + 1, name, truncate(&output, 60));
+                    variables.retain(|(existing, _)| existing != name);
+                    variables.push((name.clone(), output));
+                }
+            }
+            Err(e) if step.continue_on_failure => warn!("Macro step {} failed, continuing: {}", i + 1, e),
+            Err(e) => return Err(format!("macro step {} failed: {}", i + 1, e).into()),
+        }
+    }
+
+    Ok(replacement)
+}
+
+/// Run one step with the variables saved so far, returning its output
+fn run_step(
+    kind: &StepKind,
+    variables: &[(String, String)],
+    replacement: &mut Option<String>,
+) -> Result<String, Box<dyn std::error::Error>> {
+    match kind {
+        StepKind::Action(action) => {
+            let action = action.with_captures(variables);
+            if let ActionType::Exec(exec) = &action {
+                // The output is available to later steps even if it doesn't replace the text
+                let output = run_exec(exec)?;
+                if exec.replace_text {
+                    *replacement = Some(output.clone());
+                }
+                return Ok(output);
+            }
+
+            if let Some(text) = execute_action_output(&action)? {
+                *replacement = Some(text);
+            }
+            Ok(match action {
+                ActionType::OpenApplication(target) | ActionType::OpenUrl(target) => target,
+                _ => String::new(),
+            })
+        }
+        StepKind::Clipboard(template) => {
+            // Delivered with the rest of the result, in the profile's format and routes
+            let text = fill(template, variables);
+            info!("Macro will copy: {}", truncate(&text, 60));
+            *replacement = Some(text.clone());
+            Ok(text)
+        }
+        StepKind::Value(template) => Ok(fill(template, variables)),
+    }
+}
+
+/// Replace `{name}` placeholders with the variables' values
+fn fill(template: &str, variables: &[(String, String)]) -> String {
+    fill_placeholders(template, variables, false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keyphrase::{process_chained_actions, KeyphraseMatch};
+
+    fn step(action: Option<&str>, value: Option<&str>) -> MacroStepConfig {
+        MacroStepConfig {
+            action: action.map(str::to_string),
+            value: value.map(str::to_string),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_resolve_macro() {
+        let mut steps = vec![
+            step(None, Some("{segment_after}")),
+            step(Some("https://example.com/?q={query:words:2}"), None),
+        ];
+        steps[0].save_as = Some("title".to_string());
+        steps[1].delay_ms = Some(500);
+        steps[1].on_failure = Some("Continue".to_string());
+
+        let resolved = resolve_macro(&steps).unwrap();
+        assert_eq!(resolved.query_capture(), Some(QueryCapture::Words(2)));
+        assert_eq!(resolved.steps[0].save_as.as_deref(), Some("title"));
+        assert_eq!(resolved.steps[1].delay, Duration::from_millis(500));
+        assert!(resolved.steps[1].continue_on_failure && !resolved.steps[0].continue_on_failure);
+
+        let err = resolve_macro(&[step(Some("Notes"), Some("both"))]).unwrap_err();
+        assert!(err.contains("step 1"));
+    }
+
+    // Only Linux fails to start a missing application; macOS and Windows hand it to a launcher
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_run_macro_variables_and_failures() {
+        let failing = |continue_on_failure| MacroStep {
+            kind: StepKind::Action(ActionType::OpenApplication("/nonexistent/sw-catcher-test-app".to_string())),
+            delay: Duration::ZERO,
+            continue_on_failure,
+            save_as: Some("opened".to_string()),
+        };
+        let value = |template: &str, save_as: &str| MacroStep {
+            kind: StepKind::Value(template.to_string()),
+            delay: Duration::ZERO,
+            continue_on_failure: false,
+            save_as: Some(save_as.to_string()),
+        };
+
+        // A failing step stops the macro, unless it continues on failure
+        let stopping = Macro {
+            steps: vec![value("Notes", "app"), failing(false), value("never", "unused")],
+            ..Default::default()
+        };
+        let err = run_macro(&stopping).unwrap_err();
+        assert!(err.to_string().contains("macro step 2"));
+
+        let continuing = Macro {
+            steps: vec![failing(true), value("{opened}", "unset")],
+            ..Default::default()
+        };
+        assert!(run_macro(&continuing).is_ok());
+
+        // Saved outputs fill later steps; unknown names are left alone
+        let variables = [("app".to_string(), "Notes".to_string())];
+        let output = run_step(&StepKind::Value("{app} and {label}".to_string()), &variables, &mut None).unwrap();
+        assert_eq!(output, "Notes and {label}");
+    }
+
+    #[test]
+    fn test_saved_values_are_not_expanded() {
+        let steps = Macro::default().with_variables(&[
+            ("text".to_string(), "see {file}".to_string()),
+            ("file".to_string(), "/tmp/meta.json".to_string()),
+        ]);
+
+        // Dictated text that contains a placeholder is passed through as written
+        let mut variables = steps.variables.clone();
+        let said = run_step(&StepKind::Value("{text}".to_string()), &variables, &mut None).unwrap();
+        assert_eq!(said, "see {file}");
+
+        variables.push(("said".to_string(), said));
+        let both = run_step(&StepKind::Value("{said}|{file}".to_string()), &variables, &mut None).unwrap();
+        assert_eq!(both, "see {file}|/tmp/meta.json");
+    }
+
+    #[test]
+    fn test_clipboard_step_replaces_text() {
+        let clipboard = |template: &str| MacroStep {
+            kind: StepKind::Clipboard(template.to_string()),
+            delay: Duration::ZERO,
+            continue_on_failure: false,
+            save_as: None,
+        };
+        let steps = Macro {
+            steps: vec![clipboard("first"), clipboard("{segment_after}")],
+            ..Default::default()
+        };
+        let variables = steps.with_variables(&[("segment_after".to_string(), "buy milk".to_string())]);
+
+        // The last clipboard step is what gets copied, nothing is copied directly
+        assert_eq!(run_macro(&variables).unwrap().as_deref(), Some("buy milk"));
+
+        // and it is the text the keyphrase processing hands on for delivery
+        let text = "to notes buy milk";
+        let matches = [KeyphraseMatch {
+            keyphrase: "to notes".to_string(),
+            action: ActionType::Macro(steps),
+            start_pos: 0,
+            end_pos: 8,
+            confidence: 1.0,
+            ..Default::default()
+        }];
+        assert_eq!(process_chained_actions(text, &matches, false), "buy milk");
+    }
+}
@@ -1,9 +1,11 @@
 //! The main entry point for the sw-catcher application.
+use clap::Parser;
 use log::{error, info};
 use std::error::Error;
 use std::process;
 use sw_catcher::{
-    config::{create_default_config_file, load_config, print_usage_guide},
+    config::{create_default_config_file, CONFIG_FILE, get_ledger_path, load_config_file, load_config_with_opts, print_usage_guide, Opts},
+    ledger::{print_ledger, Ledger},
     logging::{log_startup_info, setup_logging},
     watcher::start_watcher,
     AUTHOR, VERSION,
@@ -15,7 +17,7 @@ fn main() {
     println!("Watching for LLM results in meta.json files");
 
     // Try to create default config file (will be ignored if already exists)
-    if let Err(e) = create_default_config_file("config.toml") {
+    if let Err(e) = create_default_config_file(CONFIG_FILE) {
         eprintln!("Warning: Failed to create default config file: {}", e);
         // Continue execution, as this is not a critical error
     }
@@ -31,8 +33,14 @@ fn main() {
 
 /// Run the application
 fn run() -> Result<(), Box<dyn Error>> {
-    // Load configuration
-    let app_state = load_config()?;
+    // Ledger maintenance commands run without starting the watcher
+    let opts = Opts::parse();
+    if opts.show_ledger || opts.clear_ledger {
+        return manage_ledger(&opts);
+    }
+
+    // Load configuration, reusing the options parsed above
+    let app_state = load_config_with_opts(opts.clone())?;
 
     // Set up logging
     setup_logging(&app_state)?;
@@ -43,11 +51,31 @@ fn run() -> Result<(), Box<dyn Error>> {
     // Display startup message
     info!("sw-catcher started successfully");
 
-    // Start the file watcher (this blocks until application termination)
-    start_watcher(app_state)?;
+    // Start the file watcher (this blocks until Ctrl-C or SIGTERM)
+    start_watcher(app_state, opts)?;
 
     // This point is reached only on clean shutdown
     info!("sw-catcher shutting down");
+    log::logger().flush();
 
     Ok(())
-}
\ No newline at end of file
+}
+
+/// Inspect or clear the processed-file ledger
+fn manage_ledger(opts: &Opts) -> Result<(), Box<dyn Error>> {
+    let config = load_config_file(CONFIG_FILE)?;
+    let ledger_path = get_ledger_path(&config);
+    let mut ledger = Ledger::open(&ledger_path);
+
+    if opts.show_ledger {
+        print_ledger(&ledger, &ledger_path);
+    }
+
+    if opts.clear_ledger {
+        let count = ledger.entries().len();
+        ledger.clear()?;
+        println!("Cleared {} entries from {}", count, ledger_path.display());
+    }
+
+    Ok(())
+}
@@ -0,0 +1,274 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::path::Path;
+
+/// A superwhisper meta.json file
+///
+/// Every field is optional so files from older or newer superwhisper versions still
+/// parse. Fields without a typed counterpart, or with a value of an unexpected shape,
+/// are kept in `extra`, so an odd auxiliary field never hides the result text.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(from = "Map<String, Value>")]
+pub struct Meta {
+    #[serde(rename = "llmResult", skip_serializing_if = "Option::is_none")]
+    pub llm_result: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<String>,
+    #[serde(rename = "rawResult", skip_serializing_if = "Option::is_none")]
+    pub raw_result: Option<String>,
+    #[serde(rename = "modeName", skip_serializing_if = "Option::is_none")]
+    pub mode_name: Option<String>,
+    /// Recording length in milliseconds
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duration: Option<f64>,
+    /// When the recording was made, as written by superwhisper (e.g. "2024-05-01T09:00:00")
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub datetime: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+    #[serde(rename = "appVersion", skip_serializing_if = "Option::is_none")]
+    pub app_version: Option<String>,
+    #[serde(rename = "modelName", skip_serializing_if = "Option::is_none")]
+    pub model_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prompt: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub segments: Vec<Segment>,
+    /// Any other fields in the file
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// Whether a watched file is read as JSON: a `.json` extension in any case, or none at all
+///
+/// Anything else is a plain text transcript.
+pub fn is_json_path(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_none_or(|ext| ext.eq_ignore_ascii_case("json"))
+}
+
+/// A timed piece of the transcript
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(from = "Map<String, Value>")]
+pub struct Segment {
+    /// Start time in seconds
+    pub start: f64,
+    /// End time in seconds
+    pub end: f64,
+    pub text: String,
+    /// Any other fields in the segment (e.g. speaker)
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+impl From<Map<String, Value>> for Meta {
+    fn from(mut fields: Map<String, Value>) -> Self {
+        Meta {
+            llm_result: take(&mut fields, "llmResult", string),
+            result: take(&mut fields, "result", string),
+            raw_result: take(&mut fields, "rawResult", string),
+            mode_name: take(&mut fields, "modeName", string),
+            duration: take(&mut fields, "duration", Value::as_f64),
+            datetime: take(&mut fields, "datetime", string),
+            language: take(&mut fields, "language", string),
+            app_version: take(&mut fields, "appVersion", string),
+            model_name: take(&mut fields, "modelName", string),
+            prompt: take(&mut fields, "prompt", string),
+            segments: take(&mut fields, "segments", segments).unwrap_or_default(),
+            extra: fields,
+        }
+    }
+}
+
+impl From<Map<String, Value>> for Segment {
+    fn from(mut fields: Map<String, Value>) -> Self {
+        Segment {
+            start: take(&mut fields, "start", Value::as_f64).unwrap_or_default(),
+            end: take(&mut fields, "end", Value::as_f64).unwrap_or_default(),
+            text: take(&mut fields, "text", string).unwrap_or_default(),
+            extra: fields,
+        }
+    }
+}
+
+/// Remove a field whose value `convert` accepts; nulls are dropped and anything else
+/// stays in `fields` as it was
+fn take<T>(fields: &mut Map<String, Value>, key: &str, convert: impl Fn(&Value) -> Option<T>) -> Option<T> {
+    let value = fields.get(key)?;
+    let converted = convert(value);
+    if converted.is_some() || value.is_null() {
+        fields.remove(key);
+    }
+    converted
+}
+
+fn string(value: &Value) -> Option<String> {
+    value.as_str().map(str::to_string)
+}
+
+/// A text, number or boolean value as it would read in a template
+fn scalar_text(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) => Some(s.clone()),
+        // Whole milliseconds read as "4250", not "4250.0"
+        Value::Number(n) => Some(n.as_f64().filter(|_| n.is_f64()).map_or(n.to_string(), |f| f.to_string())),
+        Value::Bool(b) => Some(b.to_string()),
+        _ => None,
+    }
+}
+
+/// Segments, if the value is a list of objects
+fn segments(value: &Value) -> Option<Vec<Segment>> {
+    value
+        .as_array()?
+        .iter()
+        .map(|segment| segment.as_object().cloned().map(Segment::from))
+        .collect()
+}
+
+impl Meta {
+    /// Recording length in seconds
+    pub fn duration_secs(&self) -> Option<f64> {
+        self.duration.map(|ms| ms / 1000.0)
+    }
+
+    /// Look up a field by its meta.json name, including fields kept in `extra`
+    ///
+    /// This gives filters, templates and actions one way to read any field.
+    pub fn get(&self, key: &str) -> Option<Value> {
+        let string = |s: &Option<String>| s.clone().map(Value::String);
+        let typed = match key {
+            "llmResult" => string(&self.llm_result),
+            "result" => string(&self.result),
+            "rawResult" => string(&self.raw_result),
+            "modeName" => string(&self.mode_name),
+            "duration" => self.duration.and_then(serde_json::Number::from_f64).map(Value::Number),
+            "datetime" => string(&self.datetime),
+            "language" => string(&self.language),
+            "appVersion" => string(&self.app_version),
+            "modelName" => string(&self.model_name),
+            "prompt" => string(&self.prompt),
+            "segments" if !self.segments.is_empty() => serde_json::to_value(&self.segments).ok(),
+            _ => None,
+        };
+        // Fields with an unexpected shape are kept as they were written
+        typed.or_else(|| self.extra.get(key).cloned())
+    }
+
+    /// Values for `{name}` placeholders in actions
+    ///
+    /// The common fields have short names that are always set, empty if the file lacks
+    /// them: `{mode}`, `{language}`, `{duration}` (in seconds), `{datetime}`, `{model}` and
+    /// `{prompt}`. Any other text or number field is available under its meta.json name,
+    /// e.g. `{appVersion}` or `{modelKey}`.
+    pub fn template_variables(&self) -> Vec<(String, String)> {
+        let mut variables: Vec<(String, String)> = match self.to_value() {
+            Value::Object(fields) => fields
+                .into_iter()
+                .filter_map(|(name, value)| Some((name, scalar_text(&value)?)))
+                .collect(),
+            _ => Vec::new(),
+        };
+
+        let short = [
+            ("mode", self.mode_name.clone()),
+            ("language", self.language.clone()),
+            ("duration", self.duration_secs().map(|secs| secs.to_string())),
+            ("datetime", self.datetime.clone()),
+            ("model", self.model_name.clone()),
+            ("prompt", self.prompt.clone()),
+        ];
+        variables.extend(short.map(|(name, value)| (name.to_string(), value.unwrap_or_default())));
+        variables
+    }
+
+    /// Convert back to a JSON value, with the same field names as meta.json
+    pub fn to_value(&self) -> Value {
+        serde_json::to_value(self).unwrap_or(Value::Null)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_full_meta() {
+        let json = r#"{
+            "appVersion": "1.40.2",
+            "datetime": "2024-05-01T09:00:00",
+            "duration": 4250,
+            "language": "en",
+            "llmResult": "Hello there.",
+            "modeName": "Default",
+            "modelName": "Ultra",
+            "modelKey": "sw-ultra-cloud-v1-east",
+            "prompt": "Fix grammar",
+            "rawResult": "hello there",
+            "result": "Hello there",
+            "segments": [
+                {"start": 0.0, "end": 1.5, "text": "Hello", "speaker": "A"},
+                {"start": 1.5, "end": 4.2, "text": " there"}
+            ]
+        }"#;
+        let meta: Meta = serde_json::from_str(json).unwrap();
+
+        assert_eq!(meta.app_version.as_deref(), Some("1.40.2"));
+        assert_eq!(meta.language.as_deref(), Some("en"));
+        assert_eq!(meta.model_name.as_deref(), Some("Ultra"));
+        assert_eq!(meta.duration_secs(), Some(4.25));
+        assert_eq!(meta.segments.len(), 2);
+        assert_eq!(meta.segments[1].end, 4.2);
+        assert_eq!(meta.segments[0].extra.get("speaker"), Some(&Value::from("A")));
+
+        // Unknown fields are kept rather than dropped
+        assert_eq!(meta.get("modelKey"), Some(Value::from("sw-ultra-cloud-v1-east")));
+        assert_eq!(meta.get("prompt"), Some(Value::from("Fix grammar")));
+        assert_eq!(meta.get("missing"), None);
+        assert_eq!(meta.to_value()["modelKey"], Value::from("sw-ultra-cloud-v1-east"));
+
+        let variables = meta.template_variables();
+        let variable = |name: &str| variables.iter().rev().find(|(n, _)| n == name).map(|(_, v)| v.as_str());
+        assert_eq!(variable("language"), Some("en"));
+        assert_eq!(variable("duration"), Some("4.25"));
+        assert_eq!(variable("model"), Some("Ultra"));
+        assert_eq!(variable("modelKey"), Some("sw-ultra-cloud-v1-east"));
+        assert_eq!(variable("appVersion"), Some("1.40.2"));
+        assert_eq!(variable("segments"), None);
+
+        let empty = Meta::default().template_variables();
+        assert!(empty.contains(&("language".to_string(), String::new())));
+    }
+
+    #[test]
+    fn test_is_json_path() {
+        assert!(is_json_path(Path::new("a/meta.json")));
+        assert!(is_json_path(Path::new("a/META.JSON")));
+        assert!(is_json_path(Path::new("a/meta")));
+        assert!(!is_json_path(Path::new("a/note.txt")));
+    }
+
+    #[test]
+    fn test_odd_auxiliary_fields_keep_the_result() {
+        let json = r#"{
+            "result": "hi",
+            "segments": null,
+            "duration": "4250",
+            "prompt": {"name": "Fix grammar"}
+        }"#;
+        let meta: Meta = serde_json::from_str(json).unwrap();
+
+        assert_eq!(meta.result.as_deref(), Some("hi"));
+        assert!(meta.segments.is_empty());
+        assert_eq!(meta.duration, None);
+        assert_eq!(meta.get("duration"), Some(Value::from("4250")));
+        assert_eq!(meta.get("prompt").unwrap()["name"], Value::from("Fix grammar"));
+
+        let meta: Meta = serde_json::from_str(
+            r#"{"result": "hi", "segments": [{"start": 0, "end": 1, "text": null}]}"#,
+        )
+        .unwrap();
+        assert_eq!((meta.segments.len(), meta.segments[0].text.as_str()), (1, ""));
+    }
+}
@@ -1,47 +1,111 @@
-use crate::clipboard::ensure_clipboard_content_with_monitoring;
 use crate::clipboard::truncate;
-use crate::config::AppState;
-use crate::extract_text_by_preference;
-use crate::keyphrase::{process_keyphrases_enhanced, KeyphraseAction, KeyphraseProcessingOptions};
-use crate::text_processing::apply_text_cleaning;
-use log::{debug, error, info};
+use crate::config::{parse_subtitle_formats, AppState, SegmentsConfig};
+use crate::keyphrase::KeyphraseProcessingOptions;
+use crate::ledger::{content_hash, Delivery, ProcessOutcome, SharedLedger};
+use crate::meta::{is_json_path, Meta};
+use crate::profile::WatchProfile;
+use crate::queue::JobQueue;
+use crate::route::{combine_outcomes, deliver, is_delivered, matching_routes, Route, Sink};
+use crate::stability::wait_for_complete_file;
+use crate::text_processing::{apply_cleaning_options, apply_text_cleaning, render_segments, render_subtitles, SegmentRenderOptions, SubtitleFormat};
+use log::{debug, error, info, warn};
 use serde_json::Value;
 use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::thread::sleep;
 
 // Type alias for the map tracking recently processed files
 pub type LastProcessedMap = Arc<Mutex<HashMap<PathBuf, Instant>>>;
 
+/// A file waiting to be processed by a worker
+pub struct ProcessJob {
+    pub path: PathBuf,
+    pub profile: Arc<WatchProfile>,
+}
+
+/// Queue of files waiting to be processed
+pub type ProcessQueue = JobQueue<ProcessJob>;
+
+/// Record that a file is about to be processed
+///
+/// Returns false if the same file was already seen within the last second.
+pub fn debounce(path: &Path, last_processed: &LastProcessedMap) -> bool {
+    let now = Instant::now();
+    let mut map = last_processed.lock().unwrap();
+    if let Some(last_time) = map.get(path) {
+        if now.duration_since(*last_time) < Duration::from_secs(1) {
+            debug!("Skipping recently processed file: {}", path.display());
+            return false;
+        }
+    }
+    // Update the last processed time
+    map.insert(path.to_path_buf(), now);
+    true
+}
+
+/// Name of the output sink a profile's results go to
+///
+/// Jobs for the same sink are processed one at a time, in the order they were queued.
+/// Profiles that can reach the clipboard share one lane; the rest get their own.
+pub fn sink_key(profile: &WatchProfile, app_state: &AppState) -> String {
+    // Recordings that match no route go to the profile's clipboard, unless a route catches everything
+    let always_routed = profile.routes.iter().any(|route| route.filter.is_empty());
+    let routes_use_clipboard = profile
+        .routes
+        .iter()
+        .flat_map(|route| &route.sinks)
+        .any(|sink| matches!(sink, Sink::Clipboard(_)));
+
+    let clipboard_enabled = !app_state.config.disable_clipboard.unwrap_or(false);
+    if clipboard_enabled && (routes_use_clipboard || !always_routed) {
+        "clipboard".to_string()
+    } else {
+        format!("watch:{}", profile.name)
+    }
+}
+
 /// Process a meta.json file
 pub fn process_meta_file(
     path: &Path,
-    last_processed: &LastProcessedMap,
-    keyphrases: &[KeyphraseAction],
+    profile: &WatchProfile,
+    ledger: &SharedLedger,
     keyphrase_options: &KeyphraseProcessingOptions,
     app_state: &Arc<AppState>,
 ) {
-    // Debounce: Check if we've recently processed this file
-    let now = Instant::now();
-    {
-        let mut map = last_processed.lock().unwrap();
-        if let Some(last_time) = map.get(path) {
-            if now.duration_since(*last_time) < Duration::from_secs(1) {
-                debug!("Skipping recently processed file: {}", path.display());
-                return;
-            }
-        }
-        // Update the last processed time
-        map.insert(path.to_path_buf(), now);
+    info!("Found new file at {} (watch '{}')", path.display(), profile.name);
+
+    if let Some((hash, outcome, delivery)) = process_with_retries(path, profile, ledger, keyphrase_options, app_state) {
+        debug!("Recording outcome '{}' for {}", outcome, path.display());
+        ledger.lock().unwrap().record_delivery(path, hash, outcome, delivery);
     }
+}
 
-    info!("Found new meta.json at {}", path.display());
+/// Read, parse and act on a meta.json file, retrying while it is still being written
+///
+/// Returns the content hash, outcome and what was delivered to record in the ledger, or
+/// `None` if there is nothing to record (unreadable file or already in the ledger).
+fn process_with_retries(
+    path: &Path,
+    profile: &WatchProfile,
+    ledger: &SharedLedger,
+    keyphrase_options: &KeyphraseProcessingOptions,
+    app_state: &Arc<AppState>,
+) -> Option<(String, ProcessOutcome, Delivery)> {
+    // Wait for the writer to finish before reading
+    let stability = &profile.stability;
+    if !wait_for_complete_file(path, stability) {
+        warn!(
+            "{} did not look complete within {:?}, trying to process it anyway",
+            path.display(),
+            stability.timeout
+        );
+    }
 
     // Retry configuration
-    let max_retries = 5;
-    let retry_delay = Duration::from_millis(500); // 500ms delay between retries
+    let max_retries = stability.max_retries;
+    let retry_delay = stability.retry_delay;
 
     for attempt in 1..=max_retries {
         // Read the file content
@@ -54,97 +118,70 @@ pub fn process_meta_file(
                     sleep(retry_delay);
                     continue;
                 }
-                return;
+                return None;
+            }
+        };
+
+        // Skip content we have already handled, even across restarts
+        let hash = content_hash(txt.as_bytes());
+        let earlier_delivery = {
+            let ledger = ledger.lock().unwrap();
+            if ledger.is_processed(path, &hash) {
+                info!("Skipping already processed file: {}", path.display());
+                return None;
             }
+            ledger.delivery(path, &hash)
         };
 
         // Parse the JSON
-        match serde_json::from_str::<crate::Meta>(&txt) {
-            Ok(meta) => {
-                // Check if a mode name filter is configured and if it matches
-                if let Some(filter_mode) = &app_state.config.mode_name {
-                    // Skip processing if mode name doesn't match
-                    if let Some(meta_mode) = &meta.mode_name {
-                        if meta_mode != filter_mode {
-                            debug!(
-                                "Skipping meta.json with mode '{}' (filter: '{}')",
-                                meta_mode, filter_mode
-                            );
-                            if attempt < max_retries {
-                                debug!("Retrying in {:?}...", retry_delay);
-                                sleep(retry_delay);
-                                continue;
-                            }
-                            return;
-                        }
-                        // Mode matches, proceed with processing
-                        debug!("Processing meta.json with matching mode: '{}'", filter_mode);
-                    } else {
-                        debug!("Skipping meta.json with no mode name (filter: '{}')", filter_mode);
-                        if attempt < max_retries {
-                            debug!("Retrying in {:?}...", retry_delay);
-                            sleep(retry_delay);
-                            continue;
-                        }
-                        return;
+        match parse_meta(path, &txt) {
+            Ok((document, meta)) => {
+                debug!(
+                    "Recording from {:?}: {:?}s, model {:?}, language {:?}",
+                    meta.datetime,
+                    meta.duration_secs(),
+                    meta.model_name,
+                    meta.language
+                );
+
+                // Build the text from the timed segments, or extract it using the profile's field chain
+                let segments_config = app_state.config.segments.as_ref();
+                let extracted = segments_config
+                    .filter(|config| config.enabled)
+                    .and_then(|config| segment_text(&meta, config))
+                    .map(|text| (text, "segments"))
+                    .or_else(|| profile.result_fields.extract(&document));
+
+                // Filtering is a final decision, the recording won't change on a retry. Without
+                // text, only decide now if the filter doesn't look at the text.
+                let text = extracted.as_ref().map(|(text, _)| text.as_str());
+                if text.is_some() || !profile.filter.uses_text() {
+                    if let Err(reason) = profile.filter.check(&meta, text) {
+                        info!("Skipping {}: {}", path.display(), reason);
+                        return Some((hash, ProcessOutcome::Skipped, Delivery::default()));
                     }
                 }
-                
-                // Get the preference from config
-                let preference = app_state.config.result_field_preference.as_deref().unwrap_or("auto");
-                
-                // Extract text based on preference
-                if let Some(text) = extract_text_by_preference(&meta, preference) {
-                    // Log which field was used
-                    match preference {
-                        "llm" => debug!("Using LLM result field"),
-                        "raw" => debug!("Using raw result field"),
-                        "intermediate" => debug!("Using intermediate result field"),
-                        _ => {
-                            if meta.llm_result.is_some() {
-                                debug!("Auto mode: Using LLM result field");
-                            } else if meta.result.is_some() {
-                                debug!("Auto mode: Using intermediate result field");
-                            } else if meta.raw_result.is_some() {
-                                debug!("Auto mode: Using raw result field");
-                            }
-                        }
-                    }
-                    
-                    // Process keyphrases and get cleaned text
-                    let cleaned_text = if !keyphrases.is_empty() {
-                        process_keyphrases_enhanced(&text, keyphrases, app_state.dry_run, keyphrase_options)
-                    } else {
-                        text.clone()
-                    };
 
-                    // Apply text cleaning if configured
-                    let final_text = apply_text_cleaning(&cleaned_text, &app_state.config);
-
-                    // Check if clipboard operations are disabled
-                    if !app_state.config.disable_clipboard.unwrap_or(false) {
-                        // Copy to clipboard with monitoring for changes
-                        match ensure_clipboard_content_with_monitoring(&final_text, &app_state.clipboard_format) {
-                            Ok(_) => {
-                                info!("Copied to clipboard: {}", truncate(&final_text, 60));
-                            }
-                            Err(e) => error!("Clipboard error: {}", e),
-                        }
-                    } else {
-                        // Log that clipboard operations are skipped
-                        info!("Clipboard operations disabled, not copying to clipboard: {}", truncate(&final_text, 60));
+                if let Some((text, field)) = extracted {
+                    debug!("Using result field {}", field);
+
+                    // Only once the recording is going out, not on every retry
+                    if let Some(config) = segments_config.filter(|config| config.enabled) {
+                        write_subtitles(path, &meta, config, app_state.dry_run);
                     }
-                    return; // Success! Exit function
+
+                    let (outcome, delivery) =
+                        route_text(path, &meta, &text, profile, keyphrase_options, app_state, earlier_delivery);
+                    return Some((hash, outcome, delivery));
+                } else if attempt < max_retries {
+                    debug!("No text found in result fields {} (attempt {}/{}). Retrying in {:?}...",
+                          profile.result_fields.describe(), attempt, max_retries, retry_delay);
+                    sleep(retry_delay);
+                    continue;
                 } else {
-                    if attempt < max_retries {
-                        debug!("No text found in the specified field '{}' (attempt {}/{}). Retrying in {:?}...", 
-                              preference, attempt, max_retries, retry_delay);
-                        sleep(retry_delay);
-                        continue;
-                    } else {
-                        error!("No text found in the specified field: {}", preference);
-                        log_unknown_json_structure(&txt);
-                    }
+                    error!("No text found in result fields: {}", profile.result_fields.describe());
+                    log_unknown_json_structure(&txt);
+                    return Some((hash, ProcessOutcome::NoText, Delivery::default()));
                 }
             }
             Err(e) => {
@@ -156,10 +193,211 @@ pub fn process_meta_file(
                 } else {
                     error!("JSON parse error in {}: {}", path.display(), e);
                     log_unknown_json_structure(&txt);
+                    return Some((hash, ProcessOutcome::ParseError, Delivery::default()));
                 }
             }
         }
     }
+
+    None
+}
+
+/// Run the text through the matching routes, or the watch's own pipeline if none match
+///
+/// Sinks in `delivery` already received this recording on an earlier attempt and are
+/// skipped. Keyphrase actions run once per recording: routes sharing a keyphrase set
+/// share its result, and a retry reuses the text an earlier attempt prepared instead of
+/// running the actions again. Returns the combined outcome and everything done so far.
+fn route_text(
+    path: &Path,
+    meta: &Meta,
+    text: &str,
+    profile: &WatchProfile,
+    keyphrase_options: &KeyphraseProcessingOptions,
+    app_state: &AppState,
+    mut delivery: Delivery,
+) -> (ProcessOutcome, Delivery) {
+    let clipboard_enabled = !app_state.config.disable_clipboard.unwrap_or(false);
+    let routes = matching_routes(&profile.routes, meta, text);
+
+    // Let actions refer to the recording
+    let mut keyphrase_options = keyphrase_options.clone();
+    keyphrase_options.context = meta.template_variables();
+    keyphrase_options.context.push(("file".to_string(), path.display().to_string()));
+    let keyphrase_options = &keyphrase_options;
+
+    let run_actions = !delivery.actions_ran();
+    if !run_actions {
+        info!("Actions for {} already ran on an earlier attempt, not running them again", path.display());
+    }
+    let prepared = &mut delivery.prepared;
+
+    if routes.is_empty() {
+        let final_text = prepare_text(text, None, profile, prepared, run_actions, keyphrase_options, app_state);
+        let sink = Sink::Clipboard(profile.clipboard_format.clone());
+        let outcome = deliver(&sink, &final_text, path, meta, clipboard_enabled, app_state.dry_run);
+        if is_delivered(outcome) {
+            delivery.delivered.push(sink.id());
+        }
+        return (outcome, delivery);
+    }
+
+    let mut outcomes = Vec::new();
+    for route in routes {
+        let pending: Vec<(&Sink, String)> = route
+            .sinks
+            .iter()
+            .map(|sink| (sink, format!("{}/{}", route.name, sink.id())))
+            .filter(|(_, id)| !delivery.delivered.contains(id))
+            .collect();
+        if pending.is_empty() && !route.sinks.is_empty() {
+            debug!("Route '{}' already delivered {}", route.name, path.display());
+            continue;
+        }
+
+        debug!("Using route '{}' for {}", route.name, path.display());
+        let final_text = prepare_text(
+            text,
+            Some(route),
+            profile,
+            &mut delivery.prepared,
+            run_actions,
+            keyphrase_options,
+            app_state,
+        );
+        for (sink, id) in pending {
+            let outcome = deliver(sink, &final_text, path, meta, clipboard_enabled, app_state.dry_run);
+            if is_delivered(outcome) {
+                delivery.delivered.push(id);
+            }
+            outcomes.push(outcome);
+        }
+    }
+    (combine_outcomes(&outcomes), delivery)
+}
+
+/// Process keyphrases, then clean the text with the route's options or the `[text_cleaning]` ones
+///
+/// Uses the route's keyphrases if it has its own, else the watch's. `prepared` holds the
+/// result for each keyphrase set already used for this recording, on this attempt or an
+/// earlier one, so its actions don't run again and their output is reused. Without
+/// `run_actions` the keyphrases are only removed.
+fn prepare_text(
+    text: &str,
+    route: Option<&Route>,
+    profile: &WatchProfile,
+    prepared: &mut BTreeMap<String, String>,
+    run_actions: bool,
+    keyphrase_options: &KeyphraseProcessingOptions,
+    app_state: &AppState,
+) -> String {
+    let (keyphrases, key) = match route {
+        Some(route @ Route { keyphrases: Some(keyphrases), .. }) => (keyphrases, format!("route:{}", route.name)),
+        _ => (&profile.keyphrases, "watch".to_string()),
+    };
+
+    let cleaned_text = match prepared.get(&key) {
+        Some(done) => done.clone(),
+        None => {
+            let done = if keyphrases.is_empty() {
+                text.to_string()
+            } else {
+                keyphrases.process(text, app_state.dry_run || !run_actions, keyphrase_options)
+            };
+            prepared.insert(key, done.clone());
+            done
+        }
+    };
+
+    match route.and_then(|route| route.cleaning.as_ref()) {
+        Some(options) => apply_cleaning_options(&cleaned_text, options),
+        None => apply_text_cleaning(&cleaned_text, &app_state.config),
+    }
+}
+
+/// Build the transcript from the recording's timed segments, if it has any
+fn segment_text(meta: &Meta, config: &SegmentsConfig) -> Option<String> {
+    let options = SegmentRenderOptions {
+        timestamps: config.timestamps,
+        paragraph_gap: config.paragraph_gap_secs.filter(|gap| *gap > 0.0),
+    };
+    let text = render_segments(&meta.segments, &options);
+    (!text.is_empty()).then_some(text)
+}
+
+/// Write subtitle files for the recording's segments, named after the audio file next to it
+fn write_subtitles(path: &Path, meta: &Meta, config: &SegmentsConfig, dry_run: bool) {
+    let formats = config.subtitles.as_deref().map(parse_subtitle_formats).unwrap_or_default();
+    if formats.is_empty() || meta.segments.is_empty() {
+        return;
+    }
+
+    for format in formats {
+        let subtitle_path = subtitle_path(path, format);
+        if dry_run {
+            info!("[DRY RUN] Would write subtitles to {}", subtitle_path.display());
+            continue;
+        }
+
+        match std::fs::write(&subtitle_path, render_subtitles(&meta.segments, format)) {
+            Ok(()) => info!("Wrote subtitles to {}", subtitle_path.display()),
+            Err(e) => error!("Couldn't write subtitles to {}: {}", subtitle_path.display(), e),
+        }
+    }
+}
+
+/// Subtitle file path: next to the recording's audio file if there is one, else next to the meta file
+///
+/// The audio file must share the meta file's stem, or be the only audio file in a folder
+/// holding just this one recording (superwhisper's `meta.json` next to `output.wav`).
+fn subtitle_path(path: &Path, format: SubtitleFormat) -> PathBuf {
+    const AUDIO_EXTENSIONS: &[&str] = &["wav", "m4a", "mp3", "aac", "flac", "ogg"];
+
+    let has_extension = |candidate: &Path, extensions: &[&str]| {
+        candidate
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| extensions.contains(&ext.to_lowercase().as_str()))
+    };
+    let files: Vec<PathBuf> = path
+        .parent()
+        .and_then(|dir| std::fs::read_dir(dir).ok())
+        .map(|entries| entries.flatten().map(|entry| entry.path()).collect())
+        .unwrap_or_default();
+    let audio: Vec<&PathBuf> = files.iter().filter(|file| has_extension(file, AUDIO_EXTENSIONS)).collect();
+
+    let same_stem = audio.iter().find(|file| file.file_stem() == path.file_stem());
+    let only_recording = || {
+        let extension = path.extension().and_then(|ext| ext.to_str()).unwrap_or_default().to_lowercase();
+        let recordings = files.iter().filter(|file| has_extension(file, &[extension.as_str()])).count();
+        (audio.len() == 1 && recordings <= 1).then(|| &audio[0])
+    };
+
+    same_stem
+        .or_else(only_recording)
+        .map_or(path, |audio| audio.as_path())
+        .with_extension(format.extension())
+}
+
+/// Parse a watched file: JSON files as meta.json, anything else as plain text
+///
+/// Returns the document as written, which the result fields are extracted from, and
+/// the recording's metadata. Any JSON document is accepted; metadata is only read from
+/// the fields of an object.
+pub fn parse_meta(path: &Path, txt: &str) -> serde_json::Result<(Value, Meta)> {
+    if is_json_path(path) {
+        let document: Value = serde_json::from_str(txt)?;
+        let meta = document.as_object().cloned().map(Meta::from).unwrap_or_default();
+        return Ok((document, meta));
+    }
+
+    // Plain text transcripts carry no metadata; an empty file is still being written
+    let text = txt.trim();
+    let meta = Meta {
+        result: (!text.is_empty()).then(|| text.to_string()),
+        ..Default::default()
+    };
+    Ok((meta.to_value(), meta))
 }
 
 /// Log details about an unknown JSON structure
@@ -191,5 +429,395 @@ pub fn log_unknown_json_structure(json_text: &str) {
 #[cfg(test)]
 mod tests {
     use super::*;
-    // Tests can be added here later
-}
\ No newline at end of file
+    use crate::clipboard::ClipboardFormat;
+    use crate::config::AppConfig;
+    use crate::extract::FieldChain;
+    use crate::filter::Filter;
+    use crate::keyphrase::KeyphraseSet;
+    use crate::ledger::Ledger;
+    use crate::route::Route;
+    use tempfile::tempdir;
+
+    /// App state, ledger and profile for recordings in `dir`
+    ///
+    /// The clipboard stays off unless the config turns it on, and the ledger is kept
+    /// in `dir`.
+    fn setup(dir: &Path, dry_run: bool, config: AppConfig) -> (Arc<AppState>, Arc<Mutex<Ledger>>, WatchProfile) {
+        let app_state = Arc::new(AppState {
+            config: AppConfig {
+                disable_clipboard: config.disable_clipboard.or(Some(true)),
+                ..config
+            },
+            clipboard_format: ClipboardFormat::PlainText,
+            dry_run,
+            disable_logs: true,
+        });
+        let ledger = Arc::new(Mutex::new(Ledger::open(&dir.join("ledger.json"))));
+        (app_state, ledger, WatchProfile::new("test", dir))
+    }
+
+    #[test]
+    fn test_process_meta_file_records_ledger() {
+        let temp_dir = tempdir().unwrap();
+        let meta_path = temp_dir.path().join("meta.json");
+        std::fs::write(&meta_path, r#"{"result": "Hello world"}"#).unwrap();
+
+        let (app_state, ledger, profile) = setup(temp_dir.path(), true, AppConfig::default());
+        process_meta_file(&meta_path, &profile, &ledger, &KeyphraseProcessingOptions::default(), &app_state);
+
+        let entry = ledger.lock().unwrap().entries().get(&meta_path).cloned().unwrap();
+        assert_eq!(entry.outcome, ProcessOutcome::ClipboardDisabled);
+        assert_eq!(entry.hash, content_hash(br#"{"result": "Hello world"}"#));
+
+        // A finished file leaves no transcript in the state directory
+        assert_eq!(entry.delivery, Delivery::default());
+        let journal = std::fs::read_to_string(temp_dir.path().join("ledger.journal")).unwrap();
+        assert!(!journal.contains("Hello world"));
+    }
+
+    #[test]
+    fn test_sink_key() {
+        let temp_dir = tempdir().unwrap();
+        let clipboard = AppConfig {
+            disable_clipboard: Some(false),
+            ..Default::default()
+        };
+        let (app_state, _, mut profile) = setup(temp_dir.path(), true, clipboard);
+        let (no_clipboard, _, _) = setup(temp_dir.path(), true, AppConfig::default());
+        let route = |filter: Filter, sinks: Vec<Sink>| Route {
+            name: "route".to_string(),
+            filter,
+            keyphrases: None,
+            cleaning: None,
+            sinks,
+            fallthrough: false,
+        };
+        let notes = Sink::NoteDir(temp_dir.path().join("notes"));
+
+        assert_eq!(sink_key(&profile, &app_state), "clipboard");
+
+        // Every recording goes to a file
+        profile.routes = vec![route(Filter::default(), vec![notes.clone()])];
+        assert_eq!(sink_key(&profile, &app_state), "watch:test");
+
+        // Recordings that match no route still fall back to the clipboard
+        profile.routes = vec![route(Filter::from_config(None, Some("Note")).unwrap(), vec![notes.clone()])];
+        assert_eq!(sink_key(&profile, &app_state), "clipboard");
+
+        profile.routes = vec![route(Filter::default(), vec![notes, Sink::Clipboard(ClipboardFormat::PlainText)])];
+        assert_eq!(sink_key(&profile, &app_state), "clipboard");
+        assert_eq!(sink_key(&profile, &no_clipboard), "watch:test");
+    }
+
+    #[test]
+    fn test_retry_skips_delivered_sinks() {
+        let temp_dir = tempdir().unwrap();
+        let meta_path = temp_dir.path().join("rec").join("meta.json");
+        std::fs::create_dir_all(meta_path.parent().unwrap()).unwrap();
+        std::fs::write(&meta_path, r#"{"result": "Hello"}"#).unwrap();
+
+        let log_file = temp_dir.path().join("log.md");
+        let notes = temp_dir.path().join("notes");
+        // A file where the note directory should be makes the note sink fail
+        std::fs::write(&notes, "").unwrap();
+
+        let (app_state, ledger, mut profile) = setup(temp_dir.path(), false, AppConfig::default());
+        profile.routes = vec![Route {
+            name: "log".to_string(),
+            filter: Filter::default(),
+            keyphrases: None,
+            cleaning: None,
+            sinks: vec![Sink::AppendFile(log_file.clone()), Sink::NoteDir(notes.clone())],
+            fallthrough: false,
+        }];
+        let options = KeyphraseProcessingOptions::default();
+
+        process_meta_file(&meta_path, &profile, &ledger, &options, &app_state);
+        let entry = ledger.lock().unwrap().entries().get(&meta_path).cloned().unwrap();
+        assert_eq!(entry.outcome, ProcessOutcome::DeliveryFailed);
+
+        // The retry only writes the note; the log isn't appended to twice
+        std::fs::remove_file(&notes).unwrap();
+        process_meta_file(&meta_path, &profile, &ledger, &options, &app_state);
+        let entry = ledger.lock().unwrap().entries().get(&meta_path).cloned().unwrap();
+        assert_eq!(entry.outcome, ProcessOutcome::Delivered);
+        assert_eq!(std::fs::read_to_string(&log_file).unwrap(), "Hello\n\n");
+        assert_eq!(std::fs::read_dir(&notes).unwrap().count(), 1);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_actions_run_once_per_recording() {
+        use crate::actions::ActionType;
+        use crate::casefold::Normalization;
+        use crate::config::ExecConfig;
+        use crate::exec::resolve_exec;
+        use crate::keyphrase::{KeyphraseAction, KeyphraseMatchingStrategy};
+
+        let temp_dir = tempdir().unwrap();
+        let meta_path = temp_dir.path().join("rec").join("meta.json");
+        std::fs::create_dir_all(meta_path.parent().unwrap()).unwrap();
+        std::fs::write(&meta_path, r#"{"result": "Count me in"}"#).unwrap();
+
+        let runs = temp_dir.path().join("runs.txt");
+        let exec = resolve_exec(&ExecConfig {
+            argv: vec!["sh".to_string(), "-c".to_string(), format!("echo run >> '{}'; echo Counted", runs.display())],
+            replace_text: true,
+            ..Default::default()
+        })
+        .unwrap();
+        let notes = temp_dir.path().join("notes");
+        // A file where the note directory should be makes the note sink fail
+        std::fs::write(&notes, "").unwrap();
+
+        let log_file = temp_dir.path().join("log.md");
+        let route = |name: &str, sink: Sink, fallthrough: bool| Route {
+            name: name.to_string(),
+            filter: Filter::default(),
+            keyphrases: None,
+            cleaning: None,
+            sinks: vec![sink],
+            fallthrough,
+        };
+        let (app_state, ledger, mut profile) = setup(temp_dir.path(), false, AppConfig::default());
+        profile.keyphrases = KeyphraseSet::new(
+            vec![KeyphraseAction {
+                keyphrase: "count me".to_string(),
+                action: ActionType::Exec(exec),
+                ..Default::default()
+            }],
+            &KeyphraseMatchingStrategy::Simple,
+            Normalization::Nfc,
+        );
+        profile.routes = vec![
+            route("log", Sink::AppendFile(log_file.clone()), true),
+            route("notes", Sink::NoteDir(notes.clone()), false),
+        ];
+        let options = KeyphraseProcessingOptions::default();
+
+        // Both routes match, but the action runs once
+        process_meta_file(&meta_path, &profile, &ledger, &options, &app_state);
+        assert_eq!(std::fs::read_to_string(&runs).unwrap(), "run\n");
+
+        // Retrying the failed note doesn't run it again, but still writes its output
+        std::fs::remove_file(&notes).unwrap();
+        process_meta_file(&meta_path, &profile, &ledger, &options, &app_state);
+        let entry = ledger.lock().unwrap().entries().get(&meta_path).cloned().unwrap();
+        assert_eq!(entry.outcome, ProcessOutcome::Delivered);
+        assert_eq!(std::fs::read_to_string(&runs).unwrap(), "run\n");
+        assert_eq!(std::fs::read_to_string(&log_file).unwrap(), "Counted\n\n");
+        let note = std::fs::read_dir(&notes).unwrap().next().unwrap().unwrap().path();
+        assert_eq!(std::fs::read_to_string(note).unwrap(), "Counted\n");
+
+        // Without a matching route, the clipboard's retry doesn't run it again either
+        profile.routes.clear();
+        let other_path = temp_dir.path().join("other").join("meta.json");
+        std::fs::create_dir_all(other_path.parent().unwrap()).unwrap();
+        std::fs::write(&other_path, r#"{"result": "Count me out"}"#).unwrap();
+        let meta = Meta {
+            result: Some("Count me out".to_string()),
+            ..Default::default()
+        };
+        let (_, delivery) =
+            route_text(&other_path, &meta, "Count me out", &profile, &options, &app_state, Delivery::default());
+        assert_eq!(std::fs::read_to_string(&runs).unwrap(), "run\nrun\n");
+        assert_eq!(delivery.delivered, vec!["clipboard".to_string()]);
+        assert_eq!(delivery.prepared.get("watch").map(String::as_str), Some("Counted"));
+
+        // As if the clipboard had failed: the prepared text is kept, nothing was delivered
+        let failed = Delivery {
+            delivered: Vec::new(),
+            ..delivery
+        };
+        let hash = content_hash(br#"{"result": "Count me out"}"#);
+        ledger.lock().unwrap().record_delivery(&other_path, hash, ProcessOutcome::ClipboardFailed, failed);
+        process_meta_file(&other_path, &profile, &ledger, &options, &app_state);
+        assert_eq!(std::fs::read_to_string(&runs).unwrap(), "run\nrun\n");
+        let entry = ledger.lock().unwrap().entries().get(&other_path).cloned().unwrap();
+        assert_eq!(entry.outcome, ProcessOutcome::ClipboardDisabled);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_actions_see_recording_fields() {
+        use crate::actions::ActionType;
+        use crate::casefold::Normalization;
+        use crate::config::ExecConfig;
+        use crate::exec::resolve_exec;
+        use crate::keyphrase::{KeyphraseAction, KeyphraseMatchingStrategy};
+
+        let temp_dir = tempdir().unwrap();
+        let meta_path = temp_dir.path().join("meta.json");
+        std::fs::write(
+            &meta_path,
+            r#"{"result": "Tag it", "language": "de", "duration": 4250, "modelName": "Ultra", "modelKey": "sw-1"}"#,
+        )
+        .unwrap();
+
+        let out = temp_dir.path().join("out.txt");
+        let exec = resolve_exec(&ExecConfig {
+            argv: vec![
+                "sh".to_string(),
+                "-c".to_string(),
+                format!("echo \"$1\" > '{}'", out.display()),
+                "sh".to_string(),
+                "{language} {duration}s {model} {modelKey}".to_string(),
+            ],
+            ..Default::default()
+        })
+        .unwrap();
+
+        let (app_state, ledger, mut profile) = setup(temp_dir.path(), false, AppConfig::default());
+        profile.keyphrases = KeyphraseSet::new(
+            vec![KeyphraseAction {
+                keyphrase: "tag it".to_string(),
+                action: ActionType::Exec(exec),
+                ..Default::default()
+            }],
+            &KeyphraseMatchingStrategy::Simple,
+            Normalization::Nfc,
+        );
+
+        process_meta_file(&meta_path, &profile, &ledger, &KeyphraseProcessingOptions::default(), &app_state);
+        assert_eq!(std::fs::read_to_string(&out).unwrap(), "de 4.25s Ultra sw-1\n");
+    }
+
+    #[test]
+    fn test_subtitle_path() {
+        let temp_dir = tempdir().unwrap();
+        let meta_path = temp_dir.path().join("meta.json");
+        assert_eq!(subtitle_path(&meta_path, SubtitleFormat::Srt), temp_dir.path().join("meta.srt"));
+
+        std::fs::write(temp_dir.path().join("output.wav"), b"RIFF").unwrap();
+        assert_eq!(subtitle_path(&meta_path, SubtitleFormat::Vtt), temp_dir.path().join("output.vtt"));
+
+        // With several recordings in one folder, only an audio file with the same name counts
+        let flat = temp_dir.path().join("flat");
+        std::fs::create_dir(&flat).unwrap();
+        for name in ["one.json", "two.json", "two.m4a"] {
+            std::fs::write(flat.join(name), b"").unwrap();
+        }
+        assert_eq!(subtitle_path(&flat.join("one.json"), SubtitleFormat::Srt), flat.join("one.srt"));
+        assert_eq!(subtitle_path(&flat.join("two.json"), SubtitleFormat::Srt), flat.join("two.srt"));
+        std::fs::write(flat.join("one.wav"), b"").unwrap();
+        assert_eq!(subtitle_path(&flat.join("one.json"), SubtitleFormat::Vtt), flat.join("one.vtt"));
+    }
+
+    #[test]
+    fn test_subtitles_follow_segments_enabled() {
+        let temp_dir = tempdir().unwrap();
+        let meta_path = temp_dir.path().join("meta.json");
+        std::fs::write(
+            &meta_path,
+            r#"{"result": "Hello", "segments": [{"start": 0.0, "end": 1.5, "text": "Hello"}]}"#,
+        )
+        .unwrap();
+
+        for enabled in [false, true] {
+            let config = AppConfig {
+                segments: Some(SegmentsConfig {
+                    enabled,
+                    subtitles: Some("srt".to_string()),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            };
+            let (app_state, _, profile) = setup(temp_dir.path(), false, config);
+            // No ledger, so the second pass processes the file again
+            let ledger = Arc::new(Mutex::new(Ledger::disabled()));
+            process_meta_file(&meta_path, &profile, &ledger, &KeyphraseProcessingOptions::default(), &app_state);
+            assert_eq!(temp_dir.path().join("meta.srt").exists(), enabled);
+        }
+    }
+
+    #[test]
+    fn test_sentinel_only_holds_up_its_own_watch() {
+        use crate::config::{FileStabilityConfig, WatchConfig};
+        use crate::profile::resolve_watch_profiles;
+
+        let temp_dir = tempdir().unwrap();
+        let watch = |name: &str, file_stability: Option<FileStabilityConfig>| {
+            let path = temp_dir.path().join(name);
+            std::fs::create_dir_all(&path).unwrap();
+            std::fs::write(path.join("meta.json"), r#"{"result": "Hello"}"#).unwrap();
+            WatchConfig {
+                name: Some(name.to_string()),
+                path: path.to_string_lossy().to_string(),
+                file_stability,
+                ..Default::default()
+            }
+        };
+        let sentinel = FileStabilityConfig {
+            sentinel: Some("output.wav".to_string()),
+            timeout_ms: Some(1500),
+            ..Default::default()
+        };
+        let config = AppConfig {
+            watch: Some(vec![watch("waits", Some(sentinel)), watch("plain", None)]),
+            ..Default::default()
+        };
+        let (app_state, ledger, _) = setup(temp_dir.path(), true, config);
+        let profiles = resolve_watch_profiles(&app_state).unwrap();
+        let options = KeyphraseProcessingOptions::default();
+
+        let started = Instant::now();
+        process_meta_file(&profiles[1].path.join("meta.json"), &profiles[1], &ledger, &options, &app_state);
+        assert!(started.elapsed() < Duration::from_secs(1));
+
+        let started = Instant::now();
+        process_meta_file(&profiles[0].path.join("meta.json"), &profiles[0], &ledger, &options, &app_state);
+        assert!(started.elapsed() >= Duration::from_millis(1500));
+    }
+
+    #[test]
+    fn test_debounce() {
+        let last_processed: LastProcessedMap = Arc::new(Mutex::new(HashMap::new()));
+        let path = Path::new("/recordings/1/meta.json");
+
+        assert!(debounce(path, &last_processed));
+        assert!(!debounce(path, &last_processed));
+        assert!(debounce(Path::new("/recordings/2/meta.json"), &last_processed));
+    }
+
+    #[test]
+    fn test_parse_meta() {
+        let (_, json) = parse_meta(Path::new("a/meta.json"), r#"{"rawResult": "raw"}"#).unwrap();
+        assert_eq!(json.raw_result.as_deref(), Some("raw"));
+        assert!(parse_meta(Path::new("a/meta.json"), "not json").is_err());
+
+        let (document, text) = parse_meta(Path::new("a/note.txt"), "  Dictated text\n").unwrap();
+        assert_eq!(text.result.as_deref(), Some("Dictated text"));
+        assert_eq!(FieldChain::default().extract(&document).unwrap().0, "Dictated text");
+        assert_eq!(parse_meta(Path::new("a/note.txt"), "").unwrap().1.result, None);
+
+        // Fields are extracted from the document as written, whatever its shape
+        let (document, meta) =
+            parse_meta(Path::new("a/out.json"), r#"{"result": {"text": "Hi"}, "duration": 4250}"#).unwrap();
+        let fields = FieldChain::parse(&["/result/text"]).unwrap();
+        assert_eq!(fields.extract(&document).unwrap().0, "Hi");
+        assert_eq!(FieldChain::parse(&["/duration"]).unwrap().extract(&document).unwrap().0, "4250");
+        assert_eq!(meta.duration_secs(), Some(4.25));
+
+        let (document, meta) = parse_meta(Path::new("a/out.json"), r#"[{"text": "Hi"}]"#).unwrap();
+        assert_eq!(FieldChain::parse(&["$[0].text"]).unwrap().extract(&document).unwrap().0, "Hi");
+        assert_eq!(meta.result, None);
+    }
+
+    #[test]
+    fn test_filtered_file_is_skipped_without_retrying() {
+        let temp_dir = tempdir().unwrap();
+        let meta_path = temp_dir.path().join("meta.json");
+        std::fs::write(&meta_path, r#"{"modeName": "Email"}"#).unwrap();
+
+        let (app_state, ledger, mut profile) = setup(temp_dir.path(), true, AppConfig::default());
+        profile.filter = Filter::from_config(None, Some("Literal")).unwrap();
+
+        // No text and the wrong mode: skipped right away rather than retried as missing text
+        let started = Instant::now();
+        process_meta_file(&meta_path, &profile, &ledger, &KeyphraseProcessingOptions::default(), &app_state);
+        assert!(started.elapsed() < Duration::from_secs(2));
+
+        let entry = ledger.lock().unwrap().entries().get(&meta_path).cloned().unwrap();
+        assert_eq!(entry.outcome, ProcessOutcome::Skipped);
+    }
+}
@@ -0,0 +1,493 @@
+use crate::backend::{WatcherBackend, DEFAULT_POLL_INTERVAL};
+use crate::clipboard::{parse_clipboard_format, ClipboardFormat};
+use crate::config::{configured_matching_strategy, configured_normalization, parse_watcher_backend, AppState, WatchConfig};
+use crate::extract::FieldChain;
+use crate::filter::Filter;
+use crate::keyphrase::{parse_configured_keyphrases, parse_keyphrase_map, KeyphraseSet};
+use crate::route::{resolve_routes, Route};
+use crate::stability::StabilityOptions;
+use globset::{GlobBuilder, GlobMatcher};
+use log::{debug, info};
+use notify::RecursiveMode;
+use regex::Regex;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Default file name written by superwhisper
+pub const DEFAULT_FILENAME: &str = "meta.json";
+
+/// How a profile decides which files to process
+#[derive(Debug, Clone)]
+pub enum FilenameMatcher {
+    /// Glob pattern; matched against the file name, or against the path relative
+    /// to the watch directory if the pattern contains a `/`
+    Glob { matcher: GlobMatcher, match_path: bool },
+    /// Regular expression matched against the file name
+    Regex(Regex),
+}
+
+impl FilenameMatcher {
+    /// Compile a glob pattern
+    pub fn glob(pattern: &str) -> Result<FilenameMatcher, String> {
+        // `*` stays within one directory level, `**` crosses levels
+        let glob = GlobBuilder::new(pattern)
+            .literal_separator(true)
+            .build()
+            .map_err(|e| format!("Invalid pattern '{}': {}", pattern, e))?;
+        Ok(FilenameMatcher::Glob {
+            matcher: glob.compile_matcher(),
+            match_path: pattern.contains('/'),
+        })
+    }
+
+    /// Compile a regular expression
+    pub fn regex(pattern: &str) -> Result<FilenameMatcher, String> {
+        Regex::new(pattern)
+            .map(FilenameMatcher::Regex)
+            .map_err(|e| format!("Invalid regex '{}': {}", pattern, e))
+    }
+
+    /// Check a file, given its path relative to the watch directory
+    pub fn is_match(&self, relative_path: &Path) -> bool {
+        let file_name = match relative_path.file_name().and_then(|s| s.to_str()) {
+            Some(name) => name,
+            None => return false,
+        };
+
+        match self {
+            FilenameMatcher::Glob { matcher, match_path: true } => matcher.is_match(relative_path),
+            FilenameMatcher::Glob { matcher, match_path: false } => matcher.is_match(file_name),
+            FilenameMatcher::Regex(re) => re.is_match(file_name),
+        }
+    }
+}
+
+/// A watched directory together with the settings used to process its files
+#[derive(Debug, Clone)]
+pub struct WatchProfile {
+    pub name: String,
+    pub path: PathBuf,
+    pub pattern: String, // Original pattern, for logging
+    pub matcher: FilenameMatcher,
+    pub recursive: bool,
+    pub max_depth: Option<usize>,
+    pub watcher_backend: WatcherBackend,
+    pub poll_interval: Duration,
+    pub result_fields: FieldChain,
+    pub clipboard_format: ClipboardFormat,
+    pub keyphrases: KeyphraseSet,
+    pub filter: Filter,
+    pub routes: Vec<Route>,
+    /// When a file counts as completely written; a sentinel only holds up its own watch
+    pub stability: StabilityOptions,
+}
+
+impl WatchProfile {
+    /// Create a profile for a directory with default settings
+    pub fn new(name: &str, path: &Path) -> WatchProfile {
+        WatchProfile {
+            name: name.to_string(),
+            path: path.to_path_buf(),
+            pattern: DEFAULT_FILENAME.to_string(),
+            matcher: FilenameMatcher::glob(DEFAULT_FILENAME).expect("default pattern is valid"),
+            recursive: true,
+            max_depth: None,
+            watcher_backend: WatcherBackend::Auto,
+            poll_interval: DEFAULT_POLL_INTERVAL,
+            result_fields: FieldChain::default(),
+            clipboard_format: ClipboardFormat::PlainText,
+            keyphrases: KeyphraseSet::default(),
+            filter: Filter::default(),
+            routes: Vec::new(),
+            stability: StabilityOptions::default(),
+        }
+    }
+
+    /// Check if a path is a file this profile should process
+    pub fn matches_file(&self, path: &Path) -> bool {
+        let relative = match path.strip_prefix(&self.path) {
+            Ok(relative) => relative,
+            Err(_) => return false,
+        };
+
+        let depth = relative.components().count();
+        if depth == 0 || self.max_depth.is_some_and(|max| depth > max) {
+            return false;
+        }
+
+        self.matcher.is_match(relative)
+    }
+
+    /// Deepest level below the watch directory that can contain matching files
+    pub fn effective_max_depth(&self) -> usize {
+        if self.recursive {
+            self.max_depth.unwrap_or(usize::MAX)
+        } else {
+            1
+        }
+    }
+
+    /// Recursive mode to register with the file watcher
+    pub fn recursive_mode(&self) -> RecursiveMode {
+        if self.recursive && self.max_depth != Some(1) {
+            RecursiveMode::Recursive
+        } else {
+            RecursiveMode::NonRecursive
+        }
+    }
+
+    /// Check if a path lies inside this profile's watch directory
+    pub fn contains(&self, path: &Path) -> bool {
+        path.starts_with(&self.path)
+    }
+}
+
+/// Build the watch profiles from the configuration
+///
+/// The top-level `watch_dir` becomes a profile named "default" that uses the global
+/// settings, along with the top-level `pattern`, `regex` and `max_depth`. Each
+/// `[[watch]]` entry falls back to the global settings for anything it doesn't set
+/// itself.
+pub fn resolve_watch_profiles(app_state: &AppState) -> Result<Vec<Arc<WatchProfile>>, String> {
+    let config = &app_state.config;
+    let mut profiles = Vec::new();
+
+    if let Some(watch_dir) = &config.watch_dir {
+        let default_watch = WatchConfig {
+            name: Some("default".to_string()),
+            path: watch_dir.clone(),
+            pattern: config.pattern.clone(),
+            regex: config.regex.clone(),
+            max_depth: config.max_depth,
+            ..Default::default()
+        };
+        profiles.push(Arc::new(resolve_profile(&default_watch, app_state)?));
+    }
+
+    for watch in config.watch.iter().flatten() {
+        profiles.push(Arc::new(resolve_profile(watch, app_state)?));
+    }
+
+    if profiles.is_empty() {
+        return Err("No watch directory specified".to_string());
+    }
+
+    Ok(profiles)
+}
+
+/// Resolve a single watch entry against the global settings
+fn resolve_profile(watch: &WatchConfig, app_state: &AppState) -> Result<WatchProfile, String> {
+    let config = &app_state.config;
+    let name = watch.name.clone().unwrap_or_else(|| watch.path.clone());
+
+    let strategy = configured_matching_strategy(config);
+    let keyphrases = if !config.detect_keyphrases.unwrap_or(false) {
+        Vec::new()
+    } else if let Some(set_name) = &watch.keyphrase_set {
+        let set = config
+            .keyphrase_sets
+            .as_ref()
+            .and_then(|sets| sets.get(set_name))
+            .ok_or_else(|| format!("Watch '{}' uses unknown keyphrase set '{}'", name, set_name))?;
+        parse_keyphrase_map(set, &strategy).map_err(|e| format!("Watch '{}': {}", name, e))?
+    } else {
+        parse_configured_keyphrases(config, &strategy).map_err(|e| format!("Watch '{}': {}", name, e))?
+    };
+    let keyphrases = KeyphraseSet::new(keyphrases, &strategy, configured_normalization(config));
+
+    let clipboard_format = match &watch.clipboard_format {
+        Some(format) => parse_clipboard_format(format),
+        None => app_state.clipboard_format.clone(),
+    };
+
+    let (pattern, matcher) = match (&watch.pattern, &watch.regex) {
+        (Some(_), Some(_)) => {
+            return Err(format!("Watch '{}' sets both pattern and regex, use only one", name));
+        }
+        (_, Some(regex)) => (regex.clone(), FilenameMatcher::regex(regex)),
+        (pattern, None) => {
+            let pattern = pattern.as_deref().unwrap_or(DEFAULT_FILENAME);
+            (pattern.to_string(), FilenameMatcher::glob(pattern))
+        }
+    };
+    let matcher = matcher.map_err(|e| format!("Watch '{}': {}", name, e))?;
+
+    let recursive = watch.recursive.unwrap_or(true);
+    let max_depth = if recursive { watch.max_depth } else { Some(1) };
+
+    let watcher_backend = watch
+        .watcher_backend
+        .as_deref()
+        .or(config.watcher_backend.as_deref())
+        .map(parse_watcher_backend)
+        .unwrap_or(WatcherBackend::Auto);
+    let poll_interval = watch
+        .poll_interval_ms
+        .or(config.poll_interval_ms)
+        .map(Duration::from_millis)
+        .unwrap_or(DEFAULT_POLL_INTERVAL);
+
+    let result_fields = watch
+        .result_field_preference
+        .as_ref()
+        .or(config.result_field_preference.as_ref())
+        .map(|preference| FieldChain::parse(&preference.entries()))
+        .unwrap_or_else(|| Ok(FieldChain::default()))
+        .map_err(|e| format!("Watch '{}': {}", name, e))?;
+
+    let filter = Filter::from_config(
+        watch.filter.as_ref().or(config.filter.as_ref()),
+        watch.mode_name.as_deref().or(config.mode_name.as_deref()),
+    )
+    .map_err(|e| format!("Watch '{}': {}", name, e))?;
+
+    let routes = watch.route.as_deref().or(config.route.as_deref()).unwrap_or_default();
+    let routes = resolve_routes(routes, config, &clipboard_format).map_err(|e| format!("Watch '{}': {}", name, e))?;
+
+    let stability = StabilityOptions::from_config(watch.file_stability.as_ref().or(config.file_stability.as_ref()));
+
+    // Canonicalize so event paths (which may come back resolved) match the profile
+    let path = PathBuf::from(&watch.path);
+    let path = path.canonicalize().unwrap_or(path);
+
+    Ok(WatchProfile {
+        name,
+        path,
+        pattern,
+        matcher,
+        recursive,
+        max_depth,
+        watcher_backend,
+        poll_interval,
+        result_fields,
+        clipboard_format,
+        keyphrases,
+        filter,
+        routes,
+        stability,
+    })
+}
+
+/// Find the profile whose watch directory contains the path (the most specific one wins)
+///
+/// Returns the path as it lies within the profile's directory, which is the canonical
+/// path if only that one matched.
+pub fn profile_for_path<'a>(profiles: &'a [Arc<WatchProfile>], path: &Path) -> Option<(&'a Arc<WatchProfile>, PathBuf)> {
+    let find = |path: &Path| {
+        profiles
+            .iter()
+            .filter(|p| p.contains(path))
+            .max_by_key(|p| p.path.components().count())
+    };
+
+    if let Some(profile) = find(path) {
+        return Some((profile, path.to_path_buf()));
+    }
+    // Event paths may not be canonical (e.g. symlinked watch directories)
+    let canonical = path.parent()?.canonicalize().ok()?.join(path.file_name()?);
+    find(&canonical).map(|profile| (profile, canonical))
+}
+
+/// Log the resolved profiles
+pub fn log_profiles(profiles: &[Arc<WatchProfile>]) {
+    for profile in profiles {
+        info!(
+            "Watch '{}': {} in {:?} (result field: {}, clipboard: {:?})",
+            profile.name, profile.pattern, profile.path, profile.result_fields.describe(), profile.clipboard_format
+        );
+        if let Some(max_depth) = profile.max_depth {
+            debug!("Watch '{}': max depth {}", profile.name, max_depth);
+        }
+        if !profile.filter.is_empty() {
+            info!("Watch '{}': only processing recordings that pass {:?}", profile.name, profile.filter);
+        }
+        debug!("Watch '{}': {} keyphrases", profile.name, profile.keyphrases.len());
+        for ka in profile.keyphrases.actions() {
+            debug!("Keyphrase: \"{}\"", ka.keyphrase);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{AppConfig, RouteConfig, StringOrList};
+    use std::collections::HashMap;
+    use tempfile::tempdir;
+
+    fn app_state(config: AppConfig) -> AppState {
+        AppState {
+            config,
+            clipboard_format: ClipboardFormat::PlainText,
+            dry_run: true,
+            disable_logs: true,
+        }
+    }
+
+    #[test]
+    fn test_resolve_watch_profiles() {
+        let main_dir = tempdir().unwrap();
+        let other_dir = tempdir().unwrap();
+
+        let mut notes = HashMap::new();
+        notes.insert("open notes".to_string(), "Notes".into());
+        let mut sets = HashMap::new();
+        sets.insert("notes".to_string(), notes);
+
+        let mut keyphrases = HashMap::new();
+        keyphrases.insert("open browser".to_string(), "firefox".into());
+
+        let state = app_state(AppConfig {
+            watch_dir: Some(main_dir.path().to_string_lossy().to_string()),
+            detect_keyphrases: Some(true),
+            keyphrases: Some(keyphrases),
+            keyphrase_sets: Some(sets),
+            result_field_preference: Some(StringOrList::One("llm".to_string())),
+            poll_interval_ms: Some(500),
+            route: Some(vec![RouteConfig {
+                name: Some("global".to_string()),
+                ..Default::default()
+            }]),
+            watch: Some(vec![WatchConfig {
+                name: Some("other".to_string()),
+                path: other_dir.path().to_string_lossy().to_string(),
+                pattern: Some("*.result.json".to_string()),
+                max_depth: Some(2),
+                watcher_backend: Some("poll".to_string()),
+                result_field_preference: Some(StringOrList::Many(vec!["/segments/*/text".to_string(), "raw".to_string()])),
+                clipboard_format: Some("markdown".to_string()),
+                keyphrase_set: Some("notes".to_string()),
+                mode_name: Some("Note".to_string()),
+                route: Some(vec![RouteConfig {
+                    name: Some("other notes".to_string()),
+                    ..Default::default()
+                }]),
+                ..Default::default()
+            }]),
+            ..Default::default()
+        });
+
+        let profiles = resolve_watch_profiles(&state).unwrap();
+        assert_eq!(profiles.len(), 2);
+
+        assert_eq!(profiles[0].name, "default");
+        assert_eq!(profiles[0].pattern, "meta.json");
+        assert_eq!(profiles[0].result_fields.describe(), "/llmResult");
+        assert_eq!(profiles[0].clipboard_format, ClipboardFormat::PlainText);
+        assert_eq!(profiles[0].keyphrases.actions()[0].keyphrase, "open browser");
+
+        assert_eq!(profiles[1].name, "other");
+        assert_eq!(profiles[1].pattern, "*.result.json");
+        assert_eq!(profiles[1].result_fields.describe(), "/segments/*/text -> /rawResult");
+        assert_eq!(profiles[1].clipboard_format, ClipboardFormat::Markdown);
+        assert_eq!(profiles[1].keyphrases.actions()[0].keyphrase, "open notes");
+        let note = crate::Meta {
+            mode_name: Some("Note".to_string()),
+            ..Default::default()
+        };
+        assert!(profiles[1].filter.check(&note, None).is_ok());
+        assert!(profiles[0].filter.is_empty());
+        assert_eq!(profiles[0].watcher_backend, WatcherBackend::Auto);
+        assert_eq!(profiles[1].watcher_backend, WatcherBackend::Poll);
+        assert_eq!(profiles[1].poll_interval, Duration::from_millis(500));
+        // A watch entry's own routes replace the global ones
+        assert_eq!(profiles[0].routes[0].name, "global");
+        assert_eq!(profiles[1].routes.len(), 1);
+        assert_eq!(profiles[1].routes[0].name, "other notes");
+
+        let other_file = profiles[1].path.join("1/take.result.json");
+        assert_eq!(profile_for_path(&profiles, &other_file).unwrap().0.name, "other");
+        assert!(profiles[1].matches_file(&other_file));
+        assert!(!profiles[0].matches_file(&other_file));
+        // Deeper than max_depth
+        assert!(!profiles[1].matches_file(&profiles[1].path.join("1/2/take.result.json")));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_profile_for_symlinked_path() {
+        let dir = tempdir().unwrap();
+        let real = dir.path().canonicalize().unwrap().join("real");
+        std::fs::create_dir_all(real.join("1")).unwrap();
+        std::os::unix::fs::symlink(&real, dir.path().join("link")).unwrap();
+        let profiles = vec![Arc::new(WatchProfile::new("real", &real))];
+
+        // Events through the symlink resolve to the path inside the watch directory
+        let (profile, path) = profile_for_path(&profiles, &dir.path().join("link/1/meta.json")).unwrap();
+        assert_eq!(profile.name, "real");
+        assert_eq!(path, real.join("1/meta.json"));
+        assert!(profile.matches_file(&path));
+    }
+
+    #[test]
+    fn test_unknown_keyphrase_set() {
+        let dir = tempdir().unwrap();
+        let state = app_state(AppConfig {
+            detect_keyphrases: Some(true),
+            watch: Some(vec![WatchConfig {
+                path: dir.path().to_string_lossy().to_string(),
+                keyphrase_set: Some("missing".to_string()),
+                ..Default::default()
+            }]),
+            ..Default::default()
+        });
+
+        assert!(resolve_watch_profiles(&state).is_err());
+    }
+
+    #[test]
+    fn test_filename_matchers() {
+        let glob = FilenameMatcher::glob("*.result.json").unwrap();
+        assert!(glob.is_match(Path::new("a/b/take.result.json")));
+        assert!(!glob.is_match(Path::new("a/meta.json")));
+
+        let path_glob = FilenameMatcher::glob("*/transcripts/*.txt").unwrap();
+        assert!(path_glob.is_match(Path::new("2024/transcripts/one.txt")));
+        assert!(!path_glob.is_match(Path::new("2024/other/one.txt")));
+        assert!(!path_glob.is_match(Path::new("a/2024/transcripts/one.txt")));
+
+        let regex = FilenameMatcher::regex(r"^transcript-\d+\.json$").unwrap();
+        assert!(regex.is_match(Path::new("x/transcript-12.json")));
+        assert!(!regex.is_match(Path::new("x/transcript-final.json")));
+
+        assert!(FilenameMatcher::regex("(unclosed").is_err());
+    }
+
+    #[test]
+    fn test_non_recursive_profile() {
+        let dir = tempdir().unwrap();
+        let state = app_state(AppConfig {
+            watch: Some(vec![WatchConfig {
+                path: dir.path().to_string_lossy().to_string(),
+                pattern: Some("*.txt".to_string()),
+                recursive: Some(false),
+                ..Default::default()
+            }]),
+            ..Default::default()
+        });
+
+        let profiles = resolve_watch_profiles(&state).unwrap();
+        assert!(matches!(profiles[0].recursive_mode(), RecursiveMode::NonRecursive));
+        assert_eq!(profiles[0].effective_max_depth(), 1);
+        assert!(profiles[0].matches_file(&profiles[0].path.join("note.txt")));
+        assert!(!profiles[0].matches_file(&profiles[0].path.join("sub/note.txt")));
+    }
+
+    #[test]
+    fn test_top_level_watch_dir_pattern() {
+        let dir = tempdir().unwrap();
+        let state = app_state(AppConfig {
+            watch_dir: Some(dir.path().to_string_lossy().to_string()),
+            regex: Some(r"^transcript-\d+\.json$".to_string()),
+            max_depth: Some(2),
+            ..Default::default()
+        });
+
+        let profiles = resolve_watch_profiles(&state).unwrap();
+        assert_eq!(profiles[0].name, "default");
+        assert!(profiles[0].matches_file(&profiles[0].path.join("1/transcript-3.json")));
+        assert!(!profiles[0].matches_file(&profiles[0].path.join("1/meta.json")));
+        assert!(!profiles[0].matches_file(&profiles[0].path.join("1/2/transcript-3.json")));
+    }
+}
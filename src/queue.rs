@@ -0,0 +1,412 @@
+use log::{debug, error, info, warn};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::VecDeque;
+use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// Default number of worker threads
+pub const DEFAULT_WORKERS: usize = 2;
+
+/// Default number of pending jobs per worker
+pub const DEFAULT_DEPTH: usize = 64;
+
+/// Default time to wait for queued jobs to finish on shutdown
+pub const DEFAULT_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// What to do when a worker's queue is full
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OverflowPolicy {
+    /// Wait until there is room (slows down event delivery)
+    Block,
+    /// Drop the job being submitted
+    DropNewest,
+    /// Drop the oldest pending job to make room
+    DropOldest,
+}
+
+/// Jobs left undone when the queue was shut down
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ShutdownReport {
+    /// Queued jobs that never started
+    pub cancelled: usize,
+    /// Jobs still running when the timeout expired
+    pub running: usize,
+}
+
+impl ShutdownReport {
+    /// True if every job finished
+    pub fn is_complete(&self) -> bool {
+        self.cancelled == 0 && self.running == 0
+    }
+}
+
+/// Pending jobs for one worker
+struct Lane<T> {
+    jobs: Mutex<VecDeque<T>>,
+    not_empty: Condvar,
+    not_full: Condvar,
+    closed: Arc<AtomicBool>,
+    /// Jobs queued or running across all lanes
+    unfinished: Arc<AtomicUsize>,
+}
+
+/// Bounded job queue served by a small pool of worker threads
+///
+/// Each job is submitted with a sink key (e.g. "clipboard"). All jobs with the same
+/// key go to the same worker, so they run one at a time in submission order.
+pub struct JobQueue<T: Send + 'static> {
+    lanes: Vec<Arc<Lane<T>>>,
+    depth: usize,
+    overflow: OverflowPolicy,
+    workers: Vec<JoinHandle<()>>,
+    closed: Arc<AtomicBool>,
+    unfinished: Arc<AtomicUsize>,
+    dropped: AtomicUsize,
+}
+
+impl<T: Send + 'static> JobQueue<T> {
+    /// Start `workers` threads that run `handler` for each job
+    pub fn new<F>(workers: usize, depth: usize, overflow: OverflowPolicy, handler: F) -> JobQueue<T>
+    where
+        F: Fn(T) + Send + Sync + 'static,
+    {
+        let handler = Arc::new(handler);
+        let workers = workers.max(1);
+        let depth = depth.max(1);
+        let closed = Arc::new(AtomicBool::new(false));
+        let unfinished = Arc::new(AtomicUsize::new(0));
+
+        let lanes: Vec<Arc<Lane<T>>> = (0..workers)
+            .map(|_| {
+                Arc::new(Lane {
+                    jobs: Mutex::new(VecDeque::with_capacity(depth)),
+                    not_empty: Condvar::new(),
+                    not_full: Condvar::new(),
+                    closed: closed.clone(),
+                    unfinished: unfinished.clone(),
+                })
+            })
+            .collect();
+
+        let handles = lanes
+            .iter()
+            .enumerate()
+            .map(|(i, lane)| {
+                let lane = lane.clone();
+                let handler = handler.clone();
+                thread::Builder::new()
+                    .name(format!("sw-catcher-worker-{}", i))
+                    .spawn(move || worker_loop(i, &lane, handler.as_ref()))
+                    .expect("failed to spawn worker thread")
+            })
+            .collect();
+
+        debug!(
+            "Started {} workers (depth {}, overflow {:?})",
+            workers, depth, overflow
+        );
+
+        JobQueue {
+            lanes,
+            depth,
+            overflow,
+            workers: handles,
+            closed,
+            unfinished,
+            dropped: AtomicUsize::new(0),
+        }
+    }
+
+    /// Submit a job, applying the overflow policy if its worker is busy
+    ///
+    /// Returns false if the job was dropped.
+    pub fn submit(&self, sink: &str, job: T) -> bool {
+        self.push(sink, job, self.overflow)
+    }
+
+    /// Submit a job, waiting for room regardless of the overflow policy
+    pub fn submit_wait(&self, sink: &str, job: T) {
+        self.push(sink, job, OverflowPolicy::Block);
+    }
+
+    /// Number of jobs waiting to be picked up by a worker
+    pub fn pending(&self) -> usize {
+        self.lanes.iter().map(|lane| lane.jobs.lock().unwrap().len()).sum()
+    }
+
+    /// True if every job submitted so far has finished, none is queued or running
+    pub fn is_idle(&self) -> bool {
+        self.unfinished.load(Ordering::SeqCst) == 0
+    }
+
+    /// Number of jobs dropped because their worker's queue was full or cancelled at shutdown
+    pub fn dropped(&self) -> usize {
+        self.dropped.load(Ordering::SeqCst)
+    }
+
+    /// Number of worker threads
+    pub fn worker_count(&self) -> usize {
+        self.workers.len()
+    }
+
+    /// Stop accepting jobs and wait up to `timeout` for the queued ones to finish
+    ///
+    /// Jobs still waiting when the timeout expires are cancelled and counted as dropped.
+    /// A job that is already running can't be interrupted; its worker is left to finish
+    /// on its own. Returns how many jobs were cancelled and how many were still running.
+    pub fn shutdown(&self, timeout: Duration) -> ShutdownReport {
+        self.closed.store(true, Ordering::SeqCst);
+        for lane in &self.lanes {
+            let _jobs = lane.jobs.lock().unwrap();
+            lane.not_empty.notify_all();
+        }
+
+        let pending = self.pending();
+        if pending > 0 {
+            info!("Waiting up to {:?} for {} queued jobs to finish", timeout, pending);
+        }
+
+        let deadline = Instant::now() + timeout;
+        while !self.workers.iter().all(|worker| worker.is_finished()) && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(20));
+        }
+
+        let mut cancelled = 0;
+        for lane in &self.lanes {
+            let mut jobs = lane.jobs.lock().unwrap();
+            cancelled += jobs.len();
+            self.dropped.fetch_add(jobs.len(), Ordering::SeqCst);
+            self.unfinished.fetch_sub(jobs.len(), Ordering::SeqCst);
+            jobs.clear();
+            lane.not_full.notify_all();
+        }
+
+        // With the queued jobs cancelled, the unfinished ones are those still running
+        let report = ShutdownReport {
+            cancelled,
+            running: self.unfinished.load(Ordering::SeqCst),
+        };
+        if !report.is_complete() {
+            warn!(
+                "Shutdown timed out: cancelled {} queued jobs, {} still running",
+                report.cancelled, report.running
+            );
+        }
+
+        report
+    }
+
+    fn push(&self, sink: &str, job: T, overflow: OverflowPolicy) -> bool {
+        if self.closed.load(Ordering::SeqCst) {
+            debug!("Queue is shut down, ignoring job for sink '{}'", sink);
+            return false;
+        }
+
+        let lane = &self.lanes[self.lane_for(sink)];
+        let mut jobs = lane.jobs.lock().unwrap();
+
+        if jobs.len() >= self.depth {
+            match overflow {
+                OverflowPolicy::Block => {
+                    debug!("Queue for sink '{}' is full, waiting", sink);
+                    jobs = lane
+                        .not_full
+                        .wait_while(jobs, |jobs| jobs.len() >= self.depth)
+                        .unwrap();
+                    if self.closed.load(Ordering::SeqCst) {
+                        return false;
+                    }
+                }
+                OverflowPolicy::DropNewest => {
+                    warn!("Queue for sink '{}' is full, dropping new job", sink);
+                    self.dropped.fetch_add(1, Ordering::SeqCst);
+                    return false;
+                }
+                OverflowPolicy::DropOldest => {
+                    warn!("Queue for sink '{}' is full, dropping oldest job", sink);
+                    jobs.pop_front();
+                    self.dropped.fetch_add(1, Ordering::SeqCst);
+                    self.unfinished.fetch_sub(1, Ordering::SeqCst);
+                }
+            }
+        }
+
+        jobs.push_back(job);
+        self.unfinished.fetch_add(1, Ordering::SeqCst);
+        lane.not_empty.notify_one();
+        true
+    }
+
+    fn lane_for(&self, sink: &str) -> usize {
+        let mut hasher = DefaultHasher::new();
+        sink.hash(&mut hasher);
+        (hasher.finish() % self.lanes.len() as u64) as usize
+    }
+}
+
+/// Take jobs from a lane and run them, one at a time, until the queue is shut down and empty
+fn worker_loop<T>(index: usize, lane: &Lane<T>, handler: &(dyn Fn(T) + Send + Sync)) {
+    loop {
+        let job = {
+            let jobs = lane.jobs.lock().unwrap();
+            let mut jobs = lane
+                .not_empty
+                .wait_while(jobs, |jobs| jobs.is_empty() && !lane.closed.load(Ordering::SeqCst))
+                .unwrap();
+            let job = jobs.pop_front();
+            lane.not_full.notify_one();
+            job
+        };
+
+        match job {
+            Some(job) => {
+                // Keep the worker alive if a single job panics
+                if std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| handler(job))).is_err() {
+                    error!("Worker {} recovered from a panic while processing a job", index);
+                }
+                lane.unfinished.fetch_sub(1, Ordering::SeqCst);
+            }
+            None => break,
+        }
+    }
+    debug!("Worker {} stopped", index);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+    use std::time::Duration;
+
+    #[test]
+    fn test_jobs_with_same_sink_keep_order() {
+        let (tx, rx) = mpsc::channel();
+        let tx = Mutex::new(tx);
+        let queue = JobQueue::new(4, 16, OverflowPolicy::Block, move |n: u32| {
+            tx.lock().unwrap().send(n).unwrap();
+        });
+
+        for n in 0..10 {
+            assert!(queue.submit("clipboard", n));
+        }
+
+        let received: Vec<u32> = (0..10)
+            .map(|_| rx.recv_timeout(Duration::from_secs(5)).unwrap())
+            .collect();
+        assert_eq!(received, (0..10).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_overflow_policies() {
+        // A job that blocks the single worker until released
+        let (release_tx, release_rx) = mpsc::channel::<()>();
+        let release_rx = Mutex::new(release_rx);
+        let (done_tx, done_rx) = mpsc::channel();
+        let done_tx = Mutex::new(done_tx);
+
+        let queue = JobQueue::new(1, 2, OverflowPolicy::DropNewest, move |n: u32| {
+            if n == 0 {
+                release_rx.lock().unwrap().recv().unwrap();
+            }
+            done_tx.lock().unwrap().send(n).unwrap();
+        });
+
+        assert!(queue.submit("sink", 0));
+        // Wait until the worker has taken job 0
+        while queue.pending() > 0 {
+            thread::sleep(Duration::from_millis(5));
+        }
+
+        assert!(queue.submit("sink", 1));
+        assert!(queue.submit("sink", 2));
+        assert!(!queue.submit("sink", 3)); // Full, dropped
+        assert_eq!(queue.dropped(), 1);
+        assert!(!queue.is_idle());
+
+        release_tx.send(()).unwrap();
+        let received: Vec<u32> = (0..3)
+            .map(|_| done_rx.recv_timeout(Duration::from_secs(5)).unwrap())
+            .collect();
+        assert_eq!(received, vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn test_drop_oldest() {
+        let (release_tx, release_rx) = mpsc::channel::<()>();
+        let release_rx = Mutex::new(release_rx);
+        let (done_tx, done_rx) = mpsc::channel();
+        let done_tx = Mutex::new(done_tx);
+
+        let queue = JobQueue::new(1, 2, OverflowPolicy::DropOldest, move |n: u32| {
+            if n == 0 {
+                release_rx.lock().unwrap().recv().unwrap();
+            }
+            done_tx.lock().unwrap().send(n).unwrap();
+        });
+
+        queue.submit("sink", 0);
+        while queue.pending() > 0 {
+            thread::sleep(Duration::from_millis(5));
+        }
+
+        queue.submit("sink", 1);
+        queue.submit("sink", 2);
+        assert!(queue.submit("sink", 3)); // Pushes out job 1
+
+        release_tx.send(()).unwrap();
+        let received: Vec<u32> = (0..3)
+            .map(|_| done_rx.recv_timeout(Duration::from_secs(5)).unwrap())
+            .collect();
+        assert_eq!(received, vec![0, 2, 3]);
+    }
+
+    #[test]
+    fn test_shutdown_drains_queued_jobs() {
+        let (tx, rx) = mpsc::channel();
+        let tx = Mutex::new(tx);
+        let queue = JobQueue::new(2, 16, OverflowPolicy::Block, move |n: u32| {
+            thread::sleep(Duration::from_millis(10));
+            tx.lock().unwrap().send(n).unwrap();
+        });
+
+        for n in 0..5 {
+            queue.submit("clipboard", n);
+        }
+
+        assert!(queue.shutdown(Duration::from_secs(5)).is_complete());
+        assert_eq!(queue.dropped(), 0);
+        assert!(queue.is_idle());
+        assert_eq!(rx.try_iter().collect::<Vec<_>>(), vec![0, 1, 2, 3, 4]);
+        assert!(!queue.submit("clipboard", 5));
+    }
+
+    #[test]
+    fn test_shutdown_cancels_after_timeout() {
+        let (release_tx, release_rx) = mpsc::channel::<()>();
+        let release_rx = Mutex::new(release_rx);
+
+        let queue = JobQueue::new(1, 8, OverflowPolicy::Block, move |n: u32| {
+            if n == 0 {
+                release_rx.lock().unwrap().recv().unwrap();
+            }
+        });
+
+        queue.submit("sink", 0);
+        while queue.pending() > 0 {
+            thread::sleep(Duration::from_millis(5));
+        }
+        queue.submit("sink", 1);
+        queue.submit("sink", 2);
+
+        // Job 0 is stuck, so jobs 1 and 2 never start
+        let report = queue.shutdown(Duration::from_millis(100));
+        assert_eq!(report, ShutdownReport { cancelled: 2, running: 1 });
+        assert_eq!(queue.dropped(), 2);
+        assert_eq!(queue.pending(), 0);
+        assert!(!queue.is_idle());
+        release_tx.send(()).unwrap();
+    }
+}
@@ -0,0 +1,315 @@
+use crate::config::{load_config_with_opts, parse_keyphrase_matching_strategy, parse_normalization, parse_punctuation_handling, AppState, Opts};
+use crate::keyphrase::{KeyphraseMatchingStrategy, KeyphraseProcessingOptions};
+use crate::ledger::content_hash;
+use crate::profile::{resolve_watch_profiles, WatchProfile};
+use log::{debug, error, info, warn};
+use notify::{Config, EventKind, RecommendedWatcher, RecursiveMode, Result as NotifyResult, Watcher};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Arc, RwLock};
+use std::thread;
+use std::time::Duration;
+
+/// How long to wait for an editor to finish writing the config file
+const RELOAD_SETTLE_TIME: Duration = Duration::from_millis(300);
+
+/// Settings used while processing files, replaced as a whole when config.toml changes
+pub struct Runtime {
+    pub app_state: Arc<AppState>,
+    pub profiles: Vec<Arc<WatchProfile>>,
+    pub keyphrase_options: KeyphraseProcessingOptions,
+    /// Command line options, applied again on every reload
+    pub opts: Opts,
+}
+
+/// The current runtime settings, shared between the watchers and workers
+pub type SharedRuntime = Arc<RwLock<Arc<Runtime>>>;
+
+impl Runtime {
+    /// Resolve the watch profiles and keyphrase options for an app state loaded with `opts`
+    pub fn new(app_state: AppState, opts: Opts) -> Result<Runtime, String> {
+        let profiles = resolve_watch_profiles(&app_state)?;
+        let keyphrase_options = parse_keyphrase_options(&app_state);
+
+        Ok(Runtime {
+            app_state: Arc::new(app_state),
+            profiles,
+            keyphrase_options,
+            opts,
+        })
+    }
+
+    /// Build the runtime for a reloaded config, keeping the directories being watched
+    ///
+    /// Watch directories and backends are fixed at startup, so only the settings used
+    /// while processing (keyphrases, clipboard format, text cleaning, routes, filter, result
+    /// fields and file stability) are taken from the new config.
+    pub fn reloaded(&self, app_state: AppState) -> Result<Runtime, String> {
+        let new = Runtime::new(app_state, self.opts.clone())?;
+
+        let profiles = self
+            .profiles
+            .iter()
+            .map(|current| match new.profiles.iter().find(|p| p.name == current.name) {
+                Some(updated) => Arc::new(WatchProfile {
+                    keyphrases: updated.keyphrases.clone(),
+                    clipboard_format: updated.clipboard_format.clone(),
+                    routes: updated.routes.clone(),
+                    filter: updated.filter.clone(),
+                    result_fields: updated.result_fields.clone(),
+                    stability: updated.stability.clone(),
+                    ..(**current).clone()
+                }),
+                None => {
+                    warn!(
+                        "Watch '{}' was removed from the config, it stays active until restart",
+                        current.name
+                    );
+                    current.clone()
+                }
+            })
+            .collect();
+
+        for added in new.profiles.iter().filter(|p| !self.profiles.iter().any(|c| c.name == p.name)) {
+            warn!("Watch '{}' was added to the config, restart to start watching it", added.name);
+        }
+
+        Ok(Runtime {
+            profiles,
+            ..new
+        })
+    }
+}
+
+/// Get the current runtime settings
+pub fn current_runtime(runtime: &SharedRuntime) -> Arc<Runtime> {
+    runtime.read().unwrap().clone()
+}
+
+/// Parse keyphrase processing options from the application configuration
+pub fn parse_keyphrase_options(app_state: &AppState) -> KeyphraseProcessingOptions {
+    // Log keyphrase configuration
+    if app_state.config.detect_keyphrases.unwrap_or(false) {
+        info!("Keyphrase detection enabled");
+    } else {
+        debug!("Keyphrase detection disabled");
+    }
+
+    // Parse keyphrase processing options
+    let mut options = KeyphraseProcessingOptions::default();
+
+    if let Some(keyphrase_settings) = &app_state.config.keyphrase_settings {
+        if let Some(strategy) = &keyphrase_settings.matching_strategy {
+            options.matching_strategy = parse_keyphrase_matching_strategy(strategy);
+            debug!("Using keyphrase matching strategy: {:?}", options.matching_strategy);
+        }
+
+        if let Some(handling) = &keyphrase_settings.punctuation_handling {
+            options.punctuation_handling = parse_punctuation_handling(handling);
+            debug!("Using punctuation handling: {:?}", options.punctuation_handling);
+        }
+
+        if let Some(threshold) = keyphrase_settings.fuzzy_threshold {
+            options.fuzzy.max_distance = threshold;
+        }
+        options.fuzzy.phonetic = keyphrase_settings.phonetic.unwrap_or(false);
+        if let Some(min_confidence) = keyphrase_settings.min_confidence {
+            options.min_confidence = min_confidence.clamp(0.0, 1.0);
+        }
+        if let Some(normalization) = &keyphrase_settings.normalization {
+            options.normalization = parse_normalization(normalization);
+            debug!("Using Unicode normalization: {:?}", options.normalization);
+        }
+        options.escape_word = keyphrase_settings
+            .escape_word
+            .as_ref()
+            .map(|word| word.trim().to_string())
+            .filter(|word| !word.is_empty());
+        if let Some(escape_word) = &options.escape_word {
+            debug!("Keyphrases after \"{}\" are dictated as text", escape_word);
+        }
+        if options.matching_strategy == KeyphraseMatchingStrategy::Fuzzy {
+            debug!("Fuzzy matching: {:?}, min confidence {}", options.fuzzy, options.min_confidence);
+        }
+    }
+
+    options
+}
+
+/// Watch the config file and swap in the new settings whenever it changes
+///
+/// The directory containing the file is watched rather than the file itself, because
+/// many editors save by replacing the file. Dropping the returned watcher stops reloading.
+pub fn watch_config_file(config_path: &Path, runtime: SharedRuntime) -> NotifyResult<RecommendedWatcher> {
+    let config_path = std::path::absolute(config_path).map_err(notify::Error::io)?;
+    let config_dir = config_path
+        .parent()
+        .map(Path::to_path_buf)
+        .unwrap_or_else(|| PathBuf::from("."));
+    let file_name = config_path.file_name().map(|name| name.to_os_string());
+
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = RecommendedWatcher::new(
+        move |res: NotifyResult<notify::Event>| {
+            if let Ok(event) = res {
+                let is_config = event.paths.iter().any(|p| p.file_name() == file_name.as_deref());
+                if is_config && matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) {
+                    let _ = tx.send(());
+                }
+            }
+        },
+        Config::default(),
+    )?;
+    watcher.watch(&config_dir, RecursiveMode::NonRecursive)?;
+    info!("Watching {} for configuration changes", config_path.display());
+
+    thread::Builder::new()
+        .name("sw-catcher-reload".to_string())
+        .spawn(move || {
+            let mut last_hash = std::fs::read(&config_path).ok().map(|content| content_hash(&content));
+
+            // The channel closes when the watcher is dropped
+            while rx.recv().is_ok() {
+                // Let a burst of write events settle before reading the file
+                loop {
+                    match rx.recv_timeout(RELOAD_SETTLE_TIME) {
+                        Ok(()) => continue,
+                        Err(RecvTimeoutError::Timeout) => break,
+                        Err(RecvTimeoutError::Disconnected) => return,
+                    }
+                }
+
+                let hash = std::fs::read(&config_path).ok().map(|content| content_hash(&content));
+                if hash.is_none() || hash == last_hash {
+                    continue;
+                }
+                last_hash = hash;
+                reload_config(&runtime);
+            }
+        })
+        .map_err(notify::Error::io)?;
+
+    Ok(watcher)
+}
+
+/// Re-read the configuration and swap it in, keeping the current settings if it is invalid
+fn reload_config(runtime: &SharedRuntime) {
+    info!("Configuration file changed, reloading");
+
+    // Apply the same command line options as at startup, without parsing them again
+    let current = current_runtime(runtime);
+    let reloaded = load_config_with_opts(current.opts.clone())
+        .map_err(|e| e.to_string())
+        .and_then(|app_state| current.reloaded(app_state));
+
+    match reloaded {
+        Ok(new) => {
+            let keyphrases: usize = new.profiles.iter().map(|p| p.keyphrases.len()).sum();
+            *runtime.write().unwrap() = Arc::new(new);
+            info!("Configuration reloaded ({} keyphrases)", keyphrases);
+        }
+        Err(e) => {
+            error!("Invalid configuration, keeping the previous settings: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clipboard::ClipboardFormat;
+    use crate::config::{AppConfig, StringOrList, WatchConfig};
+    use crate::meta::Meta;
+    use std::collections::HashMap;
+    use tempfile::tempdir;
+
+    fn app_state(watch: Vec<WatchConfig>, keyphrases: &[(&str, &str)]) -> AppState {
+        AppState {
+            config: AppConfig {
+                detect_keyphrases: Some(true),
+                keyphrases: Some(
+                    keyphrases
+                        .iter()
+                        .map(|(k, v)| (k.to_string(), (*v).into()))
+                        .collect::<HashMap<_, _>>(),
+                ),
+                watch: Some(watch),
+                ..Default::default()
+            },
+            clipboard_format: ClipboardFormat::PlainText,
+            dry_run: true,
+            disable_logs: true,
+        }
+    }
+
+    #[test]
+    fn test_reloaded_swaps_keyphrases_and_keeps_watches() {
+        let temp_dir = tempdir().unwrap();
+        let path = temp_dir.path().to_string_lossy().to_string();
+        let watch = |name: &str| WatchConfig {
+            name: Some(name.to_string()),
+            path: path.clone(),
+            ..Default::default()
+        };
+
+        let runtime = Runtime::new(app_state(vec![watch("notes")], &[("open notes", "Notes")]), Opts::default()).unwrap();
+        let reloaded = runtime
+            .reloaded(app_state(
+                vec![watch("notes"), watch("extra")],
+                &[("open mail", "Mail"), ("open safari", "Safari")],
+            ))
+            .unwrap();
+
+        assert_eq!(reloaded.profiles.len(), 1);
+        assert_eq!(reloaded.profiles[0].name, "notes");
+        assert_eq!(reloaded.profiles[0].keyphrases.len(), 2);
+
+        // Command line options carry over to the reloaded settings
+        let opts = Opts {
+            dry_run: true,
+            ..Default::default()
+        };
+        let runtime = Runtime::new(app_state(vec![watch("notes")], &[]), opts).unwrap();
+        assert!(runtime.reloaded(app_state(vec![watch("notes")], &[])).unwrap().opts.dry_run);
+    }
+
+    #[test]
+    fn test_reloaded_swaps_filter_and_result_fields() {
+        let temp_dir = tempdir().unwrap();
+        let watch = |mode_name: Option<&str>, fields: Option<&str>| WatchConfig {
+            name: Some("notes".to_string()),
+            path: temp_dir.path().to_string_lossy().to_string(),
+            mode_name: mode_name.map(str::to_string),
+            result_field_preference: fields.map(|f| StringOrList::One(f.to_string())),
+            ..Default::default()
+        };
+
+        let runtime = Runtime::new(app_state(vec![watch(None, None)], &[]), Opts::default()).unwrap();
+        assert!(runtime.profiles[0].filter.is_empty());
+
+        let reloaded = runtime.reloaded(app_state(vec![watch(Some("Email"), Some("/text"))], &[])).unwrap();
+        let meta = Meta {
+            mode_name: Some("Note".to_string()),
+            ..Default::default()
+        };
+        assert!(reloaded.profiles[0].filter.check(&meta, None).is_err());
+        assert_eq!(reloaded.profiles[0].result_fields.describe(), "/text");
+    }
+
+    #[test]
+    fn test_reloaded_rejects_invalid_config() {
+        let temp_dir = tempdir().unwrap();
+        let watch = WatchConfig {
+            name: Some("notes".to_string()),
+            path: temp_dir.path().to_string_lossy().to_string(),
+            ..Default::default()
+        };
+        let runtime = Runtime::new(app_state(vec![watch.clone()], &[]), Opts::default()).unwrap();
+
+        let invalid = WatchConfig {
+            keyphrase_set: Some("missing".to_string()),
+            ..watch
+        };
+        assert!(runtime.reloaded(app_state(vec![invalid], &[])).is_err());
+    }
+}
@@ -0,0 +1,352 @@
+use crate::clipboard::{ensure_clipboard_content_with_monitoring, parse_clipboard_format, truncate, ClipboardFormat};
+use crate::config::{configured_matching_strategy, configured_normalization, expand_home, AppConfig, RouteConfig, TextCleaningOptions};
+use crate::filter::Filter;
+use crate::keyphrase::{parse_keyphrase_map, KeyphraseSet};
+use crate::ledger::ProcessOutcome;
+use crate::meta::Meta;
+use log::info;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Where a route sends its text
+#[derive(Debug, Clone, PartialEq)]
+pub enum Sink {
+    /// Copy to the clipboard
+    Clipboard(ClipboardFormat),
+    /// Append to a file
+    AppendFile(PathBuf),
+    /// Write a new file in a directory
+    NoteDir(PathBuf),
+}
+
+impl Sink {
+    /// Stable name for the sink, used to remember which sinks a file already went out to
+    pub fn id(&self) -> String {
+        match self {
+            Sink::Clipboard(_) => "clipboard".to_string(),
+            Sink::AppendFile(file) => format!("append_file:{}", file.display()),
+            Sink::NoteDir(dir) => format!("note_dir:{}", dir.display()),
+        }
+    }
+}
+
+/// A resolved `[[route]]` entry
+#[derive(Debug, Clone)]
+pub struct Route {
+    pub name: String,
+    pub filter: Filter,
+    /// Keyphrases for this route, or `None` to use the watch profile's
+    pub keyphrases: Option<KeyphraseSet>,
+    /// Cleaning options for this route, or `None` to use `[text_cleaning]`
+    pub cleaning: Option<TextCleaningOptions>,
+    pub sinks: Vec<Sink>,
+    pub fallthrough: bool,
+}
+
+/// Resolve `[[route]]` entries, using `clipboard_format` when a route doesn't set one
+pub fn resolve_routes(
+    routes: &[RouteConfig],
+    config: &AppConfig,
+    clipboard_format: &ClipboardFormat,
+) -> Result<Vec<Route>, String> {
+    routes
+        .iter()
+        .enumerate()
+        .map(|(index, route)| resolve_route(index, route, config, clipboard_format))
+        .collect()
+}
+
+fn resolve_route(
+    index: usize,
+    route: &RouteConfig,
+    config: &AppConfig,
+    clipboard_format: &ClipboardFormat,
+) -> Result<Route, String> {
+    let name = route.name.clone().unwrap_or_else(|| format!("route {}", index + 1));
+
+    let filter = Filter::from_route_config(route.filter.as_ref(), route.mode.as_deref())
+        .map_err(|e| format!("Route '{}': {}", name, e))?;
+
+    let keyphrases = match &route.keyphrase_set {
+        Some(_) if !config.detect_keyphrases.unwrap_or(false) => Some(KeyphraseSet::default()),
+        Some(set_name) => {
+            let set = config
+                .keyphrase_sets
+                .as_ref()
+                .and_then(|sets| sets.get(set_name))
+                .ok_or_else(|| format!("Route '{}' uses unknown keyphrase set '{}'", name, set_name))?;
+            let strategy = configured_matching_strategy(config);
+            let keyphrases = parse_keyphrase_map(set, &strategy).map_err(|e| format!("Route '{}': {}", name, e))?;
+            Some(KeyphraseSet::new(keyphrases, &strategy, configured_normalization(config)))
+        }
+        None => None,
+    };
+
+    let cleaning = match &route.cleaning {
+        Some(profile_name) => Some(
+            config
+                .cleaning_profiles
+                .as_ref()
+                .and_then(|profiles| profiles.get(profile_name))
+                .cloned()
+                .ok_or_else(|| format!("Route '{}' uses unknown cleaning profile '{}'", name, profile_name))?,
+        ),
+        None => None,
+    };
+
+    let mut sinks = Vec::new();
+    if route.clipboard.unwrap_or(true) {
+        let format = match &route.clipboard_format {
+            Some(format) => parse_clipboard_format(format),
+            None => clipboard_format.clone(),
+        };
+        sinks.push(Sink::Clipboard(format));
+    }
+    if let Some(file) = &route.append_file {
+        sinks.push(Sink::AppendFile(expand_home(file)));
+    }
+    if let Some(dir) = &route.note_dir {
+        sinks.push(Sink::NoteDir(expand_home(dir)));
+    }
+
+    Ok(Route {
+        name,
+        filter,
+        keyphrases,
+        cleaning,
+        sinks,
+        fallthrough: route.fallthrough,
+    })
+}
+
+/// Pick the routes for a recording: the first match, plus any after it while routes fall through
+pub fn matching_routes<'a>(routes: &'a [Route], meta: &Meta, text: &str) -> Vec<&'a Route> {
+    let mut matched = Vec::new();
+    for route in routes {
+        if route.filter.check(meta, Some(text)).is_ok() {
+            matched.push(route);
+            if !route.fallthrough {
+                break;
+            }
+        }
+    }
+    matched
+}
+
+/// Send text to a sink
+///
+/// `clipboard_enabled` is the global clipboard switch; file sinks are skipped in dry-run mode.
+pub fn deliver(
+    sink: &Sink,
+    text: &str,
+    source: &Path,
+    meta: &Meta,
+    clipboard_enabled: bool,
+    dry_run: bool,
+) -> ProcessOutcome {
+    match sink {
+        Sink::Clipboard(_) if !clipboard_enabled => {
+            info!("Clipboard operations disabled, not copying to clipboard: {}", truncate(text, 60));
+            ProcessOutcome::ClipboardDisabled
+        }
+        Sink::Clipboard(format) => match ensure_clipboard_content_with_monitoring(text, format) {
+            Ok(_) => {
+                info!("Copied to clipboard: {}", truncate(text, 60));
+                ProcessOutcome::Copied
+            }
+            Err(e) => {
+                log::error!("Clipboard error: {}", e);
+                ProcessOutcome::ClipboardFailed
+            }
+        },
+        Sink::AppendFile(file) => write_file(file, text, true, dry_run),
+        Sink::NoteDir(dir) => write_file(&dir.join(note_file_name(source, meta)), text, false, dry_run),
+    }
+}
+
+/// Whether a sink's outcome means the text went out (or was deliberately not copied)
+pub fn is_delivered(outcome: ProcessOutcome) -> bool {
+    !matches!(outcome, ProcessOutcome::ClipboardFailed | ProcessOutcome::DeliveryFailed)
+}
+
+/// Combine the outcomes of several sinks into the one recorded in the ledger
+///
+/// Failures win, so a file that only partly went out is retried later.
+pub fn combine_outcomes(outcomes: &[ProcessOutcome]) -> ProcessOutcome {
+    const PRIORITY: &[ProcessOutcome] = &[
+        ProcessOutcome::ClipboardFailed,
+        ProcessOutcome::DeliveryFailed,
+        ProcessOutcome::Copied,
+        ProcessOutcome::ClipboardDisabled,
+    ];
+
+    PRIORITY
+        .iter()
+        .find(|outcome| outcomes.contains(outcome))
+        .copied()
+        .unwrap_or(ProcessOutcome::Delivered)
+}
+
+fn write_file(path: &Path, text: &str, append: bool, dry_run: bool) -> ProcessOutcome {
+    if dry_run {
+        info!("[DRY RUN] Would write result to {}", path.display());
+        return ProcessOutcome::Delivered;
+    }
+
+    let result = path
+        .parent()
+        .map_or(Ok(()), fs::create_dir_all)
+        .and_then(|_| {
+            if append {
+                let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+                writeln!(file, "{}\n", text)
+            } else {
+                fs::write(path, format!("{}\n", text))
+            }
+        });
+
+    match result {
+        Ok(()) => {
+            info!("Wrote result to {}", path.display());
+            ProcessOutcome::Delivered
+        }
+        Err(e) => {
+            log::error!("Couldn't write result to {}: {}", path.display(), e);
+            ProcessOutcome::DeliveryFailed
+        }
+    }
+}
+
+/// Name for a note file: the recording's datetime, or its folder name, or the current time
+fn note_file_name(source: &Path, meta: &Meta) -> String {
+    let stem = meta
+        .datetime
+        .clone()
+        .or_else(|| {
+            source
+                .parent()
+                .and_then(|dir| dir.file_name())
+                .map(|name| name.to_string_lossy().to_string())
+        })
+        .unwrap_or_else(|| {
+            let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+            now.as_secs().to_string()
+        });
+
+    let safe: String = stem
+        .chars()
+        .map(|c| if c.is_alphanumeric() || c == '-' || c == '_' { c } else { '-' })
+        .collect();
+    format!("{}.md", safe)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use tempfile::tempdir;
+
+    fn meta(mode: &str) -> Meta {
+        Meta {
+            mode_name: Some(mode.to_string()),
+            datetime: Some("2024-05-01T09:00:00".to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_resolve_and_match_routes() {
+        let config = AppConfig {
+            route: Some(vec![
+                RouteConfig {
+                    name: Some("email".to_string()),
+                    mode: Some("Email".to_string()),
+                    clipboard_format: Some("markdown".to_string()),
+                    append_file: Some("/tmp/emails.md".to_string()),
+                    fallthrough: true,
+                    ..Default::default()
+                },
+                RouteConfig {
+                    name: Some("everything".to_string()),
+                    clipboard: Some(false),
+                    cleaning: Some("prose".to_string()),
+                    ..Default::default()
+                },
+                RouteConfig {
+                    name: Some("never".to_string()),
+                    ..Default::default()
+                },
+            ]),
+            cleaning_profiles: Some(HashMap::from([(
+                "prose".to_string(),
+                TextCleaningOptions {
+                    trim_whitespace: true,
+                    normalize_newlines: false,
+                    remove_extra_spaces: false,
+                    capitalize_sentences: true,
+                },
+            )])),
+            ..Default::default()
+        };
+
+        let routes = resolve_routes(config.route.as_deref().unwrap(), &config, &ClipboardFormat::PlainText).unwrap();
+        assert_eq!(
+            routes[0].sinks,
+            vec![
+                Sink::Clipboard(ClipboardFormat::Markdown),
+                Sink::AppendFile(PathBuf::from("/tmp/emails.md"))
+            ]
+        );
+        assert!(routes[1].sinks.is_empty());
+        assert!(routes[1].cleaning.is_some());
+
+        let names = |mode: &str| -> Vec<String> {
+            matching_routes(&routes, &meta(mode), "text").iter().map(|r| r.name.clone()).collect()
+        };
+        // The email route falls through to the catch-all, which stops the search
+        assert_eq!(names("Email"), vec!["email", "everything"]);
+        assert_eq!(names("Note"), vec!["everything"]);
+    }
+
+    #[test]
+    fn test_unknown_cleaning_profile() {
+        let config = AppConfig {
+            route: Some(vec![RouteConfig {
+                cleaning: Some("missing".to_string()),
+                ..Default::default()
+            }]),
+            ..Default::default()
+        };
+        let err = resolve_routes(config.route.as_deref().unwrap(), &config, &ClipboardFormat::PlainText).unwrap_err();
+        assert!(err.contains("route 1"));
+    }
+
+    #[test]
+    fn test_file_sinks() {
+        let temp_dir = tempdir().unwrap();
+        let source = temp_dir.path().join("1714554000").join("meta.json");
+        let log_file = temp_dir.path().join("log.md");
+
+        for text in ["First", "Second"] {
+            let sink = Sink::AppendFile(log_file.clone());
+            assert_eq!(deliver(&sink, text, &source, &meta("Email"), false, false), ProcessOutcome::Delivered);
+        }
+        assert_eq!(fs::read_to_string(&log_file).unwrap(), "First\n\nSecond\n\n");
+
+        let notes = temp_dir.path().join("notes");
+        deliver(&Sink::NoteDir(notes.clone()), "A note", &source, &meta("Note"), false, false);
+        assert_eq!(fs::read_to_string(notes.join("2024-05-01T09-00-00.md")).unwrap(), "A note\n");
+
+        assert_eq!(
+            combine_outcomes(&[ProcessOutcome::Delivered, ProcessOutcome::Copied]),
+            ProcessOutcome::Copied
+        );
+        assert_eq!(
+            combine_outcomes(&[ProcessOutcome::Copied, ProcessOutcome::DeliveryFailed]),
+            ProcessOutcome::DeliveryFailed
+        );
+        assert_eq!(combine_outcomes(&[]), ProcessOutcome::Delivered);
+    }
+}
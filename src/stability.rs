@@ -0,0 +1,164 @@
+use crate::config::FileStabilityConfig;
+use crate::meta::is_json_path;
+use log::debug;
+use serde_json::Value;
+use std::fs;
+use std::path::Path;
+use std::thread::sleep;
+use std::time::{Duration, Instant, SystemTime};
+
+/// Settings for deciding when a file is complete, and for retrying if it still can't be read
+#[derive(Debug, Clone)]
+pub struct StabilityOptions {
+    pub stable_for: Duration,
+    pub check_interval: Duration,
+    pub timeout: Duration,
+    pub sentinel: Option<String>,
+    pub required_key: Option<String>,
+    pub max_retries: u32,
+    pub retry_delay: Duration,
+}
+
+impl Default for StabilityOptions {
+    fn default() -> Self {
+        StabilityOptions {
+            stable_for: Duration::from_millis(250),
+            check_interval: Duration::from_millis(50),
+            timeout: Duration::from_secs(10),
+            sentinel: None,
+            required_key: None,
+            max_retries: 5,
+            retry_delay: Duration::from_millis(500),
+        }
+    }
+}
+
+impl StabilityOptions {
+    /// Build the options from the `[file_stability]` table, using defaults for anything unset
+    pub fn from_config(config: Option<&FileStabilityConfig>) -> StabilityOptions {
+        let defaults = StabilityOptions::default();
+        let config = match config {
+            Some(config) => config,
+            None => return defaults,
+        };
+        let millis = |value: Option<u64>, default: Duration| value.map(Duration::from_millis).unwrap_or(default);
+
+        StabilityOptions {
+            stable_for: millis(config.stable_ms, defaults.stable_for),
+            check_interval: millis(config.check_interval_ms, defaults.check_interval),
+            timeout: millis(config.timeout_ms, defaults.timeout),
+            sentinel: config.sentinel.clone(),
+            required_key: config.required_key.clone(),
+            max_retries: config.max_retries.unwrap_or(defaults.max_retries).max(1),
+            retry_delay: millis(config.retry_delay_ms, defaults.retry_delay),
+        }
+    }
+}
+
+/// Wait until a file looks completely written
+///
+/// The file's size and mtime must stay unchanged for `stable_for`, the sentinel
+/// sibling (if any) must exist, and the required JSON key (if any) must be present.
+/// Returns false if that doesn't happen within the timeout.
+pub fn wait_for_complete_file(path: &Path, options: &StabilityOptions) -> bool {
+    let started = Instant::now();
+    let deadline = started + options.timeout;
+    let mut last_seen: Option<(u64, Option<SystemTime>)> = None;
+    let mut unchanged_since = Instant::now();
+
+    loop {
+        let now = Instant::now();
+        let current = fs::metadata(path).ok().map(|m| (m.len(), m.modified().ok()));
+        if current.is_none() || current != last_seen {
+            last_seen = current;
+            unchanged_since = now;
+        }
+
+        let stable = last_seen.is_some() && now.duration_since(unchanged_since) >= options.stable_for;
+        if stable && sentinel_present(path, options) && has_required_key(path, options) {
+            debug!("{} is complete after {:?}", path.display(), started.elapsed());
+            return true;
+        }
+
+        if now >= deadline {
+            return false;
+        }
+        sleep(options.check_interval);
+    }
+}
+
+/// Check for the sentinel file next to the watched file
+fn sentinel_present(path: &Path, options: &StabilityOptions) -> bool {
+    match &options.sentinel {
+        Some(sentinel) => path.with_file_name(sentinel).exists(),
+        None => true,
+    }
+}
+
+/// Check that a JSON file has the required top-level key with a non-null value
+fn has_required_key(path: &Path, options: &StabilityOptions) -> bool {
+    let key = match &options.required_key {
+        Some(key) => key,
+        None => return true,
+    };
+
+    if !is_json_path(path) {
+        return true;
+    }
+
+    fs::read_to_string(path)
+        .ok()
+        .and_then(|txt| serde_json::from_str::<Value>(&txt).ok())
+        .and_then(|value| value.get(key).map(|v| !v.is_null()))
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn quick_options() -> StabilityOptions {
+        StabilityOptions {
+            stable_for: Duration::from_millis(20),
+            check_interval: Duration::from_millis(5),
+            timeout: Duration::from_millis(200),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_wait_for_stable_file() {
+        let temp_dir = tempdir().unwrap();
+        let path = temp_dir.path().join("meta.json");
+
+        // A missing file never becomes stable
+        assert!(!wait_for_complete_file(&path, &quick_options()));
+
+        fs::write(&path, r#"{"result": "done"}"#).unwrap();
+        assert!(wait_for_complete_file(&path, &quick_options()));
+    }
+
+    #[test]
+    fn test_wait_for_sentinel_and_required_key() {
+        let temp_dir = tempdir().unwrap();
+        let path = temp_dir.path().join("meta.json");
+        fs::write(&path, r#"{"segments": [], "result": null}"#).unwrap();
+
+        let options = StabilityOptions {
+            sentinel: Some("output.wav".to_string()),
+            ..quick_options()
+        };
+        assert!(!wait_for_complete_file(&path, &options));
+        fs::write(temp_dir.path().join("output.wav"), b"RIFF").unwrap();
+        assert!(wait_for_complete_file(&path, &options));
+
+        let options = StabilityOptions {
+            required_key: Some("result".to_string()),
+            ..quick_options()
+        };
+        assert!(!wait_for_complete_file(&path, &options));
+        fs::write(&path, r#"{"segments": [], "result": "Hello"}"#).unwrap();
+        assert!(wait_for_complete_file(&path, &options));
+    }
+}
@@ -1,14 +1,18 @@
-use crate::config::AppConfig;
+use crate::config::{AppConfig, TextCleaningOptions};
+use crate::meta::Segment;
 use log::warn;
 use regex::Regex;
 
 /// Apply text cleaning operations based on configuration
 pub fn apply_text_cleaning(text: &str, config: &AppConfig) -> String {
-    let text_cleaning = match &config.text_cleaning {
-        Some(options) => options,
-        None => return text.to_string(),
-    };
+    match &config.text_cleaning {
+        Some(options) => apply_cleaning_options(text, options),
+        None => text.to_string(),
+    }
+}
 
+/// Apply a set of text cleaning options
+pub fn apply_cleaning_options(text: &str, text_cleaning: &TextCleaningOptions) -> String {
     let mut result = text.to_string();
 
     // Trim whitespace if configured
@@ -107,10 +111,112 @@ pub fn process_text_segments(segments: &[crate::keyphrase::TextSegment]) -> Stri
     normalize_whitespace(&result)
 }
 
+/// How to build a transcript from timed segments
+#[derive(Debug, Clone, Default)]
+pub struct SegmentRenderOptions {
+    /// Put each segment on its own line, prefixed with its start time
+    pub timestamps: bool,
+    /// Start a new paragraph after a pause at least this long (seconds)
+    pub paragraph_gap: Option<f64>,
+}
+
+/// Subtitle file formats
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SubtitleFormat {
+    Srt,
+    Vtt,
+}
+
+impl SubtitleFormat {
+    /// File extension for the format
+    pub fn extension(&self) -> &'static str {
+        match self {
+            SubtitleFormat::Srt => "srt",
+            SubtitleFormat::Vtt => "vtt",
+        }
+    }
+}
+
+/// Build transcript text from timed segments
+pub fn render_segments(segments: &[Segment], options: &SegmentRenderOptions) -> String {
+    let mut paragraphs: Vec<Vec<String>> = Vec::new();
+    let mut previous_end: Option<f64> = None;
+
+    for segment in segments {
+        let text = segment.text.trim();
+        if text.is_empty() {
+            continue;
+        }
+
+        let long_pause = match (options.paragraph_gap, previous_end) {
+            (Some(gap), Some(end)) => segment.start - end >= gap,
+            _ => false,
+        };
+        if long_pause || paragraphs.is_empty() {
+            paragraphs.push(Vec::new());
+        }
+        previous_end = Some(segment.end);
+
+        let line = if options.timestamps {
+            format!("{} {}", format_timestamp(segment.start), text)
+        } else {
+            text.to_string()
+        };
+        paragraphs.last_mut().unwrap().push(line);
+    }
+
+    let separator = if options.timestamps { "\n" } else { " " };
+    paragraphs
+        .iter()
+        .map(|lines| lines.join(separator))
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+/// Format a time offset as `[mm:ss]`, or `[h:mm:ss]` past the first hour
+pub fn format_timestamp(seconds: f64) -> String {
+    let total = seconds.max(0.0) as u64;
+    let (hours, minutes, secs) = (total / 3600, (total % 3600) / 60, total % 60);
+    if hours > 0 {
+        format!("[{}:{:02}:{:02}]", hours, minutes, secs)
+    } else {
+        format!("[{:02}:{:02}]", minutes, secs)
+    }
+}
+
+/// Render segments as a subtitle file
+pub fn render_subtitles(segments: &[Segment], format: SubtitleFormat) -> String {
+    let mut output = String::new();
+    if format == SubtitleFormat::Vtt {
+        output.push_str("WEBVTT\n\n");
+    }
+
+    let cues = segments.iter().filter(|segment| !segment.text.trim().is_empty());
+    for (index, segment) in cues.enumerate() {
+        let (start, end) = match format {
+            SubtitleFormat::Srt => (subtitle_time(segment.start, ','), subtitle_time(segment.end, ',')),
+            SubtitleFormat::Vtt => (subtitle_time(segment.start, '.'), subtitle_time(segment.end, '.')),
+        };
+        if format == SubtitleFormat::Srt {
+            output.push_str(&format!("{}\n", index + 1));
+        }
+        output.push_str(&format!("{} --> {}\n{}\n\n", start, end, segment.text.trim()));
+    }
+
+    output
+}
+
+/// Format a time offset as `hh:mm:ss,mmm` (SRT) or `hh:mm:ss.mmm` (WebVTT)
+fn subtitle_time(seconds: f64, millis_separator: char) -> String {
+    let total_millis = (seconds.max(0.0) * 1000.0).round() as u64;
+    let (hours, minutes) = (total_millis / 3_600_000, (total_millis % 3_600_000) / 60_000);
+    let (secs, millis) = ((total_millis % 60_000) / 1000, total_millis % 1000);
+    format!("{:02}:{:02}:{:02}{}{:03}", hours, minutes, secs, millis_separator, millis)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::config::TextCleaningOptions;
 
     #[test]
     fn test_trim_whitespace() {
@@ -133,11 +239,10 @@ mod tests {
     fn test_capitalize_sentences() {
         assert_eq!(
             capitalize_sentences("hello. this is a test. another sentence!"),
-            "hello. This is a test. Another sentence!"
+            "Hello. This is a test. Another sentence!"
         );
     }
 
-    #[test]
     #[test]
     fn test_apply_text_cleaning() {
         // Create test config with all options enabled
@@ -149,20 +254,8 @@ mod tests {
         };
 
         let config = AppConfig {
-            watch_dir: None,
-            log_file: None,
-            log_level: None,
-            echo_to_stdout: None,
-            detect_keyphrases: None,
-            keyphrases: None,
-            dry_run: None,
-            clipboard_format: None,
             text_cleaning: Some(options),
-            disable_logs: None,
-            keyphrase_settings: None,
-            disable_clipboard: None,
-            mode_name: None,
-            result_field_preference: None,
+            ..Default::default()
         };
 
         let input = "  hello  world.\r\n  this is a test.  ";
@@ -190,4 +283,55 @@ mod tests {
 
         assert_eq!(process_text_segments(&segments), "Hello my notes are here.");
     }
+
+    fn segment(start: f64, end: f64, text: &str) -> Segment {
+        Segment {
+            start,
+            end,
+            text: text.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_render_segments() {
+        let segments = vec![
+            segment(0.0, 1.5, " Hello there."),
+            segment(1.6, 3.0, " How are you?"),
+            segment(12.0, 14.0, " Next topic."),
+        ];
+
+        let plain = SegmentRenderOptions {
+            timestamps: false,
+            paragraph_gap: Some(2.0),
+        };
+        assert_eq!(
+            render_segments(&segments, &plain),
+            "Hello there. How are you?\n\nNext topic."
+        );
+
+        let timed = SegmentRenderOptions {
+            timestamps: true,
+            paragraph_gap: None,
+        };
+        assert_eq!(
+            render_segments(&segments, &timed),
+            "[00:00] Hello there.\n[00:01] How are you?\n[00:12] Next topic."
+        );
+        assert_eq!(format_timestamp(3725.0), "[1:02:05]");
+    }
+
+    #[test]
+    fn test_render_subtitles() {
+        let segments = vec![segment(0.0, 1.5, " Hello."), segment(61.25, 62.0, "Bye.")];
+
+        assert_eq!(
+            render_subtitles(&segments, SubtitleFormat::Srt),
+            "1\n00:00:00,000 --> 00:00:01,500\nHello.\n\n2\n00:01:01,250 --> 00:01:02,000\nBye.\n\n"
+        );
+        assert_eq!(
+            render_subtitles(&segments, SubtitleFormat::Vtt),
+            "WEBVTT\n\n00:00:00.000 --> 00:00:01.500\nHello.\n\n00:01:01.250 --> 00:01:02.000\nBye.\n\n"
+        );
+    }
 }
@@ -1,79 +1,136 @@
-use crate::config::AppState;
-use crate::config::{parse_keyphrase_matching_strategy, parse_punctuation_handling};
-use crate::keyphrase::{KeyphraseAction, KeyphraseProcessingOptions};
-use crate::meta_processor::LastProcessedMap;
-use log::{debug, error, info};
+use crate::backend::start_profile_watcher;
+use crate::backfill::{parse_backfill_cutoff, record_last_run, resolve_cutoff, run_backfill};
+use crate::config::{get_state_directory, parse_overflow_policy, AppState, Opts, CONFIG_FILE};
+use crate::ledger::open_configured_ledger;
+use crate::meta_processor::{debounce, process_meta_file, sink_key, LastProcessedMap, ProcessJob, ProcessQueue};
+use crate::profile::{log_profiles, profile_for_path};
+use crate::reload::{current_runtime, watch_config_file, Runtime, SharedRuntime};
+use crate::queue::{JobQueue, OverflowPolicy, ShutdownReport, DEFAULT_DEPTH, DEFAULT_SHUTDOWN_TIMEOUT, DEFAULT_WORKERS};
+use log::{debug, error, info, warn};
 use notify::{
-    Config, EventKind, RecommendedWatcher, RecursiveMode, Result as NotifyResult, Watcher,
+    EventKind, Result as NotifyResult,
     event::{AccessKind, AccessMode},
 };
-use std::path::{Path, PathBuf};
-use std::sync::{Arc, Mutex};
-use std::thread;
-use std::time::{Duration, Instant};
+use std::path::Path;
+use std::sync::mpsc::{self, Sender};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant, SystemTime};
 use std::collections::HashMap;
 
-/// Start watching a directory for meta.json files
-pub fn start_watcher(app_state: AppState) -> NotifyResult<()> {
-    // Get the watch path from configuration
-    let watch_path = match &app_state.config.watch_dir {
-        Some(dir) => PathBuf::from(dir),
-        None => {
-            error!("No watch directory specified");
-            return Err(notify::Error::generic("No watch directory specified"));
-        }
-    };
+/// Start watching the configured directories for meta.json files
+///
+/// `opts` are the command line options `app_state` was loaded with; config reloads apply them again.
+pub fn start_watcher(app_state: AppState, opts: Opts) -> NotifyResult<()> {
+    // Resolve the watch directories, their processing profiles and keyphrase options
+    let runtime = Runtime::new(app_state, opts).map_err(|e| {
+        error!("{}", e);
+        notify::Error::generic(&e)
+    })?;
+    log_profiles(&runtime.profiles);
+    let app_state = runtime.app_state.clone();
 
-    info!("Watching for meta.json in {:?}", watch_path);
 	if !app_state.config.disable_clipboard.unwrap_or(false) {
 		info!("Any LLM results will be copied to your clipboard");
 	} else {
 		info!("Clipboard operations disabled, LLM results will be processed but not copied to clipboard");
 	}
-    // Display result field preference
-    if let Some(pref) = &app_state.config.result_field_preference {
-        info!("Using result field preference: {}", pref);
-    } else {
-        info!("Using default result field preference: auto");
-    }
 
     // Create shared state
-    let app_state = Arc::new(app_state);
+    let profiles = runtime.profiles.clone();
+    let runtime: SharedRuntime = Arc::new(RwLock::new(Arc::new(runtime)));
     let last_processed = Arc::new(Mutex::new(HashMap::new()));
+    let ledger = Arc::new(Mutex::new(open_configured_ledger(&app_state.config)));
 
-    // Parse keyphrases and keyphrase options
-    let (keyphrases, keyphrase_options) = parse_keyphrases_from_config(&app_state);
-    let keyphrases = Arc::new(keyphrases);
-    let keyphrase_options = Arc::new(keyphrase_options);
-
-    // Clone references for the watcher closure
-    let last_processed_clone = last_processed.clone();
-    let keyphrases_clone = keyphrases.clone();
-    let keyphrase_options_clone = keyphrase_options.clone();
-    let app_state_clone = app_state.clone();
-
-    // Create and configure the file watcher
-    let mut watcher: RecommendedWatcher = RecommendedWatcher::new(
-        move |res: NotifyResult<notify::Event>| match res {
-            Ok(e) => handle_event(
-                &e,
-                &last_processed_clone,
-                &keyphrases_clone,
-                &keyphrase_options_clone,
-                &app_state_clone,
-            ),
-            Err(e) => error!("Watch error: {:?}", e),
-        },
-        Config::default(),
-    )?;
+    // Files are processed on worker threads so slow retries and clipboard delays
+    // don't hold up the notify callback
+    let queue = {
+        let ledger = ledger.clone();
+        let runtime = runtime.clone();
+        let (workers, depth, overflow) = queue_settings(&app_state);
+        info!(
+            "Processing files on {} workers (queue depth {}, overflow {:?})",
+            workers, depth, overflow
+        );
+        Arc::new(JobQueue::new(workers, depth, overflow, move |job: ProcessJob| {
+            // Pick up the latest settings in case the config was reloaded
+            let runtime = current_runtime(&runtime);
+            process_meta_file(&job.path, &job.profile, &ledger, &runtime.keyphrase_options, &runtime.app_state)
+        }))
+    };
+
+    // Start a watcher for each directory; each gets its own clones of the shared state
+    let mut watchers = Vec::with_capacity(profiles.len());
+    for profile in profiles.iter() {
+        let make_handler = |polling: bool| {
+            let runtime = runtime.clone();
+            let last_processed = last_processed.clone();
+            let queue = queue.clone();
+
+            move |res: NotifyResult<notify::Event>| match res {
+                Ok(e) => handle_event(&e, polling, &current_runtime(&runtime), &last_processed, &queue),
+                Err(e) => error!("Watch error: {:?}", e),
+            }
+        };
 
-    // Start watching the directory
-    watcher.watch(&watch_path, RecursiveMode::Recursive)?;
-    info!("File watcher initialized successfully");
+        watchers.push(start_profile_watcher(profile, make_handler)?);
+    }
+    // Report the backend each directory ended up with, since `auto` decides at startup
+    let backends: Vec<String> = profiles
+        .iter()
+        .zip(&watchers)
+        .map(|(profile, watcher)| format!("{}: {:?}", profile.name, watcher.backend))
+        .collect();
+    info!(
+        "File watcher initialized successfully for {} directories ({})",
+        watchers.len(),
+        backends.join(", ")
+    );
+
+    // Pick up keyphrase, cleaning and clipboard changes without a restart
+    let config_watcher = watch_config_file(Path::new(CONFIG_FILE), runtime.clone())
+        .map_err(|e| warn!("Could not watch {} for changes, edits need a restart: {}", CONFIG_FILE, e))
+        .ok();
+
+    // Listen for Ctrl-C and SIGTERM before the backfill, which can queue a long backlog
+    let (shutdown_tx, shutdown_rx) = mpsc::channel();
+    let shutdown = Arc::new(AtomicBool::new(false));
+    install_shutdown_handler(shutdown_tx, shutdown.clone());
+
+    // Catch up on recordings that arrived while we were not running. The watcher is
+    // already live, so anything landing during the scan is covered by the debounce map.
+    let state_dir = get_state_directory(&app_state.config);
+    if let Some(backfill) = app_state.config.backfill.as_ref().filter(|b| b.enabled) {
+        let since = backfill.since.as_deref().unwrap_or("last_run");
+        let cutoff = parse_backfill_cutoff(since, SystemTime::now()).map_err(|e| {
+            error!("{}", e);
+            notify::Error::generic(&e)
+        })?;
 
-    // Keep alive and clean up old entries periodically
-    loop {
-        thread::sleep(Duration::from_secs(60));
+        if let Some(cutoff_time) = resolve_cutoff(&cutoff, &state_dir) {
+            info!("Backfilling files modified since {}", humantime::format_rfc3339_seconds(cutoff_time));
+            let count: usize = profiles
+                .iter()
+                .map(|profile| {
+                    run_backfill(profile, cutoff_time, &last_processed, &queue, &app_state, &shutdown)
+                })
+                .sum();
+            if shutdown.load(Ordering::SeqCst) {
+                info!("Backfill stopped by shutdown after queueing {} files", count);
+            } else {
+                info!("Backfill complete, queued {} files", count);
+            }
+        }
+    }
+    // An interrupted backfill leaves the last run time alone, so the next start picks up the rest
+    let backfill_interrupted = shutdown.load(Ordering::SeqCst);
+    if !backfill_interrupted {
+        update_last_run(&state_dir, &queue);
+    }
+
+    // Keep alive and clean up old entries periodically until asked to stop
+    while shutdown_rx.recv_timeout(Duration::from_secs(60)).is_err() {
+        update_last_run(&state_dir, &queue);
 
         // Cleanup old entries from the debounce map
         let mut map = last_processed.lock().unwrap();
@@ -88,49 +145,102 @@ pub fn start_watcher(app_state: AppState) -> NotifyResult<()> {
             );
         }
     }
-}
 
-/// Parse keyphrases and keyphrase processing options from the application configuration
-fn parse_keyphrases_from_config(app_state: &Arc<AppState>) -> (Vec<KeyphraseAction>, KeyphraseProcessingOptions) {
-    use crate::keyphrase::parse_keyphrases;
-    let keyphrases = parse_keyphrases(&app_state.config);
-
-    // Log keyphrase configuration
-    if app_state.config.detect_keyphrases.unwrap_or(false) {
-        info!("Keyphrase detection enabled");
-        debug!("Configured {} keyphrases", keyphrases.len());
-        for ka in &keyphrases {
-            debug!("Keyphrase: \"{}\"", ka.keyphrase);
+    // Stop accepting new events, then give queued files a chance to finish
+    info!("Shutdown requested, stopping file watchers");
+    drop(config_watcher);
+    drop(watchers);
+    let timeout = app_state
+        .config
+        .queue
+        .as_ref()
+        .and_then(|q| q.shutdown_timeout_secs)
+        .map(Duration::from_secs)
+        .unwrap_or(DEFAULT_SHUTDOWN_TIMEOUT);
+    let report = queue.shutdown(timeout);
+
+    // Flush persistent state and logs
+    match ledger.lock() {
+        Ok(mut ledger) => {
+            if let Err(e) = ledger.save() {
+                warn!("Could not save ledger: {}", e);
+            }
         }
-    } else {
-        debug!("Keyphrase detection disabled");
+        Err(_) => warn!("Ledger lock poisoned, skipping final save"),
     }
+    update_last_run_at_shutdown(&state_dir, &queue, report, backfill_interrupted);
+    log::logger().flush();
 
-    // Parse keyphrase processing options
-    let mut options = KeyphraseProcessingOptions::default();
-    
-    if let Some(keyphrase_settings) = &app_state.config.keyphrase_settings {
-        if let Some(strategy) = &keyphrase_settings.matching_strategy {
-            options.matching_strategy = parse_keyphrase_matching_strategy(strategy);
-            debug!("Using keyphrase matching strategy: {:?}", options.matching_strategy);
-        }
-        
-        if let Some(handling) = &keyphrase_settings.punctuation_handling {
-            options.punctuation_handling = parse_punctuation_handling(handling);
-            debug!("Using punctuation handling: {:?}", options.punctuation_handling);
-        }
+    Ok(())
+}
+
+/// Send on `shutdown_tx` when the process receives Ctrl-C or SIGTERM
+fn install_shutdown_handler(shutdown_tx: Sender<()>, shutdown: Arc<AtomicBool>) {
+    let result = ctrlc::set_handler(move || {
+        shutdown.store(true, Ordering::SeqCst);
+        let _ = shutdown_tx.send(());
+    });
+
+    if let Err(e) = result {
+        warn!("Could not install signal handler, graceful shutdown is unavailable: {}", e);
     }
+}
 
-    (keyphrases, options)
+/// Record that we are still running so a later backfill knows where to start
+///
+/// Only once every queued file has been processed: files still queued or running, or
+/// cancelled at shutdown, would otherwise fall before the next backfill's cutoff. After
+/// the queue has dropped a file the time is left alone for the rest of the run.
+fn update_last_run<T: Send + 'static>(state_dir: &Path, queue: &JobQueue<T>) {
+    if !queue.is_idle() || queue.dropped() > 0 {
+        debug!("Files are waiting or were dropped, keeping the previous last run time");
+        return;
+    }
+    if let Err(e) = record_last_run(state_dir) {
+        warn!("Could not record last run time in {}: {}", state_dir.display(), e);
+    }
+}
+
+/// Record the last run time on the way out, unless a file was left unfinished
+///
+/// Files cancelled at shutdown and ones still running when the timeout expired are
+/// left for the next backfill, even if a running one finishes in the meantime.
+fn update_last_run_at_shutdown<T: Send + 'static>(
+    state_dir: &Path,
+    queue: &JobQueue<T>,
+    report: ShutdownReport,
+    backfill_interrupted: bool,
+) {
+    if !report.is_complete() {
+        info!(
+            "{} files were cancelled and {} still running, the next backfill will pick them up",
+            report.cancelled, report.running
+        );
+    } else if !backfill_interrupted {
+        update_last_run(state_dir, queue);
+    }
+}
+
+/// Read the worker queue settings from the application configuration
+fn queue_settings(app_state: &AppState) -> (usize, usize, OverflowPolicy) {
+    let queue = app_state.config.queue.as_ref();
+    let workers = queue.and_then(|q| q.workers).unwrap_or(DEFAULT_WORKERS);
+    let depth = queue.and_then(|q| q.depth).unwrap_or(DEFAULT_DEPTH);
+    let overflow = queue
+        .and_then(|q| q.overflow.as_deref())
+        .map(parse_overflow_policy)
+        .unwrap_or(OverflowPolicy::Block);
+
+    (workers.max(1), depth.max(1), overflow)
 }
 
 /// Handle file system events
 fn handle_event(
     evt: &notify::Event,
+    polling: bool,
+    runtime: &Runtime,
     last_processed: &LastProcessedMap,
-    keyphrases: &[KeyphraseAction],
-    keyphrase_options: &KeyphraseProcessingOptions,
-    app_state: &Arc<AppState>,
+    queue: &ProcessQueue,
 ) {
     // Track if we should process any files in this event
     let mut process_files = false;
@@ -152,6 +262,13 @@ fn handle_event(
             paths_to_process.extend(evt.paths.iter().cloned());
         },
         
+        // The poll backend reports rewritten files as modifications rather than closes
+        EventKind::Modify(_) if polling => {
+            debug!("Modify event detected by polling for {:?}", evt.paths);
+            process_files = true;
+            paths_to_process.extend(evt.paths.iter().cloned());
+        },
+        
         // Ignore other event types
         _ => {}
     }
@@ -159,17 +276,97 @@ fn handle_event(
     // Process any identified files
     if process_files {
         for path in &paths_to_process {
-            if is_meta_json_file(path) {
-                debug!("Processing meta.json file after write completion: {:?}", path);
-                crate::meta_processor::process_meta_file(path, last_processed, keyphrases, keyphrase_options, app_state);
+            let (profile, path) = match profile_for_path(&runtime.profiles, path) {
+                Some(found) => found,
+                None => {
+                    debug!("Ignoring event outside any watch directory: {:?}", path);
+                    continue;
+                }
+            };
+
+            if profile.matches_file(&path) && debounce(&path, last_processed) {
+                debug!("Queueing {} file after write completion: {:?}", profile.pattern, path);
+                let job = ProcessJob {
+                    path,
+                    profile: profile.clone(),
+                };
+                queue.submit(&sink_key(profile, &runtime.app_state), job);
             }
         }
     }
 }
 
-/// Check if a path is a meta.json file
-fn is_meta_json_file(path: &Path) -> bool {
-    path.file_name()
-        .and_then(|s| s.to_str())
-        .map_or(false, |s| s == "meta.json")
-}
\ No newline at end of file
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backfill::read_last_run;
+    use crate::queue::OverflowPolicy;
+    use std::thread;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_last_run_kept_after_cancelled_shutdown() {
+        let temp_dir = tempdir().unwrap();
+        let state_dir = temp_dir.path();
+        std::fs::write(state_dir.join("last_run"), "1000").unwrap();
+
+        let (release_tx, release_rx) = mpsc::channel::<()>();
+        let release_rx = Mutex::new(release_rx);
+        let queue = JobQueue::new(1, 8, OverflowPolicy::Block, move |n: u32| {
+            if n == 0 {
+                release_rx.lock().unwrap().recv().unwrap();
+            }
+        });
+
+        queue.submit("clipboard", 0);
+        while queue.pending() > 0 {
+            thread::sleep(Duration::from_millis(5));
+        }
+        queue.submit("clipboard", 1);
+        queue.submit("clipboard", 2);
+
+        // Job 0 is stuck, so jobs 1 and 2 are cancelled
+        assert_eq!(queue.shutdown(Duration::from_millis(100)).cancelled, 2);
+        release_tx.send(()).unwrap();
+        while !queue.is_idle() {
+            thread::sleep(Duration::from_millis(5));
+        }
+
+        // Idle again, but the cancelled files must stay after the backfill cutoff
+        update_last_run(state_dir, &queue);
+        assert_eq!(read_last_run(state_dir), Some(SystemTime::UNIX_EPOCH + Duration::from_secs(1000)));
+    }
+
+    #[test]
+    fn test_last_run_kept_after_shutdown_with_running_job() {
+        let temp_dir = tempdir().unwrap();
+        let state_dir = temp_dir.path();
+        std::fs::write(state_dir.join("last_run"), "1000").unwrap();
+
+        let (release_tx, release_rx) = mpsc::channel::<()>();
+        let release_rx = Mutex::new(release_rx);
+        let queue = JobQueue::new(1, 8, OverflowPolicy::Block, move |_: u32| {
+            release_rx.lock().unwrap().recv().unwrap();
+        });
+
+        queue.submit("clipboard", 0);
+        while queue.pending() > 0 {
+            thread::sleep(Duration::from_millis(5));
+        }
+
+        // Nothing is cancelled, but the job is still running when the timeout expires
+        let report = queue.shutdown(Duration::from_millis(100));
+        assert_eq!(report, ShutdownReport { cancelled: 0, running: 1 });
+        release_tx.send(()).unwrap();
+        while !queue.is_idle() {
+            thread::sleep(Duration::from_millis(5));
+        }
+
+        update_last_run_at_shutdown(state_dir, &queue, report, false);
+        assert_eq!(read_last_run(state_dir), Some(SystemTime::UNIX_EPOCH + Duration::from_secs(1000)));
+
+        // A clean shutdown moves it forward
+        update_last_run_at_shutdown(state_dir, &queue, ShutdownReport::default(), false);
+        assert!(read_last_run(state_dir) > Some(SystemTime::UNIX_EPOCH + Duration::from_secs(1000)));
+    }
+}